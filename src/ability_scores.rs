@@ -0,0 +1,179 @@
+use crate::components::*;
+use bevy::prelude::*;
+use rand::Rng;
+use strum::{Display, EnumIter};
+
+pub const POINT_BUY_BUDGET: i64 = 27;
+pub const STANDARD_ARRAY: [f64; 6] = [15., 14., 13., 12., 10., 8.];
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Display, EnumIter)]
+pub enum AbilityMethod {
+    #[default]
+    #[strum(to_string = "Point Buy")]
+    PointBuy,
+    #[strum(to_string = "Standard Array")]
+    StandardArray,
+    #[strum(to_string = "Roll 4d6")]
+    Roll,
+    Manual,
+}
+
+/// Base ability scores, before racial bonuses, in `StatEnum::ABILITIES` order.
+///
+/// `assigned` holds the index into the standard array (or into `rolls`) given
+/// to each ability, so each value can only be used once.
+#[derive(Resource, Clone)]
+pub struct AbilityScores {
+    pub method: AbilityMethod,
+    pub scores: [f64; 6],
+    pub assigned: [Option<usize>; 6],
+    pub rolls: Vec<[i64; 4]>,
+}
+
+impl Default for AbilityScores {
+    fn default() -> Self {
+        Self {
+            method: AbilityMethod::PointBuy,
+            scores: [8.; 6],
+            assigned: [None; 6],
+            rolls: Vec::new(),
+        }
+    }
+}
+
+impl AbilityScores {
+    pub fn set_method(&mut self, method: AbilityMethod) {
+        *self = Self {
+            method,
+            scores: match method {
+                AbilityMethod::PointBuy => [8.; 6],
+                _ => [10.; 6],
+            },
+            ..default()
+        };
+    }
+
+    pub fn get(&self, ability: &StatEnum) -> f64 {
+        StatEnum::ABILITIES
+            .iter()
+            .position(|x| x == ability)
+            .map(|i| self.scores[i])
+            .unwrap_or(10.)
+    }
+
+    /// Points left over from the 27 point budget.
+    pub fn points_remaining(&self) -> i64 {
+        POINT_BUY_BUDGET
+            - self
+                .scores
+                .iter()
+                .map(|x| point_buy_cost(*x).unwrap_or(POINT_BUY_BUDGET))
+                .sum::<i64>()
+    }
+
+    /// The values the current method lets the player assign, or None when the
+    /// scores are entered directly.
+    pub fn pool(&self) -> Option<Vec<f64>> {
+        match self.method {
+            AbilityMethod::StandardArray => Some(STANDARD_ARRAY.to_vec()),
+            AbilityMethod::Roll => Some(self.rolls.iter().map(|x| drop_lowest(x)).collect()),
+            _ => None,
+        }
+    }
+
+    /// Gives ability `i` the pool value at `slot`, taking it away from any
+    /// other ability that had it.
+    pub fn assign(&mut self, i: usize, slot: Option<usize>) {
+        let Some(pool) = self.pool() else { return };
+        if slot.is_some() {
+            for (j, other) in self.assigned.iter_mut().enumerate() {
+                if j != i && *other == slot {
+                    *other = None;
+                    self.scores[j] = 0.;
+                }
+            }
+        }
+        self.assigned[i] = slot;
+        self.scores[i] = slot.and_then(|x| pool.get(x).copied()).unwrap_or(0.);
+    }
+
+    /// Rolls six sets of 4d6, keeping every die in the log.
+    pub fn roll(&mut self, rng: &mut impl Rng) {
+        self.rolls = (0..6)
+            .map(|_| {
+                let mut dice = [0; 4];
+                for die in dice.iter_mut() {
+                    *die = rng.gen_range(1..=6);
+                }
+                dice
+            })
+            .collect();
+        self.assigned = [None; 6];
+        self.scores = [0.; 6];
+    }
+
+    /// Whether every ability has a legal score for the chosen method.
+    pub fn is_complete(&self) -> bool {
+        match self.method {
+            AbilityMethod::PointBuy => {
+                self.scores.iter().all(|x| point_buy_cost(*x).is_some())
+                    && self.points_remaining() >= 0
+            }
+            AbilityMethod::StandardArray | AbilityMethod::Roll => {
+                self.assigned.iter().all(|x| x.is_some())
+            }
+            AbilityMethod::Manual => self
+                .scores
+                .iter()
+                .all(|x| (1. ..=30.).contains(x) && x.fract() == 0.),
+        }
+    }
+
+    pub fn roll_log(&self) -> AbilityRolls {
+        AbilityRolls(self.rolls.iter().map(|x| x.to_vec()).collect())
+    }
+}
+
+/// Point buy cost of a score, or None if it can't be bought.
+pub fn point_buy_cost(score: f64) -> Option<i64> {
+    if score.fract() != 0. {
+        return None;
+    }
+    match score as i64 {
+        8 => Some(0),
+        9 => Some(1),
+        10 => Some(2),
+        11 => Some(3),
+        12 => Some(4),
+        13 => Some(5),
+        14 => Some(7),
+        15 => Some(9),
+        _ => None,
+    }
+}
+
+pub fn drop_lowest(dice: &[i64; 4]) -> f64 {
+    (dice.iter().sum::<i64>() - dice.iter().min().unwrap_or(&0)) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_must_be_whole_numbers() {
+        assert_eq!(point_buy_cost(14.), Some(7));
+        assert_eq!(point_buy_cost(14.5), None);
+        assert_eq!(point_buy_cost(7.9), None);
+
+        let mut scores = AbilityScores::default();
+        assert!(scores.is_complete());
+        scores.scores[0] = 8.5;
+        assert!(!scores.is_complete());
+
+        scores.set_method(AbilityMethod::Manual);
+        assert!(scores.is_complete());
+        scores.scores[0] = 12.25;
+        assert!(!scores.is_complete());
+    }
+}
@@ -0,0 +1,265 @@
+//! Area-of-effect templates for spells and abilities: spheres, cylinders,
+//! cones, lines and cubes laid on the battle map, and the saving throws of
+//! everyone caught in one.
+
+use crate::combat::TakeDamage;
+use crate::components::*;
+use crate::map::{blocked, center, BattleMap, GridPosition, CELL_FEET};
+use crate::RulesRng;
+use bevy::ecs::query::QueryItem;
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+pub struct AreasPlugin;
+
+impl Plugin for AreasPlugin {
+    fn build(&self, app: &mut App) {
+        app.observe(resolve_area_effect);
+    }
+}
+
+/// The shape of an area of effect, in feet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    /// Everything within `radius` of the point of origin.
+    Sphere {
+        radius: f64,
+    },
+    /// A sphere as far as the flat map is concerned.
+    Cylinder {
+        radius: f64,
+    },
+    /// As wide at any point as it is far from the point of origin.
+    Cone {
+        length: f64,
+    },
+    Line {
+        length: f64,
+        width: f64,
+    },
+    /// The point of origin is in the middle of one face.
+    Cube {
+        size: f64,
+    },
+}
+
+impl Shape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Sphere { .. } => "Sphere",
+            Shape::Cylinder { .. } => "Cylinder",
+            Shape::Cone { .. } => "Cone",
+            Shape::Line { .. } => "Line",
+            Shape::Cube { .. } => "Cube",
+        }
+    }
+
+    /// Whether the shape points somewhere, so turning it changes anything.
+    pub fn has_facing(&self) -> bool {
+        !matches!(self, Shape::Sphere { .. } | Shape::Cylinder { .. })
+    }
+}
+
+/// A shape placed on the map. Points are in squares, with square (x, y)
+/// spanning x to x + 1 and y to y + 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Template {
+    pub shape: Shape,
+    /// Usually a corner where squares meet.
+    pub origin: Vec2,
+    /// The direction the shape points in, in radians from the x axis.
+    pub facing: f32,
+}
+
+impl Template {
+    pub fn new(shape: Shape, origin: Vec2) -> Self {
+        Self {
+            shape,
+            origin,
+            facing: 0.,
+        }
+    }
+
+    /// Turns the template by `angle` radians.
+    pub fn rotate(&mut self, angle: f32) {
+        self.facing = (self.facing + angle).rem_euclid(TAU);
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let feet = |x: f64| (x / CELL_FEET) as f32;
+        let offset = point - self.origin;
+        let direction = Vec2::from_angle(self.facing);
+        let along = offset.dot(direction);
+        let across = offset.perp_dot(direction).abs();
+        match self.shape {
+            Shape::Sphere { radius } | Shape::Cylinder { radius } => {
+                offset.length() <= feet(radius)
+            }
+            Shape::Cone { length } => along >= 0. && along <= feet(length) && across <= along / 2.,
+            Shape::Line { length, width } => {
+                along >= 0. && along <= feet(length) && across <= feet(width) / 2.
+            }
+            Shape::Cube { size } => along >= 0. && along <= feet(size) && across <= feet(size) / 2.,
+        }
+    }
+
+    /// The squares the template affects: those at least half inside it,
+    /// going by the DMG's grid rules, with an unblocked line from the point
+    /// of origin to their middle.
+    pub fn cells(&self, map: &BattleMap) -> Vec<GridPosition> {
+        const SAMPLES: i32 = 4;
+        let step = 1. / SAMPLES as f32;
+        let mut cells = Vec::new();
+        for x in 0..map.width {
+            for y in 0..map.height {
+                let pos = GridPosition::new(x, y);
+                let inside = (0..SAMPLES * SAMPLES)
+                    .filter(|i| {
+                        let sample =
+                            Vec2::new((i % SAMPLES) as f32 + 0.5, (i / SAMPLES) as f32 + 0.5)
+                                * step;
+                        self.contains(Vec2::new(x as f32, y as f32) + sample)
+                    })
+                    .count();
+                if inside * 2 >= (SAMPLES * SAMPLES) as usize
+                    && !map.blocks(pos)
+                    && !blocked(map, self.origin, center(pos))
+                {
+                    cells.push(pos);
+                }
+            }
+        }
+        cells
+    }
+
+    /// The units standing on squares the template affects.
+    pub fn units<'a>(
+        &self,
+        map: &BattleMap,
+        units: impl IntoIterator<Item = (Entity, &'a GridPosition)>,
+    ) -> Vec<Entity> {
+        let cells = self.cells(map);
+        units
+            .into_iter()
+            .filter(|x| cells.contains(x.1))
+            .map(|x| x.0)
+            .collect()
+    }
+}
+
+/// A spell or ability going off over an area. Everyone in it makes a saving
+/// throw against `dc`, and takes the damage in full on a failure, halved
+/// again if they resist `damage_type`.
+#[derive(Event, Debug, Clone)]
+pub struct AreaEffect {
+    pub template: Template,
+    /// One of `StatEnum::ABILITIES`.
+    pub save: StatEnum,
+    pub dc: i64,
+    /// Rolled once for everyone.
+    pub damage: Dice,
+    pub damage_type: DamageType,
+    /// Whether a successful save takes half the damage, rather than none.
+    pub half_on_save: bool,
+}
+
+/// One unit's saving throw against an `AreaEffect`, sent once it's
+/// resolved.
+#[derive(Event, Debug, Clone)]
+pub struct SaveResult {
+    pub unit: Entity,
+    /// The d20 kept, the better of two with `advantage`.
+    pub roll: i64,
+    pub advantage: bool,
+    pub total: i64,
+    pub saved: bool,
+    pub damage: f64,
+}
+
+pub type Saver = (
+    &'static Strength,
+    &'static Constitution,
+    &'static Dexterity,
+    &'static Intelligence,
+    &'static Wisdom,
+    &'static Charisma,
+    Option<&'static ProficiencyBonus>,
+);
+
+/// The modifier a unit adds to saving throws of `save`, or None if that
+/// isn't an ability.
+pub fn save_modifier(
+    (str, con, dex, int, wis, cha, prof_bonus): QueryItem<Saver>,
+    save: &StatEnum,
+) -> Option<i64> {
+    let ability = match save {
+        StatEnum::Strength => &str.0,
+        StatEnum::Constitution => &con.0,
+        StatEnum::Dexterity => &dex.0,
+        StatEnum::Intelligence => &int.0,
+        StatEnum::Wisdom => &wis.0,
+        StatEnum::Charisma => &cha.0,
+        _ => return None,
+    };
+    let bonus = prof_bonus.map(|x| x.0).unwrap_or(0);
+    Some(ability.calculate_modifier() as i64 + ability.proficiency.bonus(bonus))
+}
+
+fn resolve_area_effect(
+    trigger: Trigger<AreaEffect>,
+    mut commands: Commands,
+    mut rng: ResMut<RulesRng>,
+    map: Res<BattleMap>,
+    units: Query<(Entity, &GridPosition), With<Unit>>,
+    savers: Query<Saver>,
+    defenses: Query<(Option<&Resistances>, Option<&SaveAdvantages>, Has<Lucky>)>,
+) {
+    let event = trigger.event();
+    let damage = event.damage.roll(&mut rng.0);
+    for unit in event.template.units(&map, &units) {
+        let Some(modifier) = savers
+            .get(unit)
+            .ok()
+            .and_then(|x| save_modifier(x, &event.save))
+        else {
+            warn!("{unit:?} can't make a {:?} save", event.save);
+            continue;
+        };
+        let (resistances, advantages, lucky) = defenses.get(unit).unwrap_or_default();
+        let resisted = resistances.is_some_and(|x| x.0.contains(&event.damage_type));
+        let advantage = advantages.is_some_and(|x| x.0.contains(&event.damage_type));
+        let roll = match advantage {
+            true => rng.d20(lucky).max(rng.d20(lucky)),
+            false => rng.d20(lucky),
+        };
+        let total = roll + modifier;
+        let saved = total >= event.dc;
+        let taken = match (saved, event.half_on_save) {
+            (false, _) => damage,
+            (true, true) => damage / 2,
+            (true, false) => 0,
+        };
+        let taken = match resisted {
+            true => taken / 2,
+            false => taken,
+        };
+        info!(
+            "{:?} save: rolled {roll}, {total} against DC {}, {taken} {} damage",
+            event.save, event.dc, event.damage_type
+        );
+        if taken > 0 {
+            commands.trigger(TakeDamage {
+                unit,
+                amount: taken as f64,
+            });
+        }
+        commands.trigger(SaveResult {
+            unit,
+            roll,
+            advantage,
+            total,
+            saved,
+            damage: taken as f64,
+        });
+    }
+}
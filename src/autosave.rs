@@ -0,0 +1,71 @@
+use crate::components::*;
+use crate::saves::{PendingSaves, SaveGame};
+use crate::AppState;
+use bevy::prelude::*;
+
+/// Seconds of play between timed autosaves.
+pub const AUTOSAVE_INTERVAL: f32 = 300.;
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AutosaveTimer>();
+        app.observe(autosave);
+        app.add_systems(
+            Update,
+            (tick_autosave_timer, autosave_on_level_up).run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutosaveReason {
+    Interval,
+    CombatEnded,
+    Rest,
+    LevelUp,
+}
+
+/// Saves the game to its current slot, unless a save is still being
+/// written.
+#[derive(Event, Debug, Clone)]
+pub struct Autosave(pub AutosaveReason);
+
+#[derive(Resource)]
+pub struct AutosaveTimer(pub Timer);
+
+impl Default for AutosaveTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(AUTOSAVE_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+fn autosave(
+    trigger: Trigger<Autosave>,
+    mut commands: Commands,
+    pending: Res<PendingSaves>,
+    mut timer: ResMut<AutosaveTimer>,
+) {
+    // A second write to the slot would race the first one's backups.
+    if !pending.0.is_empty() {
+        info!("Skipping autosave, a save is still being written");
+        return;
+    }
+    info!("Autosaving: {:?}", trigger.event().0);
+    timer.0.reset();
+    commands.add(SaveGame);
+}
+
+fn tick_autosave_timer(time: Res<Time>, mut timer: ResMut<AutosaveTimer>, mut commands: Commands) {
+    if timer.0.tick(time.delta()).just_finished() {
+        commands.trigger(Autosave(AutosaveReason::Interval));
+    }
+}
+
+fn autosave_on_level_up(mut commands: Commands, levels: Query<Ref<Level>, With<Player>>) {
+    // Loading a save adds the level, it doesn't raise it.
+    if levels.iter().any(|x| x.is_changed() && !x.is_added()) {
+        commands.trigger(Autosave(AutosaveReason::LevelUp));
+    }
+}
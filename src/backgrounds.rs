@@ -0,0 +1,201 @@
+use crate::classes::ToolChoice;
+use crate::components::*;
+use crate::items::SetProficiency;
+use bevy::{ecs::world::Command, prelude::*};
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+pub struct BackgroundsPlugin;
+
+impl Plugin for BackgroundsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BackgroundCatalog::from_ron(include_str!(
+            "../assets/data/backgrounds.ron"
+        )));
+        app.register_type::<Background>();
+        app.register_type::<BackgroundFeature>();
+        app.register_type::<Personality>();
+        app.register_type::<Gold>();
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct BackgroundCatalog(pub Vec<BackgroundData>);
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BackgroundData {
+    pub background: Background,
+    pub skills: Vec<StatEnum>,
+    pub tools: Vec<String>,
+    pub tool_choices: Option<ToolChoice>,
+    pub extra_languages: usize,
+    pub equipment: Vec<String>,
+    pub gold: i64,
+    pub feature: BackgroundFeatureData,
+    pub personality_traits: Vec<String>,
+    pub ideals: Vec<String>,
+    pub bonds: Vec<String>,
+    pub flaws: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct BackgroundFeatureData {
+    pub name: String,
+    pub description: String,
+}
+
+impl BackgroundCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("backgrounds.ron to be a valid background catalog"))
+    }
+
+    pub fn get(&self, background: &Background) -> Option<&BackgroundData> {
+        let data = self.0.iter().find(|x| &x.background == background);
+        if data.is_none() {
+            warn!("No background definition for {background}");
+        }
+        data
+    }
+}
+
+impl BackgroundData {
+    /// Background skills the character already gets from somewhere else.
+    /// Each of these may be swapped for another skill of the player's choice.
+    pub fn overlapping_skills(&self, taken: &[StatEnum]) -> Vec<StatEnum> {
+        self.skills
+            .iter()
+            .filter(|x| taken.contains(x))
+            .cloned()
+            .collect()
+    }
+
+    /// Rolls on the personality tables: two distinct traits, and one each of
+    /// ideal, bond and flaw.
+    pub fn roll_personality(&self, rng: &mut impl Rng) -> Personality {
+        Personality {
+            traits: self
+                .personality_traits
+                .choose_multiple(rng, 2)
+                .cloned()
+                .collect(),
+            ideal: self.ideals.choose(rng).cloned().unwrap_or_default(),
+            bond: self.bonds.choose(rng).cloned().unwrap_or_default(),
+            flaw: self.flaws.choose(rng).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// Choices a background leaves up to the player, made in the new character
+/// UI. `replacements` maps a background skill the character already has to
+/// the skill picked in its place.
+#[derive(Resource, Default, Clone)]
+pub struct BackgroundChoices {
+    pub tools: Vec<String>,
+    pub languages: Vec<Language>,
+    pub replacements: Vec<(StatEnum, StatEnum)>,
+    pub personality: Personality,
+}
+
+/// Applies a background to a freshly spawned unit: skill, tool and language
+/// proficiencies, starting equipment and gold, the background feature and
+/// the chosen personality.
+pub struct ApplyBackground {
+    pub unit: Entity,
+    pub background: Background,
+    pub choices: BackgroundChoices,
+}
+
+impl Command for ApplyBackground {
+    fn apply(self, world: &mut World) {
+        let unit = self.unit;
+        let Some(data) = world
+            .resource::<BackgroundCatalog>()
+            .get(&self.background)
+            .cloned()
+        else {
+            return;
+        };
+
+        let mut children = Vec::new();
+        for name in &data.equipment {
+            children.push(
+                world
+                    .spawn(ItemBundle {
+                        name: ItemName(name.clone()),
+                        ..default()
+                    })
+                    .id(),
+            );
+        }
+        children.push(
+            world
+                .spawn((
+                    BackgroundFeature,
+                    Feature {
+                        name: data.feature.name.clone(),
+                        description: data.feature.description.clone(),
+                    },
+                ))
+                .id(),
+        );
+
+        let mut tools = data.tools.clone();
+        if let Some(choice) = &data.tool_choices {
+            tools.extend(
+                self.choices
+                    .tools
+                    .iter()
+                    .filter(|x| choice.options.contains(x))
+                    .take(choice.count)
+                    .cloned(),
+            );
+        }
+        let languages = self
+            .choices
+            .languages
+            .iter()
+            .copied()
+            .take(data.extra_languages)
+            .collect::<Vec<Language>>();
+
+        let mut unit_mut = world.entity_mut(unit);
+        unit_mut.push_children(&children);
+        match unit_mut.get_mut::<ToolProficiencies>() {
+            Some(mut existing) => existing.0.extend(tools),
+            None => {
+                unit_mut.insert(ToolProficiencies(tools));
+            }
+        }
+        match unit_mut.get_mut::<Languages>() {
+            Some(mut existing) => {
+                for language in languages {
+                    if !existing.0.contains(&language) {
+                        existing.0.push(language);
+                    }
+                }
+            }
+            None => {
+                unit_mut.insert(Languages(languages));
+            }
+        }
+        match unit_mut.get_mut::<Gold>() {
+            Some(mut gold) => gold.0 += data.gold,
+            None => {
+                unit_mut.insert(Gold(data.gold));
+            }
+        }
+        unit_mut.insert(self.choices.personality.clone());
+
+        for skill in &data.skills {
+            let skill = self
+                .choices
+                .replacements
+                .iter()
+                .find(|x| &x.0 == skill)
+                .map(|x| x.1.clone())
+                .unwrap_or(skill.clone());
+            SetProficiency(unit, skill, Proficiency::Proficient).apply(world);
+        }
+    }
+}
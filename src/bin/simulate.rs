@@ -0,0 +1,98 @@
+//! Runs an encounter many times and prints how it went:
+//!
+//! ```sh
+//! cargo run --bin simulate -- --party brom,ilsa --monsters "Goblin:4,Bugbear" --runs 5000
+//! ```
+
+use newtable::saves::{scene_path, SAVE_DIR};
+use newtable::simulation::simulate;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::{env, fs};
+
+const USAGE: &str = "usage: simulate --party SLOT[,SLOT...] --monsters NAME[:COUNT][,...] \
+[--runs N] [--seed N] [--saves DIR]";
+
+struct Args {
+    party: Vec<String>,
+    monsters: Vec<String>,
+    runs: u32,
+    seed: u64,
+    saves: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        party: vec![],
+        monsters: vec![],
+        runs: 1000,
+        seed: rand::random(),
+        saves: PathBuf::from(SAVE_DIR),
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(flag) = argv.next() {
+        let value = argv.next().ok_or(format!("{flag} needs a value"))?;
+        match flag.as_str() {
+            "--party" => args.party = value.split(',').map(str::to_string).collect(),
+            "--monsters" => {
+                for monster in value.split(',') {
+                    let (name, count) = match monster.rsplit_once(':') {
+                        Some((name, count)) => (
+                            name,
+                            count
+                                .parse()
+                                .map_err(|_| format!("bad count in {monster}"))?,
+                        ),
+                        None => (monster, 1),
+                    };
+                    args.monsters
+                        .extend(std::iter::repeat_n(name.trim().to_string(), count));
+                }
+            }
+            "--runs" => {
+                args.runs = value
+                    .parse()
+                    .map_err(|_| format!("bad run count {value}"))?
+            }
+            "--seed" => args.seed = value.parse().map_err(|_| format!("bad seed {value}"))?,
+            "--saves" => args.saves = PathBuf::from(value),
+            _ => return Err(format!("unknown flag {flag}")),
+        }
+    }
+    if args.party.is_empty() || args.monsters.is_empty() {
+        return Err("a party and some monsters are needed".to_string());
+    }
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut party = vec![];
+    for slot in &args.party {
+        let path = scene_path(&args.saves, slot);
+        match fs::read_to_string(&path) {
+            Ok(text) => party.push(text),
+            Err(e) => {
+                eprintln!("couldn't read {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    match simulate(&party, &args.monsters, args.runs, args.seed) {
+        Ok(report) => {
+            println!("Seed:           {}", args.seed);
+            print!("{report}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use crate::ability_scores::{AbilityMethod, AbilityScores};
+use crate::backgrounds::{ApplyBackground, BackgroundCatalog, BackgroundChoices};
+use crate::classes::{ApplyClass, ClassCatalog, ClassChoices};
+use crate::components::*;
+use crate::items::ArmorCatalog;
+use crate::races::{ApplyRace, RaceCatalog, RacialChoices};
+use bevy::{ecs::world::Command, prelude::*};
+
+/// Everything that goes into a new character, whether it comes from the new
+/// character ui, a test or an importer. Setting the same inputs always builds
+/// the same character, so the builder is the single source of truth until
+/// `spawn` turns it into a `PlayerBundle` with its race, class and background
+/// applied.
+#[derive(Resource, Default, Clone)]
+pub struct CharacterBuilder {
+    pub sheet: BasePlayer,
+    pub scores: AbilityScores,
+    pub racial_choices: RacialChoices,
+    pub class_choices: ClassChoices,
+    pub background_choices: BackgroundChoices,
+    pub overrides: Overrides,
+}
+
+impl CharacterBuilder {
+    pub fn name(mut self, name: &str) -> Self {
+        self.sheet.name = name.to_string();
+        self
+    }
+
+    pub fn player_name(mut self, name: &str) -> Self {
+        self.sheet.player_name = name.to_string();
+        self
+    }
+
+    pub fn race(mut self, race: Race, choices: RacialChoices) -> Self {
+        self.sheet.race = race;
+        self.racial_choices = choices;
+        self
+    }
+
+    pub fn class(mut self, class: Class, level: i64, choices: ClassChoices) -> Self {
+        self.sheet.class = class;
+        self.sheet.level = level;
+        self.class_choices = choices;
+        self
+    }
+
+    pub fn background(mut self, background: Background, choices: BackgroundChoices) -> Self {
+        self.sheet.background = background;
+        self.background_choices = choices;
+        self
+    }
+
+    /// Base ability scores before racial bonuses, in `StatEnum::ABILITIES`
+    /// order. They are entered as is, the same as the Manual method.
+    pub fn abilities(mut self, scores: [f64; 6]) -> Self {
+        self.scores.set_method(AbilityMethod::Manual);
+        self.scores.scores = scores;
+        self
+    }
+
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.sheet.alignment = alignment;
+        self
+    }
+
+    pub fn xp(mut self, xp: f64) -> Self {
+        self.sheet.xp = xp;
+        self
+    }
+
+    /// Recomputes everything the sheet derives from the other choices and
+    /// writes it into `sheet`.
+    pub fn update(&mut self, catalogs: &Catalogs) -> Derived {
+        for (i, ability) in StatEnum::ABILITIES.iter().enumerate() {
+            if let Some(base) = self.sheet.ability_base_mut(ability) {
+                *base = self.scores.scores[i];
+            }
+        }
+        let derived = Derived::new(
+            &self.sheet,
+            catalogs,
+            &self.scores,
+            &self.racial_choices,
+            &self.class_choices,
+            &self.background_choices,
+        );
+        derived.apply(&mut self.sheet, &self.overrides, catalogs);
+        derived
+    }
+
+    /// Spawns the character and applies its race, class and background.
+    pub fn spawn(mut self, world: &mut World) -> Entity {
+        self.update(&Catalogs::from_world(world));
+        let unit = world.spawn(self.sheet.to_bundle()).id();
+        if self.scores.method == AbilityMethod::Roll {
+            world.entity_mut(unit).insert(self.scores.roll_log());
+        }
+        ApplyRace {
+            unit,
+            race: self.sheet.race.clone(),
+            choices: self.racial_choices,
+        }
+        .apply(world);
+        ApplyClass {
+            unit,
+            class: self.sheet.class.clone(),
+            choices: self.class_choices,
+        }
+        .apply(world);
+        ApplyBackground {
+            unit,
+            background: self.sheet.background.clone(),
+            choices: self.background_choices,
+        }
+        .apply(world);
+        // Racial hit point bonuses only land once the race is applied.
+        if let Some(max_health) = world.get::<MaxHealth>(unit).map(|x| x.0.total) {
+            world.entity_mut(unit).insert(Health(max_health));
+        }
+        unit
+    }
+}
+
+/// Spawns a character from a builder.
+pub struct SpawnCharacter(pub CharacterBuilder);
+
+impl Command for SpawnCharacter {
+    fn apply(self, world: &mut World) {
+        self.0.spawn(world);
+    }
+}
+
+/// The data catalogs character creation reads from.
+pub struct Catalogs<'a> {
+    pub races: &'a RaceCatalog,
+    pub classes: &'a ClassCatalog,
+    pub backgrounds: &'a BackgroundCatalog,
+    pub armor: &'a ArmorCatalog,
+}
+
+impl<'a> Catalogs<'a> {
+    pub fn from_world(world: &'a World) -> Self {
+        Self {
+            races: world.resource::<RaceCatalog>(),
+            classes: world.resource::<ClassCatalog>(),
+            backgrounds: world.resource::<BackgroundCatalog>(),
+            armor: world.resource::<ArmorCatalog>(),
+        }
+    }
+}
+
+/// The character sheet of a character being built. Numbers are stat bases:
+/// racial modifiers are added on top when the character is spawned.
+#[derive(Default, Clone, Reflect)]
+pub struct BasePlayer {
+    pub name: String,
+    pub player_name: String,
+    pub ac: f64,
+    pub speed: f64,
+    pub strength: f64,
+    pub constitution: f64,
+    pub dexterity: f64,
+    pub intelligence: f64,
+    pub wisdom: f64,
+    pub charisma: f64,
+    pub athletics: f64,
+    pub acrobatics: f64,
+    pub sleight_of_hand: f64,
+    pub stealth: f64,
+    pub arcana: f64,
+    pub history: f64,
+    pub investigation: f64,
+    pub nature: f64,
+    pub religion: f64,
+    pub animal_handling: f64,
+    pub insight: f64,
+    pub medicine: f64,
+    pub perception: f64,
+    pub survival: f64,
+    pub deception: f64,
+    pub intimidation: f64,
+    pub performance: f64,
+    pub persuasion: f64,
+    pub race: Race,
+    pub class: Class,
+    pub wep_profs: WeaponProficiencies,
+    pub max_health: f64,
+    pub background: Background,
+    pub alignment: Alignment,
+    pub xp: f64,
+    pub level: i64,
+    pub hit_dice: HitDice,
+}
+
+impl BasePlayer {
+    /// The bundle to spawn for this sheet. Every field of the sheet ends up
+    /// in the bundle; skill totals include their ability modifier, the same
+    /// as UpdateStat computes them, and each ability lists its skills as
+    /// dependencies so racial increases carry over to them.
+    pub fn to_bundle(&self) -> PlayerBundle {
+        let ability = |ability: StatEnum, score: f64| Ability {
+            stat: Stat::new(
+                score,
+                StatEnum::SKILLS
+                    .into_iter()
+                    .filter(|x| x.ability() == Some(ability.clone()))
+                    .collect(),
+            ),
+            proficiency: Proficiency::None,
+        };
+        let modifier = |score: f64| ((score - 10.) / 2.).floor();
+        let skill = |base: f64, score: f64| Skill {
+            stat: Stat {
+                base,
+                total: base + modifier(score),
+                deps: vec![],
+            },
+            proficiency: Proficiency::None,
+        };
+        PlayerBundle {
+            player_tag: Player,
+            unit_tag: Unit,
+            name: UnitName(self.name.clone()),
+            player_name: PlayerName(self.player_name.clone()),
+            ac: ArmorClass(Stat::new(self.ac, vec![])),
+            speed: Speed(Stat::new(self.speed, vec![])),
+            abilities: AbilitiesBundle {
+                str: Strength(ability(StatEnum::Strength, self.strength)),
+                con: Constitution(ability(StatEnum::Constitution, self.constitution)),
+                dex: Dexterity(ability(StatEnum::Dexterity, self.dexterity)),
+                int: Intelligence(ability(StatEnum::Intelligence, self.intelligence)),
+                wis: Wisdom(ability(StatEnum::Wisdom, self.wisdom)),
+                cha: Charisma(ability(StatEnum::Charisma, self.charisma)),
+            },
+            skills: SkillsBundle {
+                athletics: Athletics(skill(self.athletics, self.strength)),
+                acrobatics: Acrobatics(skill(self.acrobatics, self.dexterity)),
+                sleight_of_hand: SleightOfHand(skill(self.sleight_of_hand, self.dexterity)),
+                stealth: Stealth(skill(self.stealth, self.dexterity)),
+                arcana: Arcana(skill(self.arcana, self.intelligence)),
+                history: History(skill(self.history, self.intelligence)),
+                investigation: Investigation(skill(self.investigation, self.intelligence)),
+                nature: Nature(skill(self.nature, self.intelligence)),
+                religion: Religion(skill(self.religion, self.intelligence)),
+                animal_handling: AnimalHandling(skill(self.animal_handling, self.wisdom)),
+                insight: Insight(skill(self.insight, self.wisdom)),
+                medicine: Medicine(skill(self.medicine, self.wisdom)),
+                perception: Perception(skill(self.perception, self.wisdom)),
+                survival: Survival(skill(self.survival, self.wisdom)),
+                deception: Deception(skill(self.deception, self.charisma)),
+                intimidation: Intimidation(skill(self.intimidation, self.charisma)),
+                performance: Performance(skill(self.performance, self.charisma)),
+                persuasion: Persuasion(skill(self.persuasion, self.charisma)),
+            },
+            race: self.race.clone(),
+            class: self.class.clone(),
+            wep_profs: self.wep_profs.clone(),
+            prof_bonus: ProficiencyBonus::from_level(self.level.max(1)),
+            health: Health(self.max_health),
+            max_health: MaxHealth(Stat::new(self.max_health, vec![])),
+            background: self.background.clone(),
+            alignment: self.alignment.clone(),
+            xp: Xp(self.xp),
+            level: Level(self.level.max(1)),
+            hit_dice: self.hit_dice.clone(),
+            settings: SettingsBundle::default(),
+            play_time: PlayTime::default(),
+        }
+    }
+
+    fn ability_base_mut(&mut self, ability: &StatEnum) -> Option<&mut f64> {
+        match ability {
+            StatEnum::Strength => Some(&mut self.strength),
+            StatEnum::Constitution => Some(&mut self.constitution),
+            StatEnum::Dexterity => Some(&mut self.dexterity),
+            StatEnum::Intelligence => Some(&mut self.intelligence),
+            StatEnum::Wisdom => Some(&mut self.wisdom),
+            StatEnum::Charisma => Some(&mut self.charisma),
+            _ => None,
+        }
+    }
+
+    pub fn skill_base_mut(&mut self, skill: &StatEnum) -> Option<&mut f64> {
+        match skill {
+            StatEnum::Athletics => Some(&mut self.athletics),
+            StatEnum::Acrobatics => Some(&mut self.acrobatics),
+            StatEnum::SleightOfHand => Some(&mut self.sleight_of_hand),
+            StatEnum::Stealth => Some(&mut self.stealth),
+            StatEnum::Arcana => Some(&mut self.arcana),
+            StatEnum::History => Some(&mut self.history),
+            StatEnum::Investigation => Some(&mut self.investigation),
+            StatEnum::Nature => Some(&mut self.nature),
+            StatEnum::Religion => Some(&mut self.religion),
+            StatEnum::AnimalHandling => Some(&mut self.animal_handling),
+            StatEnum::Insight => Some(&mut self.insight),
+            StatEnum::Medicine => Some(&mut self.medicine),
+            StatEnum::Perception => Some(&mut self.perception),
+            StatEnum::Survival => Some(&mut self.survival),
+            StatEnum::Deception => Some(&mut self.deception),
+            StatEnum::Intimidation => Some(&mut self.intimidation),
+            StatEnum::Performance => Some(&mut self.performance),
+            StatEnum::Persuasion => Some(&mut self.persuasion),
+            _ => None,
+        }
+    }
+}
+
+/// A derived value the player has chosen to set by hand, for house rules.
+#[derive(Default, Clone, Copy)]
+pub struct Override {
+    pub enabled: bool,
+    pub value: f64,
+}
+
+impl Override {
+    pub fn resolve(&self, derived: f64) -> f64 {
+        match self.enabled {
+            true => self.value,
+            false => derived,
+        }
+    }
+}
+
+/// Overrides for the derived values in character creation. Skills are in
+/// `StatEnum::SKILLS` order.
+#[derive(Resource, Default, Clone)]
+pub struct Overrides {
+    pub skills: [Override; 18],
+    pub ac: Override,
+    pub speed: Override,
+    pub max_health: Override,
+}
+
+/// Everything on the new character sheet that follows from the player's
+/// other choices.
+pub struct Derived {
+    pub abilities: [f64; 6],
+    pub proficiency_bonus: f64,
+    pub saves: [(Proficiency, f64); 6],
+    pub skills: [(Proficiency, f64); 18],
+    pub ac: f64,
+    pub speed: f64,
+    pub max_health: f64,
+    pub racial_health: f64,
+}
+
+impl Derived {
+    pub fn new(
+        newchar: &BasePlayer,
+        catalogs: &Catalogs,
+        scores: &AbilityScores,
+        choices: &RacialChoices,
+        class_choices: &ClassChoices,
+        background_choices: &BackgroundChoices,
+    ) -> Self {
+        let level = newchar.level.max(1);
+        let mut abilities = [0.; 6];
+        for (i, ability) in StatEnum::ABILITIES.iter().enumerate() {
+            abilities[i] = scores.scores[i]
+                + catalogs
+                    .races
+                    .ability_bonus(&newchar.race, choices, ability);
+        }
+        let modifier = |ability: &StatEnum| {
+            StatEnum::ABILITIES
+                .iter()
+                .position(|x| x == ability)
+                .map(|i| ((abilities[i] - 10.) / 2.).floor())
+                .unwrap_or(0.)
+        };
+        let proficiency_bonus = ProficiencyBonus::from_level(level).0 as f64;
+
+        let mut proficient =
+            non_background_skills(catalogs.races, &newchar.race, choices, class_choices);
+        if let Some(data) = catalogs.backgrounds.get(&newchar.background) {
+            for skill in &data.skills {
+                match background_choices
+                    .replacements
+                    .iter()
+                    .find(|x| &x.0 == skill)
+                {
+                    Some(replacement) => proficient.push(replacement.1.clone()),
+                    None => proficient.push(skill.clone()),
+                }
+            }
+        }
+        let skills = StatEnum::SKILLS.map(|skill| {
+            let ability = skill.ability().map(|x| modifier(&x)).unwrap_or(0.);
+            match proficient.contains(&skill) {
+                true => (Proficiency::Proficient, ability + proficiency_bonus),
+                false => (Proficiency::None, ability),
+            }
+        });
+
+        let class = catalogs.classes.get(&newchar.class);
+        let saves = StatEnum::ABILITIES.map(|ability| {
+            let proficient = class.is_some_and(|x| x.saving_throws.contains(&ability));
+            match proficient {
+                true => (
+                    Proficiency::Proficient,
+                    modifier(&ability) + proficiency_bonus,
+                ),
+                false => (Proficiency::None, modifier(&ability)),
+            }
+        });
+        let unarmored = class
+            .and_then(|x| x.unarmored_defense.as_ref())
+            .map(modifier)
+            .unwrap_or(0.);
+        let items = class
+            .map(|x| x.equipment(class_choices))
+            .unwrap_or_default();
+        let ac = catalogs
+            .armor
+            .armor_class(&items, modifier(&StatEnum::Dexterity), unarmored);
+
+        let racial_health = catalogs
+            .races
+            .traits(&newchar.race)
+            .iter()
+            .map(|x| x.max_health_per_level)
+            .sum::<f64>()
+            * level as f64;
+        let max_health = class
+            .map(|x| x.max_health(level, modifier(&StatEnum::Constitution)))
+            .unwrap_or(0.)
+            + racial_health;
+
+        Self {
+            abilities,
+            proficiency_bonus,
+            saves,
+            skills,
+            ac,
+            speed: catalogs.races.speed(&newchar.race),
+            max_health,
+            racial_health,
+        }
+    }
+
+    /// Writes the derived values into `newchar` as stat bases. Racial
+    /// modifiers are added on top by ApplyRace, so they are taken back out
+    /// here, and an override only shifts the base by how far it is from the
+    /// derived value.
+    pub fn apply(&self, newchar: &mut BasePlayer, overrides: &Overrides, catalogs: &Catalogs) {
+        newchar.ac = overrides.ac.resolve(self.ac);
+        newchar.speed = overrides.speed.resolve(self.speed) - self.speed;
+        newchar.max_health = overrides.max_health.resolve(self.max_health) - self.racial_health;
+        for (i, skill) in StatEnum::SKILLS.iter().enumerate() {
+            let derived = self.skills[i].1;
+            if let Some(base) = newchar.skill_base_mut(skill) {
+                *base = overrides.skills[i].resolve(derived) - derived;
+            }
+        }
+        if let Some(data) = catalogs.classes.get(&newchar.class) {
+            newchar.hit_dice = data.hit_dice(newchar.level);
+            newchar.wep_profs = data.weapon_proficiencies();
+        }
+    }
+}
+
+/// Skills the character is proficient in from their race and class, which
+/// a background skill may overlap with.
+pub fn non_background_skills(
+    races: &RaceCatalog,
+    race: &Race,
+    choices: &RacialChoices,
+    class_choices: &ClassChoices,
+) -> Vec<StatEnum> {
+    let mut skills = class_choices.skills.clone();
+    for t in races.traits(race) {
+        skills.extend(t.skills.iter().cloned());
+        skills.extend(choices.skills.iter().cloned().take(t.skill_choices));
+    }
+    skills
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::areas::{AreaEffect, AreasPlugin, SaveResult, Shape, Template};
+    use crate::backgrounds::BackgroundsPlugin;
+    use crate::classes::ClassesPlugin;
+    use crate::items::ItemsPlugin;
+    use crate::map::{BattleMap, GridPosition};
+    use crate::migrations::{load_scene, SaveVersion};
+    use crate::races::RacesPlugin;
+    use crate::saves::serialize_scene;
+    use crate::RulesRng;
+    use bevy::ecs::entity::EntityHashMap;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            HierarchyPlugin,
+            ItemsPlugin,
+            RacesPlugin,
+            ClassesPlugin,
+            BackgroundsPlugin,
+        ));
+        app.register_type::<ComponentRegistry>();
+        app.register_type::<AbilityRolls>();
+        app.register_type::<SaveVersion>();
+        app
+    }
+
+    fn dwarf_soldier() -> CharacterBuilder {
+        CharacterBuilder::default()
+            .name("Thora")
+            .player_name("Sam")
+            .race(
+                Race::HillDwarf,
+                RacialChoices {
+                    tool: Some("Smith's Tools".into()),
+                    ..default()
+                },
+            )
+            .class(
+                Class::Fighter,
+                3,
+                ClassChoices {
+                    skills: vec![StatEnum::Athletics, StatEnum::Perception],
+                    equipment: vec![0, 1, 1, 0],
+                    ..default()
+                },
+            )
+            .background(
+                Background::Soldier,
+                BackgroundChoices {
+                    tools: vec!["Dice Set".into()],
+                    replacements: vec![(StatEnum::Athletics, StatEnum::Survival)],
+                    ..default()
+                },
+            )
+            .abilities([15., 14., 12., 8., 13., 10.])
+            .alignment(Alignment::LawfulNeutral)
+            .xp(900.)
+    }
+
+    /// Everything about a spawned player that should survive a save.
+    #[derive(Debug, PartialEq)]
+    struct Snapshot {
+        name: String,
+        player_name: String,
+        race: Race,
+        class: Class,
+        background: Background,
+        alignment: Alignment,
+        level: i64,
+        xp: f64,
+        abilities: Vec<(f64, Proficiency)>,
+        skills: Vec<(f64, Proficiency)>,
+        ac: f64,
+        speed: f64,
+        max_health: f64,
+        health: f64,
+        hit_dice: String,
+        languages: Languages,
+        tools: ToolProficiencies,
+        gold: Gold,
+        personality: Personality,
+        children: Vec<String>,
+    }
+
+    fn snapshot(world: &mut World) -> Snapshot {
+        let unit = world.query_filtered::<Entity, With<Player>>().single(world);
+        let unit = world.entity(unit);
+        let ability = |x: &Ability| (x.stat.total, x.proficiency.clone());
+        let skill = |x: &Skill| (x.stat.total, x.proficiency.clone());
+        let mut children = unit
+            .get::<Children>()
+            .unwrap()
+            .iter()
+            .filter_map(|x| {
+                let child = world.entity(*x);
+                child
+                    .get::<Feature>()
+                    .map(|x| x.name.clone())
+                    .or(child.get::<ItemName>().map(|x| x.0.clone()))
+            })
+            .collect::<Vec<String>>();
+        children.sort();
+        Snapshot {
+            name: unit.get::<UnitName>().unwrap().0.clone(),
+            player_name: unit.get::<PlayerName>().unwrap().0.clone(),
+            race: unit.get::<Race>().unwrap().clone(),
+            class: unit.get::<Class>().unwrap().clone(),
+            background: unit.get::<Background>().unwrap().clone(),
+            alignment: unit.get::<Alignment>().unwrap().clone(),
+            level: unit.get::<Level>().unwrap().0,
+            xp: unit.get::<Xp>().unwrap().0,
+            abilities: vec![
+                ability(&unit.get::<Strength>().unwrap().0),
+                ability(&unit.get::<Constitution>().unwrap().0),
+                ability(&unit.get::<Dexterity>().unwrap().0),
+                ability(&unit.get::<Intelligence>().unwrap().0),
+                ability(&unit.get::<Wisdom>().unwrap().0),
+                ability(&unit.get::<Charisma>().unwrap().0),
+            ],
+            skills: vec![
+                skill(&unit.get::<Acrobatics>().unwrap().0),
+                skill(&unit.get::<AnimalHandling>().unwrap().0),
+                skill(&unit.get::<Arcana>().unwrap().0),
+                skill(&unit.get::<Athletics>().unwrap().0),
+                skill(&unit.get::<Deception>().unwrap().0),
+                skill(&unit.get::<History>().unwrap().0),
+                skill(&unit.get::<Insight>().unwrap().0),
+                skill(&unit.get::<Intimidation>().unwrap().0),
+                skill(&unit.get::<Investigation>().unwrap().0),
+                skill(&unit.get::<Medicine>().unwrap().0),
+                skill(&unit.get::<Nature>().unwrap().0),
+                skill(&unit.get::<Perception>().unwrap().0),
+                skill(&unit.get::<Performance>().unwrap().0),
+                skill(&unit.get::<Persuasion>().unwrap().0),
+                skill(&unit.get::<Religion>().unwrap().0),
+                skill(&unit.get::<SleightOfHand>().unwrap().0),
+                skill(&unit.get::<Stealth>().unwrap().0),
+                skill(&unit.get::<Survival>().unwrap().0),
+            ],
+            ac: unit.get::<ArmorClass>().unwrap().0.total,
+            speed: unit.get::<Speed>().unwrap().0.total,
+            max_health: unit.get::<MaxHealth>().unwrap().0.total,
+            health: unit.get::<Health>().unwrap().0,
+            hit_dice: format!("{:?}", unit.get::<HitDice>().unwrap().0),
+            languages: unit.get::<Languages>().unwrap().clone(),
+            tools: unit.get::<ToolProficiencies>().unwrap().clone(),
+            gold: *unit.get::<Gold>().unwrap(),
+            personality: unit.get::<Personality>().unwrap().clone(),
+            children,
+        }
+    }
+
+    #[test]
+    fn spawned_character_matches_the_sheet() {
+        let mut app = app();
+        let mut builder = dwarf_soldier();
+        let derived = builder.update(&Catalogs::from_world(app.world()));
+        builder.spawn(app.world_mut());
+        let spawned = snapshot(app.world_mut());
+
+        let bonus = derived.proficiency_bonus;
+        let with_bonus = |(total, proficiency): &(f64, Proficiency)| match proficiency {
+            Proficiency::None => (*total, proficiency.clone()),
+            _ => (total + bonus, proficiency.clone()),
+        };
+        assert_eq!(
+            spawned.abilities.iter().map(|x| x.0).collect::<Vec<f64>>(),
+            derived.abilities.to_vec()
+        );
+        assert_eq!(
+            spawned
+                .abilities
+                .iter()
+                .map(|(score, proficiency)| {
+                    with_bonus(&(((score - 10.) / 2.).floor(), proficiency.clone()))
+                })
+                .collect::<Vec<_>>(),
+            derived
+                .saves
+                .to_vec()
+                .into_iter()
+                .map(|x| (x.1, x.0))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            spawned.skills.iter().map(with_bonus).collect::<Vec<_>>(),
+            derived
+                .skills
+                .to_vec()
+                .into_iter()
+                .map(|x| (x.1, x.0))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(spawned.ac, derived.ac);
+        assert_eq!(spawned.speed, derived.speed);
+        assert_eq!(spawned.max_health, derived.max_health);
+        assert_eq!(spawned.health, derived.max_health);
+        assert_eq!(spawned.level, 3);
+        assert_eq!(spawned.gold, Gold(10));
+    }
+
+    #[test]
+    fn character_survives_save_and_load() {
+        let mut app = app();
+        dwarf_soldier().spawn(app.world_mut());
+        let created = snapshot(app.world_mut());
+        let saved = serialize_scene(app.world_mut());
+
+        let mut loaded = super::tests::app();
+        let scene = {
+            let registry = loaded.world().resource::<AppTypeRegistry>().read();
+            load_scene(&saved, &registry).unwrap()
+        };
+        scene
+            .write_to_world(loaded.world_mut(), &mut EntityHashMap::default())
+            .unwrap();
+
+        assert_eq!(snapshot(loaded.world_mut()), created);
+    }
+
+    #[derive(Resource, Default)]
+    struct SaveResults(Vec<SaveResult>);
+
+    #[test]
+    fn dwarves_save_against_poison_with_advantage_and_halflings_are_lucky() {
+        let mut app = app();
+        app.add_plugins(AreasPlugin)
+            .insert_resource(RulesRng::seeded(5))
+            .init_resource::<BattleMap>()
+            .init_resource::<SaveResults>()
+            .observe(
+                |trigger: Trigger<SaveResult>, mut results: ResMut<SaveResults>| {
+                    results.0.push(trigger.event().clone());
+                },
+            );
+        let thora = dwarf_soldier().spawn(app.world_mut());
+        let pip = dwarf_soldier()
+            .name("Pip")
+            .race(Race::LightfootHalfling, default())
+            .spawn(app.world_mut());
+        for (unit, x) in [(thora, 9), (pip, 10)] {
+            app.world_mut()
+                .entity_mut(unit)
+                .insert(GridPosition::new(x, 7));
+        }
+        let world = app.world_mut();
+        assert_eq!(
+            world.get::<SaveAdvantages>(thora).unwrap().0,
+            [DamageType::Poison]
+        );
+        assert!(world.get::<Lucky>(thora).is_none());
+        assert!(world.get::<Lucky>(pip).is_some());
+
+        for damage_type in [DamageType::Poison, DamageType::Fire] {
+            world.trigger(AreaEffect {
+                template: Template::new(Shape::Sphere { radius: 20. }, Vec2::new(10., 7.)),
+                save: StatEnum::Constitution,
+                dc: 30,
+                damage: Dice {
+                    dice_type: DiceType::D6,
+                    number: 2,
+                },
+                damage_type,
+                half_on_save: true,
+            });
+            world.flush();
+        }
+        let results = std::mem::take(&mut world.resource_mut::<SaveResults>().0);
+        let advantage = |unit| {
+            results
+                .iter()
+                .filter(|x| x.unit == unit)
+                .map(|x| x.advantage)
+                .collect::<Vec<_>>()
+        };
+        // Only the dwarf, and only against poison.
+        assert_eq!(advantage(thora), [true, false]);
+        assert_eq!(advantage(pip), [false, false]);
+    }
+
+    #[test]
+    fn classes_skip_proficiencies_the_race_gave() {
+        let mut app = app();
+        let unit = dwarf_soldier()
+            .race(
+                Race::MountainDwarf,
+                RacialChoices {
+                    tool: Some("Smith's Tools".into()),
+                    ..default()
+                },
+            )
+            .spawn(app.world_mut());
+        let world = app.world();
+        // Mountain dwarves train in light and medium armor, and fighters in
+        // all of it.
+        assert_eq!(
+            world.get::<ArmorProficiencies>(unit).unwrap().0,
+            [
+                ArmorCategory::Light,
+                ArmorCategory::Medium,
+                ArmorCategory::Heavy,
+                ArmorCategory::Shields
+            ]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Ability checks, skill checks and saving throws a unit rolls from its
+//! sheet, and the passive scores that stand in for them.
+
+use crate::areas::{save_modifier, Saver};
+use crate::components::*;
+use crate::RulesRng;
+use bevy::ecs::query::{QueryData, ROQueryItem};
+use bevy::prelude::*;
+
+pub struct ChecksPlugin;
+
+impl Plugin for ChecksPlugin {
+    fn build(&self, app: &mut App) {
+        app.observe(roll_check);
+    }
+}
+
+/// Every skill a unit has.
+#[derive(QueryData)]
+pub struct Skills {
+    pub athletics: &'static Athletics,
+    pub acrobatics: &'static Acrobatics,
+    pub sleight_of_hand: &'static SleightOfHand,
+    pub stealth: &'static Stealth,
+    pub arcana: &'static Arcana,
+    pub history: &'static History,
+    pub investigation: &'static Investigation,
+    pub nature: &'static Nature,
+    pub religion: &'static Religion,
+    pub animal_handling: &'static AnimalHandling,
+    pub insight: &'static Insight,
+    pub medicine: &'static Medicine,
+    pub perception: &'static Perception,
+    pub survival: &'static Survival,
+    pub deception: &'static Deception,
+    pub intimidation: &'static Intimidation,
+    pub performance: &'static Performance,
+    pub persuasion: &'static Persuasion,
+}
+
+impl SkillsItem<'_> {
+    /// The skill `stat` names, or None if it isn't a skill.
+    pub fn get(&self, stat: &StatEnum) -> Option<&Skill> {
+        let skill = match stat {
+            StatEnum::Athletics => &self.athletics.0,
+            StatEnum::Acrobatics => &self.acrobatics.0,
+            StatEnum::SleightOfHand => &self.sleight_of_hand.0,
+            StatEnum::Stealth => &self.stealth.0,
+            StatEnum::Arcana => &self.arcana.0,
+            StatEnum::History => &self.history.0,
+            StatEnum::Investigation => &self.investigation.0,
+            StatEnum::Nature => &self.nature.0,
+            StatEnum::Religion => &self.religion.0,
+            StatEnum::AnimalHandling => &self.animal_handling.0,
+            StatEnum::Insight => &self.insight.0,
+            StatEnum::Medicine => &self.medicine.0,
+            StatEnum::Perception => &self.perception.0,
+            StatEnum::Survival => &self.survival.0,
+            StatEnum::Deception => &self.deception.0,
+            StatEnum::Intimidation => &self.intimidation.0,
+            StatEnum::Performance => &self.performance.0,
+            StatEnum::Persuasion => &self.persuasion.0,
+            _ => return None,
+        };
+        Some(skill)
+    }
+}
+
+/// What a unit carries or knows: its items, features and spells.
+pub type Belonging = (
+    Option<&'static ItemName>,
+    Option<&'static Feature>,
+    Option<&'static SpellName>,
+);
+
+/// A unit's children sorted into what shows up where on its sheet.
+#[derive(Default)]
+pub struct Belongings<'a> {
+    pub items: Vec<&'a ItemName>,
+    pub features: Vec<&'a Feature>,
+    pub spells: Vec<&'a SpellName>,
+}
+
+impl<'a> Belongings<'a> {
+    /// Spells are listed as spells even when they're also features, like
+    /// racial spells, and features as features even when they're items.
+    pub fn sort(children: impl IntoIterator<Item = ROQueryItem<'a, Belonging>>) -> Self {
+        let mut belongings = Self::default();
+        for (item, feature, spell) in children {
+            match (item, feature, spell) {
+                (_, _, Some(spell)) => belongings.spells.push(spell),
+                (_, Some(feature), _) => belongings.features.push(feature),
+                (Some(item), _, _) => belongings.items.push(item),
+                _ => {}
+            }
+        }
+        belongings
+    }
+}
+
+/// What a unit adds to checks with `skill`. Skill totals already include
+/// their ability modifier.
+pub fn skill_modifier(skill: &Skill, proficiency_bonus: i64) -> i64 {
+    skill.stat.total as i64 + skill.proficiency.bonus(proficiency_bonus)
+}
+
+/// The score others roll against when the unit isn't actively trying, like
+/// passive Perception.
+pub fn passive_score(skill: &Skill, proficiency_bonus: i64) -> i64 {
+    10 + skill_modifier(skill, proficiency_bonus)
+}
+
+/// A d20 roll for a unit, with the stat it's rolled with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// A plain check of one of `StatEnum::ABILITIES`.
+    Ability(StatEnum),
+    /// A saving throw of one of `StatEnum::ABILITIES`.
+    Save(StatEnum),
+    /// One of `StatEnum::SKILLS`.
+    Skill(StatEnum),
+}
+
+impl Check {
+    /// "Stealth check", "Wisdom save"
+    pub fn name(&self) -> String {
+        match self {
+            Check::Ability(stat) | Check::Skill(stat) => format!("{stat:?} check"),
+            Check::Save(stat) => format!("{stat:?} save"),
+        }
+    }
+}
+
+/// Asks for `unit` to roll `check`.
+#[derive(Event, Debug, Clone)]
+pub struct RollCheck {
+    pub unit: Entity,
+    pub check: Check,
+}
+
+/// The roll of a `RollCheck`, sent once it's rolled.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct CheckResult {
+    pub unit: Entity,
+    pub check: Check,
+    pub roll: i64,
+    pub total: i64,
+}
+
+fn roll_check(
+    trigger: Trigger<RollCheck>,
+    mut commands: Commands,
+    mut rng: ResMut<RulesRng>,
+    savers: Query<Saver>,
+    skills: Query<Skills>,
+    lucky: Query<Has<Lucky>>,
+) {
+    let event = trigger.event();
+    let modifier = match &event.check {
+        Check::Ability(stat) => savers.get(event.unit).ok().and_then(|x| {
+            // Without a proficiency bonus a save is a plain check.
+            let check = (x.0, x.1, x.2, x.3, x.4, x.5, None);
+            save_modifier(check, stat)
+        }),
+        Check::Save(stat) => savers
+            .get(event.unit)
+            .ok()
+            .and_then(|x| save_modifier(x, stat)),
+        Check::Skill(stat) => {
+            let bonus = savers.get(event.unit).ok().and_then(|x| x.6).map(|x| x.0);
+            skills
+                .get(event.unit)
+                .ok()
+                .and_then(|x| x.get(stat).map(|x| skill_modifier(x, bonus.unwrap_or(0))))
+        }
+    };
+    let Some(modifier) = modifier else {
+        warn!("{:?} can't make a {}", event.unit, event.check.name());
+        return;
+    };
+    let roll = rng.d20(lucky.get(event.unit).unwrap_or(false));
+    let total = roll + modifier;
+    info!("{}: rolled {roll}, {total} in all", event.check.name());
+    commands.trigger(CheckResult {
+        unit: event.unit,
+        check: event.check.clone(),
+        roll,
+        total,
+    });
+}
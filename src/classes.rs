@@ -0,0 +1,223 @@
+use crate::components::*;
+use crate::items::{ArmWeapons, SetProficiency};
+use crate::races::extend_unique;
+use bevy::{ecs::world::Command, prelude::*};
+use serde::Deserialize;
+
+pub struct ClassesPlugin;
+
+impl Plugin for ClassesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClassCatalog::from_ron(include_str!(
+            "../assets/data/classes.ron"
+        )));
+        app.register_type::<ClassFeature>();
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ClassCatalog(pub Vec<ClassData>);
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ClassData {
+    pub class: Class,
+    pub hit_die: DiceType,
+    pub saving_throws: Vec<StatEnum>,
+    pub armor: Vec<ArmorCategory>,
+    pub simple_weapons: bool,
+    pub martial_weapons: bool,
+    pub weapons: Vec<String>,
+    pub tools: Vec<String>,
+    pub tool_choices: Option<ToolChoice>,
+    pub skill_choices: usize,
+    pub skill_list: Vec<StatEnum>,
+    pub equipment: Vec<Vec<Vec<String>>>,
+    pub unarmored_defense: Option<StatEnum>,
+    pub features: Vec<ClassFeatureData>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct ToolChoice {
+    pub count: usize,
+    pub options: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ClassFeatureData {
+    pub level: i64,
+    pub name: String,
+    pub description: String,
+}
+
+impl ClassCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("classes.ron to be a valid class catalog"))
+    }
+
+    pub fn get(&self, class: &Class) -> Option<&ClassData> {
+        let data = self.0.iter().find(|x| &x.class == class);
+        if data.is_none() {
+            warn!("No class definition for {class}");
+        }
+        data
+    }
+}
+
+impl ClassData {
+    /// Hit points at 1st level: the hit die's maximum plus the Constitution modifier.
+    pub fn first_level_health(&self, con_modifier: f64) -> f64 {
+        self.hit_die.max() as f64 + con_modifier
+    }
+
+    /// Hit points at `level`, taking the fixed average for every level after
+    /// the first.
+    pub fn max_health(&self, level: i64, con_modifier: f64) -> f64 {
+        let average = (self.hit_die.max() / 2 + 1) as f64 + con_modifier;
+        self.first_level_health(con_modifier) + average * (level.max(1) - 1) as f64
+    }
+
+    /// The starting equipment picked in `choices`.
+    pub fn equipment(&self, choices: &ClassChoices) -> Vec<String> {
+        self.equipment
+            .iter()
+            .enumerate()
+            .filter_map(|(i, choice)| {
+                let picked = choices.equipment.get(i).copied().unwrap_or(0);
+                choice.get(picked).or(choice.first())
+            })
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    pub fn hit_dice(&self, level: i64) -> HitDice {
+        HitDice(Dice {
+            dice_type: self.hit_die.clone(),
+            number: level.max(1),
+        })
+    }
+
+    pub fn weapon_proficiencies(&self) -> WeaponProficiencies {
+        let prof = |x: bool| match x {
+            true => Proficiency::Proficient,
+            false => Proficiency::None,
+        };
+        WeaponProficiencies {
+            simple: SimpleWeaponProficiency(prof(self.simple_weapons)),
+            martial: MartialWeaponProficiency(prof(self.martial_weapons)),
+            ind: IndividualWeaponProficiency(
+                self.weapons.iter().map(|x| ItemName(x.clone())).collect(),
+            ),
+        }
+    }
+}
+
+/// Choices a class leaves up to the player, made in the new character UI.
+/// `equipment` holds the picked alternative for each starting equipment
+/// choice, by index.
+#[derive(Resource, Default, Clone)]
+pub struct ClassChoices {
+    pub skills: Vec<StatEnum>,
+    pub tools: Vec<String>,
+    pub equipment: Vec<usize>,
+}
+
+/// Applies a class to a freshly spawned unit: saving throw, armor, weapon,
+/// tool and skill proficiencies, hit dice, starting equipment and every
+/// class feature up to the unit's level.
+pub struct ApplyClass {
+    pub unit: Entity,
+    pub class: Class,
+    pub choices: ClassChoices,
+}
+
+impl Command for ApplyClass {
+    fn apply(self, world: &mut World) {
+        let unit = self.unit;
+        let Some(data) = world.resource::<ClassCatalog>().get(&self.class).cloned() else {
+            return;
+        };
+        let level = world.get::<Level>(unit).map(|x| x.0).unwrap_or(1);
+
+        let mut children = Vec::new();
+        for name in data.equipment(&self.choices) {
+            children.push(
+                world
+                    .spawn(ItemBundle {
+                        name: ItemName(name),
+                        ..default()
+                    })
+                    .id(),
+            );
+        }
+        for feature in data.features.iter().filter(|x| x.level <= level) {
+            children.push(
+                world
+                    .spawn((
+                        ClassFeature {
+                            level: feature.level,
+                        },
+                        Feature {
+                            name: feature.name.clone(),
+                            description: feature.description.clone(),
+                        },
+                    ))
+                    .id(),
+            );
+        }
+
+        let mut tools = data.tools.clone();
+        if let Some(choice) = &data.tool_choices {
+            tools.extend(
+                self.choices
+                    .tools
+                    .iter()
+                    .filter(|x| choice.options.contains(x))
+                    .take(choice.count)
+                    .cloned(),
+            );
+        }
+        let wep_profs = data.weapon_proficiencies();
+
+        let mut unit_mut = world.entity_mut(unit);
+        unit_mut.push_children(&children);
+        unit_mut.insert(data.hit_dice(level));
+        // A race can already grant some of the class's proficiencies.
+        match unit_mut.get_mut::<ArmorProficiencies>() {
+            Some(mut armor) => extend_unique(&mut armor.0, data.armor.iter().copied()),
+            None => {
+                unit_mut.insert(ArmorProficiencies(data.armor.clone()));
+            }
+        }
+        match unit_mut.get_mut::<ToolProficiencies>() {
+            Some(mut existing) => extend_unique(&mut existing.0, tools),
+            None => {
+                unit_mut.insert(ToolProficiencies(tools));
+            }
+        }
+        if wep_profs.simple.0 == Proficiency::Proficient {
+            unit_mut.insert(wep_profs.simple);
+        }
+        if wep_profs.martial.0 == Proficiency::Proficient {
+            unit_mut.insert(wep_profs.martial);
+        }
+        if let Some(mut ind) = unit_mut.get_mut::<IndividualWeaponProficiency>() {
+            ind.0.extend(wep_profs.ind.0);
+        }
+
+        for save in data.saving_throws.iter() {
+            SetProficiency(unit, save.clone(), Proficiency::Proficient).apply(world);
+        }
+        for skill in self
+            .choices
+            .skills
+            .iter()
+            .filter(|x| data.skill_list.contains(x))
+            .take(data.skill_choices)
+        {
+            SetProficiency(unit, skill.clone(), Proficiency::Proficient).apply(world);
+        }
+        ArmWeapons(unit).apply(world);
+    }
+}
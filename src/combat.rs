@@ -0,0 +1,446 @@
+use crate::autosave::{Autosave, AutosaveReason};
+use crate::components::*;
+use crate::items::UNARMED_STRIKE;
+use crate::map::{cover, Battlefield, GridPosition};
+use crate::{AppState, RulesRng};
+use bevy::ecs::query::ROQueryItem;
+use bevy::prelude::*;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<InGameState>();
+        app.init_resource::<TurnOrder>();
+        app.register_type::<Initiative>();
+        app.register_type::<Downed>();
+        app.register_type::<TempHealth>();
+        app.add_event::<Attack>();
+        app.observe(handle_attack);
+        app.observe(roll_initiative);
+        app.observe(end_turn);
+        app.observe(handle_taking_damage);
+        app.observe(handle_rest);
+        app.add_systems(
+            Update,
+            autosave_after_combat.run_if(in_state(InGameState::Combat)),
+        );
+    }
+}
+
+#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[source(AppState = AppState::InGame)]
+pub enum InGameState {
+    #[default]
+    // Narrative,
+    Combat,
+    Paused,
+    /// Putting together the next fight in the encounter builder.
+    Encounter,
+    /// Painting the battle map in the map editor.
+    MapEditor,
+}
+
+#[derive(Event)]
+pub struct Attack {
+    pub from: Entity,
+    pub with: Entity,
+    pub to: Entity,
+    /// Where the target stood when it was attacked, if it has moved on
+    /// since, as when it provokes an opportunity attack by leaving reach.
+    pub at: Option<GridPosition>,
+}
+
+impl Attack {
+    pub fn new(from: Entity, with: Entity, to: Entity) -> Self {
+        Self {
+            from,
+            with,
+            to,
+            at: None,
+        }
+    }
+}
+
+/// Where `attack` lands: where the target stood when it was attacked, or
+/// else where it stands now.
+fn target_square(battlefield: &Battlefield, attack: &Attack) -> Option<GridPosition> {
+    attack.at.or_else(|| battlefield.position(attack.to))
+}
+
+#[derive(Event)]
+pub struct TakeDamage {
+    pub unit: Entity,
+    pub amount: f64,
+}
+
+/// A player at 0 hit points or less. Downed players skip their turns until
+/// they're healed.
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct Downed;
+
+/// The party takes a short or long rest together.
+#[derive(Event)]
+pub struct Rest {
+    pub long: bool,
+}
+
+/// The d20 roll of an attack and what it did, sent once it's resolved.
+#[derive(Event, Debug, Clone)]
+pub struct AttackResult {
+    pub from: Entity,
+    pub to: Entity,
+    pub roll: i64,
+    pub total: i64,
+    pub hit: bool,
+    pub critical: bool,
+    pub damage: f64,
+    /// Whether the d20 was rolled with advantage or disadvantage, from the
+    /// weapon, range or who can see whom. Having both rolls it straight.
+    pub advantage: bool,
+    pub disadvantage: bool,
+    /// Walls and any `Cover` the target already had.
+    pub cover: Option<Cover>,
+}
+
+pub type AttackerQuery = (
+    &'static Strength,
+    &'static Dexterity,
+    Option<&'static SimpleWeaponProficiency>,
+    Option<&'static MartialWeaponProficiency>,
+    Option<&'static IndividualWeaponProficiency>,
+    &'static ProficiencyBonus,
+    Option<&'static CritType>,
+);
+
+type WeaponQuery = (
+    &'static ItemName,
+    &'static WeaponType,
+    &'static Dice,
+    &'static BaseDamage,
+    Has<Finesse>,
+    Option<&'static AttackModifier>,
+    Option<&'static DamageModifier>,
+    Has<Advantage>,
+    Has<Disadvantage>,
+    Option<&'static Range>,
+    Option<&'static MaxRange>,
+    Has<Reach>,
+);
+
+/// Disadvantage from attacking at long range or shooting with a hostile
+/// unit next to the attacker, or None if the target is out of reach and
+/// range. Melee attacks reach 5 ft, or 10 ft with a `Reach` weapon.
+fn range_disadvantage(
+    battlefield: &Battlefield,
+    attack: &Attack,
+    melee: bool,
+    reach: bool,
+    range: Option<(&Range, &MaxRange)>,
+) -> Option<bool> {
+    let target = target_square(battlefield, attack);
+    let Some(distance) = (battlefield.position(attack.from))
+        .zip(target)
+        .map(|(from, to)| from.distance(to, *battlefield.rule))
+    else {
+        // Off the map, e.g. in simulations, everyone is in reach.
+        return Some(false);
+    };
+    let reach = if reach { 10. } else { 5. };
+    if melee && distance <= reach {
+        return Some(false);
+    }
+    let (range, max_range) = range?;
+    if distance > max_range.0 as f64 {
+        return None;
+    }
+    Some(distance > range.0 as f64 || battlefield.hostile_adjacent(attack.from))
+}
+
+/// What `attacker` adds to its attack and damage rolls with the weapon
+/// `name`: its Strength or Dexterity modifier to both, and its proficiency
+/// bonus to the attack if it's proficient with the weapon.
+pub fn weapon_bonuses(
+    attacker: ROQueryItem<AttackerQuery>,
+    name: &ItemName,
+    wep_type: &WeaponType,
+    finesse: bool,
+) -> (i64, i64) {
+    let (strength, dexterity, simple, martial, individual, prof_bonus, _) = attacker;
+    let strength = strength.0.calculate_modifier() as i64;
+    let dexterity = dexterity.0.calculate_modifier() as i64;
+    let ability = match wep_type {
+        _ if finesse => strength.max(dexterity),
+        WeaponType::SimpleMelee | WeaponType::MartialMelee => strength,
+        WeaponType::SimpleRanged | WeaponType::MartialRanged => dexterity,
+    };
+    let proficiency = match wep_type {
+        _ if name.0 == UNARMED_STRIKE => Proficiency::Proficient,
+        _ if individual.is_some_and(|x| x.0.contains(name)) => Proficiency::Proficient,
+        WeaponType::SimpleMelee | WeaponType::SimpleRanged => {
+            simple.map(|x| x.0.clone()).unwrap_or_default()
+        }
+        WeaponType::MartialMelee | WeaponType::MartialRanged => {
+            martial.map(|x| x.0.clone()).unwrap_or_default()
+        }
+    };
+    (ability + proficiency.bonus(prof_bonus.0), ability)
+}
+
+fn handle_attack(
+    trigger: Trigger<Attack>,
+    mut commands: Commands,
+    mut rng: ResMut<RulesRng>,
+    from_query: Query<(AttackerQuery, Has<Lucky>)>,
+    with_query: Query<WeaponQuery>,
+    to_query: Query<(&ArmorClass, Option<&Cover>)>,
+    battlefield: Battlefield,
+) {
+    let event = trigger.event();
+    let Ok((attacker, lucky)) = from_query.get(event.from) else {
+        warn!("{:?} can't attack", event.from);
+        return;
+    };
+    let Ok((
+        name,
+        wep_type,
+        dice,
+        base,
+        finesse,
+        att_mod,
+        dmg_mod,
+        adv,
+        disadv,
+        range,
+        max_range,
+        reach,
+    )) = with_query.get(event.with)
+    else {
+        warn!("{:?} isn't a weapon", event.with);
+        return;
+    };
+    let Ok((ac, target_cover)) = to_query.get(event.to) else {
+        return;
+    };
+    let melee = matches!(wep_type, WeaponType::SimpleMelee | WeaponType::MartialMelee);
+    let Some(range_disadv) =
+        range_disadvantage(&battlefield, event, melee, reach, range.zip(max_range))
+    else {
+        warn!("{:?} is out of range of the {}", event.to, name.0);
+        return;
+    };
+    // Attacking what you can't see is at disadvantage, and attacking
+    // someone who can't see you is at advantage.
+    let adv = adv || !battlefield.vision.can_see(event.to, event.from);
+    let disadv = disadv || range_disadv || !battlefield.vision.can_see(event.from, event.to);
+    let walls = (battlefield.position(event.from))
+        .zip(target_square(&battlefield, event))
+        .and_then(|(from, to)| cover(&battlefield.map, from, to));
+    let cover = target_cover.copied().max(walls);
+
+    let crit_type = attacker.6;
+    let (attack_bonus, ability) = weapon_bonuses(attacker, name, wep_type, finesse);
+
+    let first = rng.d20(lucky);
+    let second = rng.d20(lucky);
+    let roll = match (adv, disadv) {
+        (true, false) => first.max(second),
+        (false, true) => first.min(second),
+        _ => first,
+    };
+    let total = roll + attack_bonus + att_mod.map(|x| x.0).unwrap_or(0);
+    let target = match cover {
+        None => ac.0.total as i64,
+        Some(Cover::Half) => ac.0.total as i64 + 2,
+        Some(Cover::ThreeQuarters) => ac.0.total as i64 + 5,
+        Some(Cover::Total) => i64::MAX,
+    };
+    // A natural 20 always hits and a natural 1 always misses.
+    let critical = roll == 20 && cover != Some(Cover::Total);
+    let hit = critical || (roll != 1 && total >= target);
+
+    let mut damage = 0;
+    if hit {
+        let mut dice_total = dice.roll(&mut rng.0);
+        if critical && matches!(crit_type, Some(CritType::DoubleDice)) {
+            dice_total += dice.roll(&mut rng.0);
+        }
+        damage = (dice_total + base.0 + ability + dmg_mod.map(|x| x.0).unwrap_or(0)).max(0);
+        if critical && !matches!(crit_type, Some(CritType::DoubleDice)) {
+            damage *= 2;
+        }
+        commands.trigger(TakeDamage {
+            unit: event.to,
+            amount: damage as f64,
+        });
+    }
+    info!(
+        "{} attack: rolled {roll}, {total} against AC {target}, {damage} damage",
+        name.0
+    );
+    commands.trigger(AttackResult {
+        from: event.from,
+        to: event.to,
+        roll,
+        total,
+        hit,
+        critical,
+        damage: damage as f64,
+        advantage: adv,
+        disadvantage: disadv,
+        cover,
+    });
+}
+
+/// Rolls initiative for every unit and starts the first round.
+#[derive(Event)]
+pub struct StartCombat;
+
+/// Passes the turn to the next unit that can act.
+#[derive(Event)]
+pub struct EndTurn;
+
+/// Sent when a unit's turn begins.
+#[derive(Event)]
+pub struct TurnStarted(pub Entity);
+
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct Initiative(pub i64);
+
+/// The units in initiative order. `current` indexes whoever's turn it is.
+#[derive(Resource, Default, Debug)]
+pub struct TurnOrder {
+    pub order: Vec<Entity>,
+    pub current: usize,
+    pub round: u32,
+}
+
+impl TurnOrder {
+    pub fn active(&self) -> Option<Entity> {
+        self.order.get(self.current).copied()
+    }
+}
+
+fn roll_initiative(
+    _trigger: Trigger<StartCombat>,
+    mut commands: Commands,
+    mut rng: ResMut<RulesRng>,
+    mut turn_order: ResMut<TurnOrder>,
+    units: Query<(Entity, &Dexterity, Has<Lucky>), With<Unit>>,
+) {
+    let mut rolls = units
+        .iter()
+        .map(|(unit, dex, lucky)| {
+            let modifier = dex.0.calculate_modifier() as i64;
+            (unit, rng.d20(lucky) + modifier, modifier)
+        })
+        .collect::<Vec<(Entity, i64, i64)>>();
+    // Ties go to the higher Dexterity.
+    rolls.sort_by_key(|x| std::cmp::Reverse((x.1, x.2)));
+    for (unit, initiative, _) in &rolls {
+        commands.entity(*unit).insert(Initiative(*initiative));
+    }
+    *turn_order = TurnOrder {
+        order: rolls.into_iter().map(|x| x.0).collect(),
+        current: 0,
+        round: 1,
+    };
+    if let Some(unit) = turn_order.active() {
+        commands.trigger(TurnStarted(unit));
+    }
+}
+
+fn end_turn(
+    _trigger: Trigger<EndTurn>,
+    mut commands: Commands,
+    mut turn_order: ResMut<TurnOrder>,
+    units: Query<Has<Downed>, With<Unit>>,
+) {
+    let turn_order = &mut *turn_order;
+    for _ in 0..turn_order.order.len() {
+        turn_order.current += 1;
+        if turn_order.current >= turn_order.order.len() {
+            // Defeated enemies leave the order at the end of the round.
+            turn_order.order.retain(|x| units.contains(*x));
+            turn_order.current = 0;
+            turn_order.round += 1;
+        }
+        // Downed players keep their place but can't act.
+        if let Some(Ok(false)) = turn_order.active().map(|x| units.get(x)) {
+            commands.trigger(TurnStarted(turn_order.order[turn_order.current]));
+            break;
+        }
+    }
+}
+
+fn handle_taking_damage(
+    trigger: Trigger<TakeDamage>,
+    mut commands: Commands,
+    mut health_query: Query<(
+        &mut Health,
+        Option<&mut TempHealth>,
+        Option<&Player>,
+        &MaxHealth,
+    )>,
+) {
+    info!("Inside taking damage function");
+    let event = trigger.event();
+    let Ok((mut health, temp_health, player, max_health)) = health_query.get_mut(event.unit) else {
+        warn!("{:?} can't take damage", event.unit);
+        return;
+    };
+    info!("Previous health: {}", health.0);
+    // Temporary hit points soak up damage first.
+    let mut amount = event.amount;
+    if let Some(mut temp_health) = temp_health.filter(|x| x.0 > 0.) {
+        let soaked = amount.min(temp_health.0);
+        temp_health.0 -= soaked;
+        amount -= soaked;
+    }
+    if amount <= 0. {
+        return;
+    }
+    health.0 -= amount;
+    if health.0 > 0. {
+        info!("Current health: {}", health.0);
+        return;
+    } else {
+        info!("Uh oh, somebody's in trouble!");
+        match player {
+            None => commands.entity(event.unit).despawn_recursive(),
+            Some(_) => {
+                commands.entity(event.unit).insert(Downed);
+                let dead = health.0.abs() >= (max_health.0.total * 2.);
+                if dead {}
+            }
+        }
+    }
+}
+
+fn handle_rest(
+    trigger: Trigger<Rest>,
+    mut commands: Commands,
+    mut health_query: Query<(Entity, &mut Health, &MaxHealth), With<Player>>,
+) {
+    // Short rests will spend hit dice once those can be rolled.
+    if trigger.event().long {
+        for (unit, mut health, max_health) in &mut health_query {
+            health.0 = max_health.0.total;
+            commands.entity(unit).remove::<Downed>();
+        }
+    }
+    commands.trigger(Autosave(AutosaveReason::Rest));
+}
+
+/// Combat is over once the last enemy has been defeated.
+fn autosave_after_combat(
+    mut commands: Commands,
+    mut defeated: RemovedComponents<Enemy>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    if defeated.read().count() > 0 && enemies.is_empty() {
+        commands.trigger(Autosave(AutosaveReason::CombatEnded));
+    }
+}
@@ -1,4 +1,6 @@
 use bevy::{ecs::query::QueryData, prelude::*};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     marker::PhantomData,
@@ -14,7 +16,7 @@ pub struct ComponentRegistry {
     pub spell: SpellBundle,
 }
 
-#[derive(Bundle, Resource, Default, Reflect)]
+#[derive(Bundle, Default, Reflect)]
 pub struct PlayerBundle {
     pub player_tag: Player,
     pub unit_tag: Unit,
@@ -36,6 +38,7 @@ pub struct PlayerBundle {
     pub level: Level,
     pub hit_dice: HitDice,
     pub settings: SettingsBundle,
+    pub play_time: PlayTime,
 }
 
 #[derive(Bundle, Resource, Default, Reflect)]
@@ -198,6 +201,17 @@ pub enum Proficiency {
     Expert,
 }
 
+impl Proficiency {
+    /// What this adds to a roll, given the unit's proficiency bonus.
+    pub fn bonus(&self, proficiency_bonus: i64) -> i64 {
+        match self {
+            Proficiency::None => 0,
+            Proficiency::Proficient => proficiency_bonus,
+            Proficiency::Expert => proficiency_bonus * 2,
+        }
+    }
+}
+
 #[derive(Component, Default, Clone, Reflect)]
 #[reflect(Component)]
 pub struct Skill {
@@ -226,7 +240,7 @@ pub struct AcroParQ {
 
 impl<'a> AcroParQItem<'a> {
     fn mods(&self) -> f64 {
-        self.s1.calculate_modifier()
+        self.s1.0.calculate_modifier()
     }
 }
 
@@ -359,7 +373,181 @@ pub struct Speed(pub Stat);
 #[reflect(Component)]
 pub struct DarkVision(pub Stat);
 
-#[derive(Component, Default, EnumIter, Display, PartialEq, Eq, Clone, Reflect)]
+#[derive(
+    Component, Default, Reflect, Clone, Copy, Debug, PartialEq, Eq, Display, Serialize, Deserialize,
+)]
+#[reflect(Component)]
+pub enum Size {
+    Tiny,
+    Small,
+    #[default]
+    Medium,
+    Large,
+    Huge,
+    Gargantuan,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Resistances(pub Vec<DamageType>);
+
+/// Damage types a unit has advantage on saving throws against, like a
+/// dwarf against poison.
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct SaveAdvantages(pub Vec<DamageType>);
+
+/// Rerolls natural 1s on attack rolls, ability checks and saving throws.
+#[derive(Component, Default, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Lucky;
+
+#[derive(
+    Default, Reflect, Clone, Copy, Debug, PartialEq, Eq, EnumIter, Display, Serialize, Deserialize,
+)]
+pub enum Language {
+    #[default]
+    Common,
+    Dwarvish,
+    Elvish,
+    Giant,
+    Gnomish,
+    Goblin,
+    Halfling,
+    Orc,
+    Abyssal,
+    Celestial,
+    Draconic,
+    DeepSpeech,
+    Infernal,
+    Primordial,
+    Sylvan,
+    Undercommon,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Languages(pub Vec<Language>);
+
+#[derive(Default, Reflect, Clone, Copy, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
+pub enum ArmorCategory {
+    #[default]
+    Light,
+    Medium,
+    Heavy,
+    Shields,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct ArmorProficiencies(pub Vec<ArmorCategory>);
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct ToolProficiencies(pub Vec<String>);
+
+#[derive(
+    Component,
+    Default,
+    Reflect,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+)]
+#[reflect(Component)]
+pub enum DraconicAncestry {
+    #[default]
+    Black,
+    Blue,
+    Brass,
+    Bronze,
+    Copper,
+    Gold,
+    Green,
+    Red,
+    Silver,
+    White,
+}
+
+// Features are spawned as children of the unit that has them, the same way
+// equipped items are, so their StatModLists are picked up by UpdateStat.
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Feature {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct RacialTrait;
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct ClassFeature {
+    pub level: i64,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct RacialSpell {
+    pub min_level: i64,
+    pub ability: StatEnum,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct BreathWeapon {
+    pub area: String,
+    pub save: StatEnum,
+}
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct BackgroundFeature;
+
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Personality {
+    pub traits: Vec<String>,
+    pub ideal: String,
+    pub bond: String,
+    pub flaw: String,
+}
+
+#[derive(Component, Default, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Gold(pub i64);
+
+/// Every 4d6 roll made for the unit's ability scores at character creation.
+#[derive(Component, Default, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct AbilityRolls(pub Vec<Vec<i64>>);
+
+/// Seconds the unit has spent in game, shown in the save slot list.
+#[derive(Component, Default, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct PlayTime(pub f64);
+
+#[derive(
+    Component,
+    Default,
+    EnumIter,
+    Display,
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    Reflect,
+    Serialize,
+    Deserialize,
+)]
 #[reflect(Component)]
 pub enum Race {
     #[default]
@@ -378,7 +566,19 @@ pub enum Race {
     Human,
     Tiefling,
 }
-#[derive(Component, Default, EnumIter, Display, PartialEq, Eq, Clone, Reflect)]
+#[derive(
+    Component,
+    Default,
+    EnumIter,
+    Display,
+    Debug,
+    PartialEq,
+    Eq,
+    Clone,
+    Reflect,
+    Serialize,
+    Deserialize,
+)]
 #[reflect(Component)]
 pub enum Class {
     #[default]
@@ -395,7 +595,19 @@ pub enum Class {
     Wizard,
 }
 
-#[derive(Component, Default, PartialEq, Eq, EnumIter, Debug, Display, Clone, Reflect)]
+#[derive(
+    Component,
+    Default,
+    PartialEq,
+    Eq,
+    EnumIter,
+    Debug,
+    Display,
+    Clone,
+    Reflect,
+    Serialize,
+    Deserialize,
+)]
 #[reflect(Component)]
 pub enum Background {
     #[default]
@@ -431,7 +643,7 @@ impl ProficiencyBonus {
     }
 }
 
-#[derive(Component, Default, PartialEq, Eq, Debug, Clone, Reflect)]
+#[derive(Component, Default, PartialEq, Debug, Clone, Reflect)]
 #[reflect(Component)]
 pub struct Xp(pub f64);
 
@@ -466,7 +678,19 @@ pub struct SpellName(pub String);
 #[reflect(Component)]
 pub struct Weapon;
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, EnumIter, Display, Reflect, Component)]
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    EnumIter,
+    Display,
+    Reflect,
+    Component,
+    Serialize,
+    Deserialize,
+)]
 #[reflect(Component)]
 pub enum DiceType {
     D2,
@@ -493,15 +717,27 @@ impl DiceType {
             DiceType::D100 => 101,
         }
     }
+
+    pub fn max(&self) -> i64 {
+        self.upper_limit() - 1
+    }
 }
 
-#[derive(Component, Debug, Clone, Default, Reflect)]
+#[derive(Component, Debug, Clone, Default, PartialEq, Reflect, Deserialize)]
 #[reflect(Component)]
 pub struct Dice {
     pub dice_type: DiceType,
     pub number: i64,
 }
 
+impl Dice {
+    pub fn roll(&self, rng: &mut impl Rng) -> i64 {
+        (0..self.number)
+            .map(|_| rng.gen_range(1..self.dice_type.upper_limit()))
+            .sum()
+    }
+}
+
 #[derive(Bundle, Default, Reflect)]
 pub struct DamageBundle {
     pub damage_type: DamageType,
@@ -517,7 +753,7 @@ pub struct ItemBundle {
     pub cost: Cost,
 }
 
-#[derive(Component, Default, Reflect, Debug)]
+#[derive(Component, Default, Reflect, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[reflect(Component)]
 pub enum WeaponType {
     #[default]
@@ -547,7 +783,20 @@ pub struct SpellBundle {
     pub spell_name: SpellName,
 }
 
-#[derive(Component, Default, Reflect)]
+#[derive(
+    Component,
+    Default,
+    Reflect,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    EnumIter,
+    Display,
+    Serialize,
+    Deserialize,
+)]
 #[reflect(Component)]
 pub enum DamageType {
     Acid,
@@ -637,6 +886,11 @@ pub struct Range(pub i64);
 #[reflect(Component)]
 pub struct Health(pub f64);
 
+/// Temporary hit points, lost before `Health` is.
+#[derive(Component, Default, PartialEq, Debug, Clone, Reflect)]
+#[reflect(Component)]
+pub struct TempHealth(pub f64);
+
 #[derive(Component, Default, PartialEq, Debug, Clone, Reflect)]
 #[reflect(Component)]
 pub struct MaxHealth(pub Stat);
@@ -661,7 +915,7 @@ pub struct Advantage;
 #[reflect(Component)]
 pub struct Disadvantage;
 
-#[derive(Component, Debug, Clone, Default, Reflect)]
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Reflect)]
 #[reflect(Component)]
 pub enum Cover {
     #[default]
@@ -725,13 +979,14 @@ pub enum ModType {
     BestOf,
 }
 
-#[derive(Component, Reflect, Default, Clone, PartialEq, Eq, Debug)]
+#[derive(Component, Reflect, Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[reflect(Component)]
 pub enum StatEnum {
     #[default]
     MaxHealth,
     ArmorClass,
     DarkVision,
+    Speed,
     Strength,
     Constitution,
     Dexterity,
@@ -757,3 +1012,60 @@ pub enum StatEnum {
     Performance,
     Persuasion,
 }
+
+impl StatEnum {
+    pub const ABILITIES: [StatEnum; 6] = [
+        StatEnum::Strength,
+        StatEnum::Constitution,
+        StatEnum::Dexterity,
+        StatEnum::Intelligence,
+        StatEnum::Wisdom,
+        StatEnum::Charisma,
+    ];
+
+    pub const SKILLS: [StatEnum; 18] = [
+        StatEnum::Acrobatics,
+        StatEnum::AnimalHandling,
+        StatEnum::Arcana,
+        StatEnum::Athletics,
+        StatEnum::Deception,
+        StatEnum::History,
+        StatEnum::Insight,
+        StatEnum::Intimidation,
+        StatEnum::Investigation,
+        StatEnum::Medicine,
+        StatEnum::Nature,
+        StatEnum::Perception,
+        StatEnum::Performance,
+        StatEnum::Persuasion,
+        StatEnum::Religion,
+        StatEnum::SleightOfHand,
+        StatEnum::Stealth,
+        StatEnum::Survival,
+    ];
+
+    /// The ability a skill is based on, or None if this isn't a skill.
+    pub fn ability(&self) -> Option<StatEnum> {
+        match self {
+            StatEnum::Athletics => Some(StatEnum::Strength),
+            StatEnum::Acrobatics | StatEnum::SleightOfHand | StatEnum::Stealth => {
+                Some(StatEnum::Dexterity)
+            }
+            StatEnum::Arcana
+            | StatEnum::History
+            | StatEnum::Investigation
+            | StatEnum::Nature
+            | StatEnum::Religion => Some(StatEnum::Intelligence),
+            StatEnum::AnimalHandling
+            | StatEnum::Insight
+            | StatEnum::Medicine
+            | StatEnum::Perception
+            | StatEnum::Survival => Some(StatEnum::Wisdom),
+            StatEnum::Deception
+            | StatEnum::Intimidation
+            | StatEnum::Performance
+            | StatEnum::Persuasion => Some(StatEnum::Charisma),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Encounters built from the monster catalog, rated against the party with
+//! the XP budget rules from chapter 3 of the DMG.
+
+use crate::combat::{InGameState, StartCombat};
+use crate::monsters::MonsterCatalog;
+use crate::saves::{ensure_dir, io, slot_name, slot_names, write_atomic, SaveError};
+use crate::AppState;
+use bevy::{ecs::world::Command, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const ENCOUNTER_DIR: &str = "assets/encounters";
+
+/// Easy, medium, hard and deadly XP thresholds for one character of each
+/// level from 1 to 20.
+const XP_THRESHOLDS: [[u32; 4]; 20] = [
+    [25, 50, 75, 100],
+    [50, 100, 150, 200],
+    [75, 150, 225, 400],
+    [125, 250, 375, 500],
+    [250, 500, 750, 1100],
+    [300, 600, 900, 1400],
+    [350, 750, 1100, 1700],
+    [450, 900, 1400, 2100],
+    [550, 1100, 1600, 2400],
+    [600, 1200, 1900, 2800],
+    [800, 1600, 2400, 3600],
+    [1000, 2000, 3000, 4500],
+    [1100, 2200, 3400, 5100],
+    [1250, 2500, 3800, 5700],
+    [1400, 2800, 4300, 6400],
+    [1600, 3200, 4800, 7200],
+    [2000, 3900, 5900, 8800],
+    [2100, 4200, 6300, 9500],
+    [2400, 4900, 7300, 10900],
+    [2800, 5700, 8500, 12700],
+];
+
+/// Encounter multipliers, from a single monster against a large party up to
+/// fifteen or more against a small one.
+const MULTIPLIERS: [f64; 8] = [0.5, 1., 1.5, 2., 2.5, 3., 4., 5.];
+
+/// The party's XP thresholds, summed over its characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XpThresholds {
+    pub easy: u32,
+    pub medium: u32,
+    pub hard: u32,
+    pub deadly: u32,
+}
+
+impl XpThresholds {
+    pub fn for_party(levels: &[i64]) -> Self {
+        levels.iter().fold(Self::default(), |total, level| {
+            let [easy, medium, hard, deadly] = XP_THRESHOLDS[(*level).clamp(1, 20) as usize - 1];
+            Self {
+                easy: total.easy + easy,
+                medium: total.medium + medium,
+                hard: total.hard + hard,
+                deadly: total.deadly + deadly,
+            }
+        })
+    }
+
+    pub fn difficulty(&self, adjusted_xp: f64) -> Difficulty {
+        match adjusted_xp {
+            x if x >= self.deadly as f64 => Difficulty::Deadly,
+            x if x >= self.hard as f64 => Difficulty::Hard,
+            x if x >= self.medium as f64 => Difficulty::Medium,
+            x if x >= self.easy as f64 => Difficulty::Easy,
+            _ => Difficulty::Trivial,
+        }
+    }
+}
+
+/// How much harder a group of monsters is than their XP alone suggests.
+/// Parties of fewer than three use the next multiplier up and parties of six
+/// or more the next one down.
+pub fn group_multiplier(monsters: u32, party_size: usize) -> f64 {
+    let step = match monsters {
+        0 | 1 => 1,
+        2 => 2,
+        3..=6 => 3,
+        7..=10 => 4,
+        11..=14 => 5,
+        _ => 6,
+    };
+    let step = match party_size {
+        0..=2 => step + 1,
+        3..=5 => step,
+        _ => step - 1,
+    };
+    MULTIPLIERS[step]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Trivial,
+    Easy,
+    Medium,
+    Hard,
+    Deadly,
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncounterRating {
+    pub thresholds: XpThresholds,
+    /// The monsters' XP added up. This is what the party earns.
+    pub xp: u32,
+    pub multiplier: f64,
+    /// `xp` times the group multiplier, which the difficulty is judged by.
+    pub adjusted_xp: f64,
+    pub difficulty: Difficulty,
+}
+
+/// A reusable set of monsters, saved as RON in `ENCOUNTER_DIR`:
+///
+/// ```ron
+/// (name: "Goblin ambush", monsters: [(name: "Goblin", count: 4), (name: "Bugbear", count: 1)])
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct Encounter {
+    pub name: String,
+    pub monsters: Vec<EncounterMonster>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct EncounterMonster {
+    pub name: String,
+    pub count: u32,
+}
+
+impl Encounter {
+    pub fn add(&mut self, name: &str) {
+        match self.monsters.iter_mut().find(|x| x.name == name) {
+            Some(monster) => monster.count += 1,
+            None => self.monsters.push(EncounterMonster {
+                name: name.to_string(),
+                count: 1,
+            }),
+        }
+    }
+
+    /// Takes one `name` out, dropping its entry once none are left.
+    pub fn remove(&mut self, name: &str) {
+        if let Some(monster) = self.monsters.iter_mut().find(|x| x.name == name) {
+            monster.count = monster.count.saturating_sub(1);
+        }
+        self.monsters.retain(|x| x.count > 0);
+    }
+
+    pub fn monster_count(&self) -> u32 {
+        self.monsters.iter().map(|x| x.count).sum()
+    }
+
+    /// One name per monster, e.g. for `simulation::simulate`.
+    pub fn monster_names(&self) -> Vec<String> {
+        self.monsters
+            .iter()
+            .flat_map(|x| std::iter::repeat_n(x.name.clone(), x.count as usize))
+            .collect()
+    }
+
+    /// Rates the encounter against a party of the given levels. Monsters
+    /// missing from the catalog are worth no XP.
+    pub fn rate(&self, catalog: &MonsterCatalog, levels: &[i64]) -> EncounterRating {
+        let thresholds = XpThresholds::for_party(levels);
+        let xp = self
+            .monsters
+            .iter()
+            .map(|x| catalog.get(&x.name).map_or(0, |data| data.xp) * x.count)
+            .sum();
+        let multiplier = group_multiplier(self.monster_count(), levels.len());
+        let adjusted_xp = xp as f64 * multiplier;
+        EncounterRating {
+            thresholds,
+            xp,
+            multiplier,
+            adjusted_xp,
+            difficulty: thresholds.difficulty(adjusted_xp),
+        }
+    }
+}
+
+pub fn encounter_path(dir: &Path, slot: &str) -> PathBuf {
+    dir.join(format!("{slot}.encounter.ron"))
+}
+
+/// Writes the encounter under a slot named after it, which is returned.
+pub fn save_encounter(dir: &Path, encounter: &Encounter) -> Result<String, SaveError> {
+    let text = ron::ser::to_string_pretty(encounter, ron::ser::PrettyConfig::default())
+        .map_err(|e| SaveError::Serialize(e.to_string()))?;
+    let slot = slot_name(&encounter.name);
+    ensure_dir(dir)?;
+    write_atomic(&encounter_path(dir, &slot), &text)?;
+    Ok(slot)
+}
+
+pub fn load_encounter(dir: &Path, slot: &str) -> Result<Encounter, SaveError> {
+    let path = encounter_path(dir, slot);
+    let text = io(&path, fs::read_to_string(&path))?;
+    ron::from_str(&text).map_err(|e| SaveError::Parse(path, e.to_string()))
+}
+
+/// The slots of every saved encounter, sorted by name.
+pub fn list_encounters(dir: &Path) -> Result<Vec<String>, SaveError> {
+    slot_names(dir, ".encounter.ron")
+}
+
+/// Spawns the encounter's monsters as enemies, rolls initiative and enters
+/// `InGameState::Combat`.
+pub struct StartEncounter(pub Encounter);
+
+impl Command for StartEncounter {
+    fn apply(self, world: &mut World) {
+        let monsters = {
+            let catalog = world.resource::<MonsterCatalog>();
+            self.0
+                .monster_names()
+                .iter()
+                .filter_map(|x| catalog.get(x).cloned())
+                .collect::<Vec<_>>()
+        };
+        for monster in monsters {
+            monster.spawn(world);
+        }
+        world.trigger(StartCombat);
+        world.flush();
+        match world.get_resource::<State<AppState>>().map(|x| x.get()) {
+            Some(AppState::InGame) => world
+                .resource_mut::<NextState<InGameState>>()
+                .set(InGameState::Combat),
+            // Combat is where the game starts.
+            _ => world
+                .resource_mut::<NextState<AppState>>()
+                .set(AppState::InGame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> MonsterCatalog {
+        MonsterCatalog::from_ron(include_str!("../assets/data/monsters.ron"))
+    }
+
+    #[test]
+    fn thresholds_add_up_over_the_party() {
+        let thresholds = XpThresholds::for_party(&[3, 3, 3, 2]);
+        assert_eq!(
+            thresholds,
+            XpThresholds {
+                easy: 275,
+                medium: 550,
+                hard: 825,
+                deadly: 1400,
+            }
+        );
+        // Levels outside 1 to 20 are clamped.
+        assert_eq!(
+            XpThresholds::for_party(&[0, 25]),
+            XpThresholds::for_party(&[1, 20])
+        );
+    }
+
+    #[test]
+    fn multipliers_follow_group_and_party_size() {
+        assert_eq!(group_multiplier(1, 4), 1.);
+        assert_eq!(group_multiplier(2, 4), 1.5);
+        assert_eq!(group_multiplier(6, 4), 2.);
+        assert_eq!(group_multiplier(7, 4), 2.5);
+        assert_eq!(group_multiplier(15, 4), 4.);
+        // Small parties find groups harder, large ones easier.
+        assert_eq!(group_multiplier(1, 2), 1.5);
+        assert_eq!(group_multiplier(15, 1), 5.);
+        assert_eq!(group_multiplier(1, 6), 0.5);
+        assert_eq!(group_multiplier(2, 6), 1.);
+    }
+
+    #[test]
+    fn encounters_are_rated_by_adjusted_xp() {
+        let mut encounter = Encounter::default();
+        for _ in 0..4 {
+            encounter.add("Goblin");
+        }
+        encounter.add("Bugbear");
+        assert_eq!(encounter.monster_count(), 5);
+
+        // 4 × 50 + 200 = 400 XP, doubled for five monsters.
+        let rating = encounter.rate(&catalog(), &[3, 3, 3, 2]);
+        assert_eq!(rating.xp, 400);
+        assert_eq!(rating.adjusted_xp, 800.);
+        assert_eq!(rating.difficulty, Difficulty::Medium);
+
+        encounter.remove("Bugbear");
+        encounter.remove("Goblin");
+        assert_eq!(encounter.monster_names(), vec!["Goblin"; 3]);
+        assert_eq!(
+            encounter.rate(&catalog(), &[3, 3, 3, 2]).difficulty,
+            Difficulty::Easy
+        );
+        assert_eq!(
+            Encounter::default().rate(&catalog(), &[1]).difficulty,
+            Difficulty::Trivial
+        );
+    }
+
+    #[test]
+    fn saved_encounters_load_back() {
+        let dir = crate::saves::scratch_dir("encounters");
+        let encounter = Encounter {
+            name: "Goblin ambush!".to_string(),
+            monsters: vec![EncounterMonster {
+                name: "Goblin".to_string(),
+                count: 4,
+            }],
+        };
+        let slot = save_encounter(&dir, &encounter).unwrap();
+        assert_eq!(slot, "Goblin-ambush-");
+        assert_eq!(list_encounters(&dir).unwrap(), vec![slot.clone()]);
+        assert_eq!(load_encounter(&dir, &slot).unwrap(), encounter);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,11 @@
 use crate::components::*;
+use crate::party::Party;
 use bevy::{
     ecs::{system::EntityCommands, world::Command},
     prelude::*,
     reflect::DynamicTypePath,
 };
+use serde::Deserialize;
 use std::{marker::PhantomData, mem::discriminant};
 
 pub struct ItemsPlugin;
@@ -16,10 +18,165 @@ impl Plugin for ItemsPlugin {
         app.add_systems(Update, equip_item.run_if(on_event::<EquipItem>()));
         app.add_systems(Update, unequip_item.run_if(on_event::<UnequipItem>()));
         app.observe(spawn_item);
+        app.insert_resource(ArmorCatalog::from_ron(include_str!(
+            "../assets/data/armor.ron"
+        )));
+        app.insert_resource(WeaponCatalog::from_ron(include_str!(
+            "../assets/data/weapons.ron"
+        )));
     }
 }
 
-pub struct UpdateStat(Entity, StatEnum);
+#[derive(Resource, Default)]
+pub struct ArmorCatalog(pub Vec<ArmorData>);
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ArmorData {
+    pub name: String,
+    pub category: ArmorCategory,
+    pub base_ac: f64,
+    pub dex_cap: Option<f64>,
+    pub strength: f64,
+    pub stealth_disadvantage: bool,
+}
+
+impl ArmorCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("armor.ron to be a valid armor catalog"))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ArmorData> {
+        self.0.iter().find(|x| x.name == name)
+    }
+
+    /// Armor class for a character carrying `items`: the best body armor
+    /// among them plus a shield if there is one. Without body armor this is
+    /// 10 + Dexterity, plus `unarmored_bonus` for features like the
+    /// barbarian's Unarmored Defense.
+    pub fn armor_class(&self, items: &[String], dex_modifier: f64, unarmored_bonus: f64) -> f64 {
+        let armor = items.iter().filter_map(|x| self.get(x));
+        let body = armor
+            .clone()
+            .filter(|x| x.category != ArmorCategory::Shields)
+            .map(|x| match x.dex_cap {
+                Some(cap) => x.base_ac + dex_modifier.min(cap),
+                None => x.base_ac + dex_modifier,
+            })
+            .reduce(f64::max)
+            .unwrap_or(10. + dex_modifier + unarmored_bonus);
+        let shield = armor
+            .filter(|x| x.category == ArmorCategory::Shields)
+            .map(|x| x.base_ac)
+            .reduce(f64::max)
+            .unwrap_or(0.);
+        body + shield
+    }
+}
+
+/// The weapon every unit can fall back on, and is proficient with.
+pub const UNARMED_STRIKE: &str = "Unarmed Strike";
+
+#[derive(Resource, Default)]
+pub struct WeaponCatalog(pub Vec<WeaponData>);
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WeaponData {
+    pub name: String,
+    pub weapon_type: WeaponType,
+    pub dice: Dice,
+    pub base_damage: i64,
+    pub damage_type: DamageType,
+    pub finesse: bool,
+    /// In feet, for ranged and thrown weapons. 0 if it can't be shot or
+    /// thrown.
+    pub range: i64,
+    pub max_range: i64,
+    pub reach: bool,
+}
+
+impl WeaponCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("weapons.ron to be a valid weapon catalog"))
+    }
+
+    /// Looks a weapon up by item name. Bundles like "Dart (10)" are found
+    /// under their weapon.
+    pub fn get(&self, name: &str) -> Option<&WeaponData> {
+        let name = match name.rsplit_once(" (") {
+            Some((weapon, count)) if count.ends_with(')') => weapon,
+            _ => name,
+        };
+        self.0.iter().find(|x| x.name == name)
+    }
+}
+
+impl WeaponData {
+    /// The components that make an item of this name a weapon.
+    pub fn components(&self) -> (Weapon, DamageBundle, WeaponType) {
+        (
+            Weapon,
+            DamageBundle {
+                damage_type: self.damage_type,
+                base_damage: BaseDamage(self.base_damage),
+                dice: self.dice.clone(),
+            },
+            self.weapon_type,
+        )
+    }
+
+    /// Makes `item` a weapon of this kind.
+    pub fn insert(&self, item: &mut EntityWorldMut) {
+        item.insert(self.components());
+        if self.finesse {
+            item.insert(Finesse);
+        }
+        if self.range > 0 {
+            item.insert((Range(self.range), MaxRange(self.max_range)));
+        }
+        if self.reach {
+            item.insert(Reach);
+        }
+    }
+
+    /// Spawns a weapon item of this kind.
+    pub fn spawn(&self, world: &mut World) -> Entity {
+        let mut weapon = world.spawn(ItemBundle {
+            item_marker: Item,
+            name: ItemName(self.name.clone()),
+            ..default()
+        });
+        self.insert(&mut weapon);
+        weapon.id()
+    }
+}
+
+/// Turns the items a unit carries into weapons where the weapon catalog
+/// knows them, so starting equipment and older saves can attack with them.
+pub struct ArmWeapons(pub Entity);
+
+impl Command for ArmWeapons {
+    fn apply(self, world: &mut World) {
+        let Some(children) = world.get::<Children>(self.0) else {
+            return;
+        };
+        let weapons = children
+            .iter()
+            .filter(|x| world.get::<Weapon>(**x).is_none())
+            .filter_map(|x| {
+                let name = world.get::<ItemName>(*x)?;
+                let data = world.resource::<WeaponCatalog>().get(&name.0)?;
+                Some((*x, data.clone()))
+            })
+            .collect::<Vec<(Entity, WeaponData)>>();
+        for (item, data) in weapons {
+            data.insert(&mut world.entity_mut(item));
+        }
+    }
+}
+
+pub struct UpdateStat(pub Entity, pub StatEnum);
 
 impl Command for UpdateStat {
     fn apply(self, world: &mut World) {
@@ -80,6 +237,7 @@ impl Command for UpdateStat {
                 (&mut world.get_mut::<MaxHealth>(unit_id).unwrap().0, 0.)
             }
             StatEnum::DarkVision => (&mut world.get_mut::<DarkVision>(unit_id).unwrap().0, 0.),
+            StatEnum::Speed => (&mut world.get_mut::<Speed>(unit_id).unwrap().0, 0.),
             StatEnum::Acrobatics => {
                 let parent = world
                     .get::<Dexterity>(unit_id)
@@ -280,7 +438,7 @@ impl Command for UpdateStat {
     }
 }
 
-trait UpdateStatExt {
+pub trait UpdateStatExt {
     fn update_stat(&mut self, stat: StatEnum);
 }
 
@@ -291,6 +449,104 @@ impl UpdateStatExt for EntityCommands<'_> {
     }
 }
 
+/// Raises the proficiency of an ability (saving throw) or skill on a unit.
+/// Never lowers it, so an Expert skill stays Expert when a second source
+/// grants plain proficiency.
+pub struct SetProficiency(pub Entity, pub StatEnum, pub Proficiency);
+
+impl Command for SetProficiency {
+    fn apply(self, world: &mut World) {
+        let unit_id = self.0;
+        let current = match self.1 {
+            StatEnum::Strength => world
+                .get_mut::<Strength>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Constitution => world
+                .get_mut::<Constitution>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Dexterity => world
+                .get_mut::<Dexterity>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Intelligence => world
+                .get_mut::<Intelligence>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Wisdom => world
+                .get_mut::<Wisdom>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Charisma => world
+                .get_mut::<Charisma>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Athletics => world
+                .get_mut::<Athletics>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Acrobatics => world
+                .get_mut::<Acrobatics>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::SleightOfHand => world
+                .get_mut::<SleightOfHand>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Stealth => world
+                .get_mut::<Stealth>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Arcana => world
+                .get_mut::<Arcana>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::History => world
+                .get_mut::<History>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Investigation => world
+                .get_mut::<Investigation>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Nature => world
+                .get_mut::<Nature>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Religion => world
+                .get_mut::<Religion>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::AnimalHandling => world
+                .get_mut::<AnimalHandling>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Insight => world
+                .get_mut::<Insight>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Medicine => world
+                .get_mut::<Medicine>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Perception => world
+                .get_mut::<Perception>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Survival => world
+                .get_mut::<Survival>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Deception => world
+                .get_mut::<Deception>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Intimidation => world
+                .get_mut::<Intimidation>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Performance => world
+                .get_mut::<Performance>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            StatEnum::Persuasion => world
+                .get_mut::<Persuasion>(unit_id)
+                .map(|x| x.map_unchanged(|x| &mut x.0.proficiency)),
+            _ => None,
+        };
+        let Some(mut current) = current else {
+            warn!("{:?} has no proficiency to set on {:?}", self.1, unit_id);
+            return;
+        };
+        let rank = |p: &Proficiency| match p {
+            Proficiency::None => 0,
+            Proficiency::Proficient => 1,
+            Proficiency::Expert => 2,
+        };
+        if rank(&self.2) > rank(&current) {
+            *current = self.2;
+        }
+    }
+}
+
 fn equip_item(mut evr: EventReader<EquipItem>, modq: Query<&StatModList>, mut commands: Commands) {
     for ev in evr.read() {
         info!("Inside equip_item");
@@ -321,16 +577,19 @@ fn spawn_item(
     trigger: Trigger<SpawnItem>,
     mut commands: Commands,
     mut ev_w: EventWriter<EquipItem>,
-    player_q: Query<Entity, With<Player>>,
+    party: Party,
 ) {
-    let unit = player_q.single();
+    let Some(unit) = party.active() else {
+        warn!("No player character to give {:?} to", trigger.event().0);
+        return;
+    };
     info!("Inside spawn_item");
     let item = trigger.event().0.spawn_id(&mut commands);
-    // other stuff later, for now just equip it to the player
+    // other stuff later, for now just equip it to the active character
     ev_w.send(EquipItem { unit, item });
 }
 
-#[derive(Component, Default, Reflect)]
+#[derive(Component, Default, Reflect, Deserialize, Clone, Debug)]
 #[reflect(Component)]
 pub enum ItemsEnum {
     #[default]
@@ -339,7 +598,7 @@ pub enum ItemsEnum {
 }
 
 impl ItemsEnum {
-    fn spawn_id(&self, commands: &mut Commands) -> Entity {
+    pub fn spawn_id(&self, commands: &mut Commands) -> Entity {
         match self {
             ItemsEnum::Club => commands.spawn(Club::default()).id(),
             ItemsEnum::RingOfHealth => commands.spawn(RingOfHealth::default()).id(),
@@ -348,15 +607,15 @@ impl ItemsEnum {
 }
 
 #[derive(Event)]
-struct EquipItem {
-    unit: Entity,
-    item: Entity,
+pub struct EquipItem {
+    pub unit: Entity,
+    pub item: Entity,
 }
 
 #[derive(Event)]
-struct UnequipItem {
-    unit: Entity,
-    item: Entity,
+pub struct UnequipItem {
+    pub unit: Entity,
+    pub item: Entity,
 }
 
 #[derive(Event)]
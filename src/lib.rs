@@ -0,0 +1,177 @@
+//! The rules of the game as Bevy plugins. `RulesPlugin` holds everything
+//! that changes the world and runs headless under `MinimalPlugins`; the
+//! windowed game adds `StatePlugins` and the egui plugins on top of it.
+
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use components::{Dice, DiceType};
+use rand::{rngs::StdRng, SeedableRng};
+
+pub mod ability_scores;
+pub mod areas;
+pub mod autosave;
+pub mod backgrounds;
+pub mod character;
+pub mod checks;
+pub mod classes;
+pub mod combat;
+pub mod components;
+pub mod encounters;
+pub mod items;
+pub mod map;
+pub mod migrations;
+pub mod monsters;
+pub mod movement;
+pub mod party;
+pub mod pdf;
+pub mod races;
+pub mod saves;
+pub mod scenario;
+pub mod simulation;
+pub mod states;
+pub mod ui;
+pub mod uvtt;
+pub mod vision;
+
+use areas::AreasPlugin;
+use autosave::AutosavePlugin;
+use backgrounds::BackgroundsPlugin;
+use checks::ChecksPlugin;
+use classes::ClassesPlugin;
+use combat::CombatPlugin;
+use components::ComponentRegistry;
+use items::ItemsPlugin;
+use map::MapPlugin;
+use monsters::MonstersPlugin;
+use movement::MovementPlugin;
+use party::PartyPlugin;
+use races::RacesPlugin;
+use saves::SavesPlugin;
+use vision::VisionPlugin;
+
+/// Stats, items, races, classes, backgrounds, monsters, the party, the battle map,
+/// light and sight, area effects, checks, combat and saves. Doesn't open a window or draw anything.
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        // Both come with DefaultPlugins but not with MinimalPlugins.
+        if !app.is_plugin_added::<StatesPlugin>() {
+            app.add_plugins(StatesPlugin);
+        }
+        if !app.is_plugin_added::<HierarchyPlugin>() {
+            app.add_plugins(HierarchyPlugin);
+        }
+        app.add_plugins(ItemsPlugin)
+            .add_plugins(RacesPlugin)
+            .add_plugins(ClassesPlugin)
+            .add_plugins(BackgroundsPlugin)
+            .add_plugins(MonstersPlugin)
+            .add_plugins(MapPlugin)
+            .add_plugins(MovementPlugin)
+            .add_plugins(AreasPlugin)
+            .add_plugins(ChecksPlugin)
+            .add_plugins(VisionPlugin)
+            .add_plugins(PartyPlugin)
+            .add_plugins(SavesPlugin)
+            .add_plugins(AutosavePlugin)
+            .init_resource::<RulesRng>()
+            .register_type::<ComponentRegistry>()
+            .insert_state(AppState::Startup)
+            .add_plugins(CombatPlugin);
+    }
+}
+
+/// The random number generator for every roll the rules make. Seed it to
+/// replay the same rolls, e.g. in tests and simulations.
+#[derive(Resource)]
+pub struct RulesRng(pub StdRng);
+
+impl RulesRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+
+    /// Rolls a d20 for an attack roll, ability check or saving throw. A
+    /// `Lucky` roller rerolls a natural 1 and must use the new roll.
+    pub fn d20(&mut self, lucky: bool) -> i64 {
+        let d20 = Dice {
+            dice_type: DiceType::D20,
+            number: 1,
+        };
+        match d20.roll(&mut self.0) {
+            1 if lucky => d20.roll(&mut self.0),
+            roll => roll,
+        }
+    }
+}
+
+impl Default for RulesRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+#[derive(States, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppState {
+    Startup,
+    MainMenu,
+    LoadCharacter,
+    NewCharacter,
+    SaveCharacter,
+    InGame,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::character::{CharacterBuilder, SpawnCharacter};
+    use crate::combat::InGameState;
+    use crate::components::*;
+
+    #[test]
+    fn rules_run_without_a_window() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, RulesPlugin));
+        app.update();
+        app.world_mut().commands().add(SpawnCharacter(
+            CharacterBuilder::default()
+                .name("Brom")
+                .abilities([15., 14., 12., 8., 13., 10.]),
+        ));
+        app.world_mut()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        app.update();
+
+        let world = app.world_mut();
+        assert_eq!(
+            world.resource::<State<InGameState>>().get(),
+            &InGameState::Combat
+        );
+        let name = world
+            .query_filtered::<&UnitName, With<Player>>()
+            .single(world);
+        assert_eq!(name.0, "Brom");
+    }
+
+    #[test]
+    fn lucky_rollers_reroll_a_natural_one() {
+        let seed = (0..)
+            .find(|x| RulesRng::seeded(*x).d20(false) == 1)
+            .unwrap();
+        let mut unlucky = RulesRng::seeded(seed);
+        assert_eq!(unlucky.d20(false), 1);
+        // The reroll is the next d20, and it stands even if it's a 1 too.
+        let reroll = unlucky.d20(false);
+        assert_eq!(RulesRng::seeded(seed).d20(true), reroll);
+        // Anything but a 1 isn't rerolled.
+        let seed = (0..)
+            .find(|x| RulesRng::seeded(*x).d20(false) != 1)
+            .unwrap();
+        assert_eq!(
+            RulesRng::seeded(seed).d20(true),
+            RulesRng::seeded(seed).d20(false)
+        );
+    }
+}
@@ -1,33 +1,19 @@
-use bevy::ecs::system::SystemState;
-use bevy::ecs::world::Command;
-use bevy::{prelude::*, tasks::IoTaskPool};
+use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use items::ItemsPlugin;
-use std::fs::File;
-use std::io::Write;
-
-mod components;
-mod items;
-mod states;
-mod ui;
-
-use components::*;
-use states::StatePlugins;
-use ui::*;
+use newtable::components::*;
+use newtable::states::StatePlugins;
+use newtable::{AppState, RulesPlugin};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugins(RulesPlugin)
         .add_plugins(StatePlugins)
-        .add_plugins(ItemsPlugin)
         .add_plugins(EguiPlugin)
         .add_plugins(WorldInspectorPlugin::new())
-        .register_type::<ComponentRegistry>()
         .add_systems(Startup, setup)
         .add_systems(Update, button_system)
-        .insert_state(AppState::Startup)
-        .add_systems(OnEnter(AppState::SaveCharacter), save_game)
         .run();
 }
 
@@ -79,38 +65,6 @@ fn setup(mut commands: Commands, mut next_state: ResMut<NextState<AppState>>) {
     next_state.set(AppState::MainMenu);
 }
 
-fn save_game(world: &mut World, params: &mut SystemState<ResMut<NextState<AppState>>>) {
-    let mut units: QueryState<
-        (Entity, Option<&Children>),
-        Or<(With<Unit>, With<Item>, With<Spell>)>,
-    > = QueryState::new(world);
-    let parents = units.iter(world).map(|x| x.0);
-    let mut builder = DynamicSceneBuilder::from_world(world).extract_entities(parents);
-    let children = units.iter(world).filter_map(|x| x.1);
-    builder = builder.extract_entities(children.flatten().map(|x| *x));
-    let scene = builder.build();
-    let registry = world.resource::<AppTypeRegistry>();
-    let serialized_scene = scene.serialize(&registry.read()).unwrap();
-    IoTaskPool::get()
-        .spawn(async move {
-            // Write the scene RON data to file
-            File::create(format!("assets/saves/test-save.scn.ron"))
-                .and_then(|mut file| file.write_all(serialized_scene.as_bytes()))
-                .expect("Error while writing scene to file");
-        })
-        .detach();
-    {
-        let mut state = params.get_mut(world);
-        state.set(AppState::InGame);
-    }
-    params.apply(world);
-}
-
-fn despawn_ui(mut commands: Commands, ui_root: Query<Entity, With<RootUI>>) {
-    let root = ui_root.single();
-    commands.entity(root).despawn_descendants();
-}
-
 fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, &ButtonType),
@@ -132,13 +86,3 @@ fn button_system(
         }
     }
 }
-
-#[derive(States, Debug, Clone, PartialEq, Eq, Hash)]
-enum AppState {
-    Startup,
-    MainMenu,
-    LoadCharacter,
-    NewCharacter,
-    SaveCharacter,
-    InGame,
-}
@@ -0,0 +1,507 @@
+//! The battle map: a grid of 5 ft squares that units stand on. Positions are
+//! kept as grid coordinates so they save with the unit and don't depend on
+//! how the map is drawn. Maps are saved as RON in `MAP_DIR`, with their
+//! background images next to them.
+
+use crate::combat::Downed;
+use crate::components::*;
+use crate::migrations::MigrationError;
+use crate::saves::{ensure_dir, io, slot_name, slot_names, write_atomic, SaveError};
+use crate::vision::{LightLevel, LightSource, Vision};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The size of a grid square.
+pub const CELL_FEET: f64 = 5.;
+
+pub const MAP_DIR: &str = "assets/maps";
+
+/// Where imported background images are kept, under the asset folder.
+pub const MAP_IMAGE_DIR: &str = "maps/images";
+
+/// The version of `MapFile` this build writes.
+pub const MAP_VERSION: u32 = 1;
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GridPosition>();
+        app.init_resource::<BattleMap>();
+        app.init_resource::<DiagonalRule>();
+        app.observe(toggle_door);
+        app.add_systems(Update, place_units);
+    }
+}
+
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct GridPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl GridPosition {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    /// Squares between this one and `other`, counting a diagonal step as
+    /// one.
+    pub fn cells_to(&self, other: GridPosition) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    /// Distance in feet under `rule`, as a straight line of squares.
+    pub fn distance(&self, other: GridPosition, rule: DiagonalRule) -> f64 {
+        let dx = (self.x - other.x).abs();
+        let dy = (self.y - other.y).abs();
+        let diagonals = dx.min(dy);
+        let straight = dx.max(dy) - diagonals;
+        let diagonal_cells = match rule {
+            DiagonalRule::Uniform => diagonals,
+            DiagonalRule::Alternating => diagonals + diagonals / 2,
+        };
+        (straight + diagonal_cells) as f64 * CELL_FEET
+    }
+}
+
+/// How much a diagonal step costs.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagonalRule {
+    /// Every step costs 5 ft (5/5/5), as in the PHB.
+    #[default]
+    Uniform,
+    /// Every second diagonal costs 10 ft (5/10/5), the DMG variant.
+    Alternating,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Door {
+    Open,
+    Closed,
+    /// Closed, and stays that way until someone unlocks it.
+    Locked,
+}
+
+/// An image drawn under the grid, and how it lines up with it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Background {
+    /// Relative to the asset folder, e.g. `maps/images/crypt.png`.
+    pub image: String,
+    /// How many of the image's pixels one grid square spans.
+    pub pixels_per_square: f32,
+    /// The pixel, from the image's top left, where the grid's top left
+    /// corner is.
+    pub offset: (f32, f32),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            image: String::new(),
+            pixels_per_square: 70.,
+            offset: (0., 0.),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct BattleMap {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub background: Option<Background>,
+    /// Squares nobody can enter or see through.
+    pub walls: HashSet<GridPosition>,
+    /// Closed and locked doors are walls until they're opened.
+    pub doors: HashMap<GridPosition, Door>,
+    /// Squares that cost double to move into.
+    pub difficult: HashSet<GridPosition>,
+    /// Squares that hurt to stand in, like fire or spikes. How much is up
+    /// to the GM.
+    pub hazards: HashSet<GridPosition>,
+    /// How well lit the map is, unless `lit` says otherwise for a square.
+    pub light: LightLevel,
+    pub lit: HashMap<GridPosition, LightLevel>,
+    /// Torches in sconces and the like, that nobody carries.
+    pub lights: HashMap<GridPosition, LightSource>,
+}
+
+impl Default for BattleMap {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            width: 20,
+            height: 15,
+            background: None,
+            walls: HashSet::default(),
+            doors: HashMap::default(),
+            difficult: HashSet::default(),
+            hazards: HashSet::default(),
+            light: LightLevel::Bright,
+            lit: HashMap::default(),
+            lights: HashMap::default(),
+        }
+    }
+}
+
+impl BattleMap {
+    pub fn contains(&self, pos: GridPosition) -> bool {
+        (0..self.width).contains(&pos.x) && (0..self.height).contains(&pos.y)
+    }
+
+    /// Sets the size of the map, dropping everything on squares that no
+    /// longer fit.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        let inside =
+            |pos: &GridPosition| (0..width).contains(&pos.x) && (0..height).contains(&pos.y);
+        self.walls.retain(inside);
+        self.doors.retain(|x, _| inside(x));
+        self.difficult.retain(inside);
+        self.hazards.retain(inside);
+        self.lit.retain(|x, _| inside(x));
+        self.lights.retain(|x, _| inside(x));
+    }
+
+    /// The light a square has before light sources are counted.
+    pub fn light_at(&self, pos: GridPosition) -> LightLevel {
+        self.lit.get(&pos).copied().unwrap_or(self.light)
+    }
+
+    /// Whether `pos` is a wall or a door that isn't open.
+    pub fn blocks(&self, pos: GridPosition) -> bool {
+        self.walls.contains(&pos) || self.doors.get(&pos).is_some_and(|x| *x != Door::Open)
+    }
+
+    /// Whether a unit could stand on `pos` if nobody else were there.
+    pub fn is_open(&self, pos: GridPosition) -> bool {
+        self.contains(pos) && !self.blocks(pos)
+    }
+}
+
+/// Opens a closed door or closes an open one. Locked doors stay shut.
+#[derive(Event)]
+pub struct ToggleDoor(pub GridPosition);
+
+fn toggle_door(trigger: Trigger<ToggleDoor>, mut map: ResMut<BattleMap>) {
+    let pos = trigger.event().0;
+    let door = match map.doors.get(&pos) {
+        Some(Door::Open) => Door::Closed,
+        Some(Door::Closed) => Door::Open,
+        Some(Door::Locked) => {
+            info!("The door is locked");
+            return;
+        }
+        None => return,
+    };
+    map.doors.insert(pos, door);
+}
+
+/// A `BattleMap` as it's saved in `MAP_DIR`. Squares are `(x, y)` pairs
+/// counted from the bottom left, kept sorted so that saving the same map
+/// twice gives the same file:
+///
+/// ```ron
+/// (
+///     version: 1,
+///     name: "Crypt",
+///     width: 12,
+///     height: 8,
+///     background: Some((image: "maps/images/crypt.png", pixels_per_square: 70, offset: (35, 0))),
+///     walls: [(0, 0), (0, 1)],
+///     doors: [((4, 3), Locked)],
+///     difficult: [(6, 2)],
+///     hazards: [],
+///     light: Dim,
+///     lit: [((8, 5), Dark)],
+///     lights: [((2, 2), (bright: 20, dim: 20))],
+/// )
+/// ```
+///
+/// Everything but `version` may be left out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MapFile {
+    pub version: u32,
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub background: Option<Background>,
+    pub walls: Vec<(i32, i32)>,
+    pub doors: Vec<((i32, i32), Door)>,
+    pub difficult: Vec<(i32, i32)>,
+    pub hazards: Vec<(i32, i32)>,
+    pub light: LightLevel,
+    pub lit: Vec<((i32, i32), LightLevel)>,
+    pub lights: Vec<((i32, i32), LightSource)>,
+}
+
+impl Default for MapFile {
+    fn default() -> Self {
+        Self::from(&BattleMap::default())
+    }
+}
+
+fn sorted(cells: &HashSet<GridPosition>) -> Vec<(i32, i32)> {
+    let mut cells = cells.iter().map(|x| (x.x, x.y)).collect::<Vec<_>>();
+    cells.sort();
+    cells
+}
+
+fn sorted_map<T: Clone>(cells: &HashMap<GridPosition, T>) -> Vec<((i32, i32), T)> {
+    let mut cells = cells
+        .iter()
+        .map(|(pos, x)| ((pos.x, pos.y), x.clone()))
+        .collect::<Vec<_>>();
+    cells.sort_by_key(|x| x.0);
+    cells
+}
+
+impl From<&BattleMap> for MapFile {
+    fn from(map: &BattleMap) -> Self {
+        Self {
+            version: MAP_VERSION,
+            name: map.name.clone(),
+            width: map.width,
+            height: map.height,
+            background: map.background.clone(),
+            walls: sorted(&map.walls),
+            doors: sorted_map(&map.doors),
+            difficult: sorted(&map.difficult),
+            hazards: sorted(&map.hazards),
+            light: map.light,
+            lit: sorted_map(&map.lit),
+            lights: sorted_map(&map.lights),
+        }
+    }
+}
+
+impl From<MapFile> for BattleMap {
+    fn from(file: MapFile) -> Self {
+        let cells = |cells: Vec<(i32, i32)>| {
+            cells
+                .into_iter()
+                .map(|(x, y)| GridPosition::new(x, y))
+                .collect()
+        };
+        fn cell_map<T>(cells: Vec<((i32, i32), T)>) -> HashMap<GridPosition, T> {
+            cells
+                .into_iter()
+                .map(|((x, y), value)| (GridPosition::new(x, y), value))
+                .collect()
+        }
+        Self {
+            name: file.name,
+            width: file.width,
+            height: file.height,
+            background: file.background,
+            walls: cells(file.walls),
+            doors: cell_map(file.doors),
+            difficult: cells(file.difficult),
+            hazards: cells(file.hazards),
+            light: file.light,
+            lit: cell_map(file.lit),
+            lights: cell_map(file.lights),
+        }
+    }
+}
+
+pub fn map_path(dir: &Path, slot: &str) -> PathBuf {
+    dir.join(format!("{slot}.map.ron"))
+}
+
+/// Writes the map under a slot named after it, which is returned.
+pub fn save_map(dir: &Path, map: &BattleMap) -> Result<String, SaveError> {
+    let text = ron::ser::to_string_pretty(&MapFile::from(map), ron::ser::PrettyConfig::default())
+        .map_err(|e| SaveError::Serialize(e.to_string()))?;
+    let slot = slot_name(&map.name);
+    ensure_dir(dir)?;
+    write_atomic(&map_path(dir, &slot), &text)?;
+    Ok(slot)
+}
+
+pub fn load_map(dir: &Path, slot: &str) -> Result<BattleMap, SaveError> {
+    let path = map_path(dir, slot);
+    let text = io(&path, fs::read_to_string(&path))?;
+    let file: MapFile = ron::from_str(&text).map_err(|e| SaveError::Parse(path, e.to_string()))?;
+    if file.version > MAP_VERSION {
+        return Err(SaveError::Version(MigrationError::TooNew(file.version)));
+    }
+    Ok(file.into())
+}
+
+/// The slots of every saved map, sorted by name.
+pub fn list_maps(dir: &Path) -> Result<Vec<String>, SaveError> {
+    slot_names(dir, ".map.ron")
+}
+
+/// Copies a PNG or JPG into the asset folder under `assets`, so the map keeps
+/// working wherever the original goes, and returns its asset path.
+pub fn import_image(assets: &Path, image: &Path) -> Result<String, SaveError> {
+    let is_image = image
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| ["png", "jpg", "jpeg"].contains(&x.to_lowercase().as_str()));
+    let Some(file_name) = image
+        .file_name()
+        .and_then(|x| x.to_str())
+        .filter(|_| is_image)
+    else {
+        return Err(SaveError::Parse(
+            image.to_path_buf(),
+            "not a PNG or JPG".to_string(),
+        ));
+    };
+    let dir = assets.join(MAP_IMAGE_DIR);
+    ensure_dir(&dir)?;
+    let to = dir.join(file_name);
+    if to != image {
+        io(image, fs::copy(image, &to))?;
+    }
+    Ok(format!("{MAP_IMAGE_DIR}/{file_name}"))
+}
+
+type Unplaced = (With<Unit>, Without<GridPosition>);
+
+/// Puts units that aren't on the map yet on the first free square, players
+/// from the left edge and enemies from the right.
+fn place_units(
+    mut commands: Commands,
+    map: Res<BattleMap>,
+    new_units: Query<(Entity, Has<Player>), Unplaced>,
+    placed: Query<&GridPosition>,
+) {
+    if new_units.is_empty() {
+        return;
+    }
+    let mut occupied = placed.iter().copied().collect::<HashSet<GridPosition>>();
+    for (unit, player) in &new_units {
+        let columns = (0..map.width).map(|x| match player {
+            true => x,
+            false => map.width - 1 - x,
+        });
+        let free = columns
+            .flat_map(|x| (0..map.height).map(move |y| GridPosition::new(x, y)))
+            .find(|x| map.is_open(*x) && !occupied.contains(x));
+        let Some(pos) = free else {
+            warn!("No room on the map for {unit:?}");
+            continue;
+        };
+        occupied.insert(pos);
+        commands.entity(unit).insert(pos);
+    }
+}
+
+/// Whether a point, in squares, is inside a wall. Points on the edge
+/// between two wall squares are; points on the outside edge of a wall
+/// aren't.
+fn solid(map: &BattleMap, point: Vec2) -> bool {
+    const EDGE: f32 = 1e-4;
+    let cells = |v: f32| {
+        let cell = v.round();
+        match (v - cell).abs() < EDGE {
+            true => cell as i32 - 1..=cell as i32,
+            false => v.floor() as i32..=v.floor() as i32,
+        }
+    };
+    let ys = cells(point.y);
+    cells(point.x).all(|x| ys.clone().all(|y| map.blocks(GridPosition::new(x, y))))
+}
+
+/// Whether the straight line between two points, in squares, passes through
+/// a wall. Grazing a wall's corner or running along its outside doesn't
+/// count.
+pub fn blocked(map: &BattleMap, from: Vec2, to: Vec2) -> bool {
+    let samples = ((to - from).length() * 32.).ceil().max(1.) as i32;
+    (1..samples).any(|i| solid(map, from.lerp(to, i as f32 / samples as f32)))
+}
+
+/// The middle of a square, in squares.
+pub fn center(pos: GridPosition) -> Vec2 {
+    Vec2::new(pos.x as f32 + 0.5, pos.y as f32 + 0.5)
+}
+
+fn corners(pos: GridPosition) -> [Vec2; 4] {
+    let (x, y) = (pos.x as f32, pos.y as f32);
+    [
+        Vec2::new(x, y),
+        Vec2::new(x + 1., y),
+        Vec2::new(x, y + 1.),
+        Vec2::new(x + 1., y + 1.),
+    ]
+}
+
+/// Whether a unit on `from` can see `to`, going by walls alone.
+pub fn line_of_sight(map: &BattleMap, from: GridPosition, to: GridPosition) -> bool {
+    !blocked(map, center(from), center(to))
+}
+
+/// The cover walls give a target against an attacker, using the DMG's grid
+/// rule: the attacker picks a corner of its square and draws lines to every
+/// corner of the target's. One or two blocked lines is half cover, three is
+/// three-quarters and four is total.
+pub fn cover(map: &BattleMap, attacker: GridPosition, target: GridPosition) -> Option<Cover> {
+    let blocked_lines = corners(attacker)
+        .iter()
+        .map(|from| {
+            corners(target)
+                .iter()
+                .filter(|to| blocked(map, *from, **to))
+                .count()
+        })
+        .min()
+        .unwrap_or(0);
+    match blocked_lines {
+        0 => None,
+        1 | 2 => Some(Cover::Half),
+        3 => Some(Cover::ThreeQuarters),
+        _ => Some(Cover::Total),
+    }
+}
+
+type Placed = (&'static GridPosition, Has<Player>, Has<Downed>);
+
+/// The map and who's standing where, for rules that need to know.
+#[derive(SystemParam)]
+pub struct Battlefield<'w, 's> {
+    pub map: Res<'w, BattleMap>,
+    pub rule: Res<'w, DiagonalRule>,
+    pub units: Query<'w, 's, Placed, With<Unit>>,
+    pub vision: Vision<'w, 's>,
+}
+
+impl Battlefield<'_, '_> {
+    pub fn position(&self, unit: Entity) -> Option<GridPosition> {
+        self.units.get(unit).ok().map(|x| *x.0)
+    }
+
+    /// Distance in feet between two units, if both are on the map.
+    pub fn distance(&self, from: Entity, to: Entity) -> Option<f64> {
+        Some(
+            self.position(from)?
+                .distance(self.position(to)?, *self.rule),
+        )
+    }
+
+    pub fn cover(&self, from: Entity, to: Entity) -> Option<Cover> {
+        cover(&self.map, self.position(from)?, self.position(to)?)
+    }
+
+    /// Whether a standing opponent of `unit` is in the squares around it.
+    pub fn hostile_adjacent(&self, unit: Entity) -> bool {
+        let Ok((pos, player, _)) = self.units.get(unit) else {
+            return false;
+        };
+        self.units.iter().any(|(other, other_player, downed)| {
+            other_player != player && !downed && pos.cells_to(*other) == 1
+        })
+    }
+}
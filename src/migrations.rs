@@ -0,0 +1,319 @@
+use crate::components::*;
+use bevy::reflect::{GetPath, TypePath, TypeRegistry};
+use bevy::scene::serde::SceneDeserializer;
+use bevy::{prelude::*, scene::DynamicEntity};
+use serde::de::DeserializeSeed;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The layout of units in saves written by this build. Bump it, and add a
+/// `Migration` from the old version, whenever a saved component is renamed
+/// or its fields change.
+pub const SAVE_VERSION: u32 = 2;
+
+/// Saves from before the version was stored.
+const UNVERSIONED: u32 = 1;
+
+/// Stored as a resource in every saved scene.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq)]
+#[reflect(Resource)]
+pub struct SaveVersion(pub u32);
+
+/// Upgrades a save from version `from` to `from + 1`.
+///
+/// `renames` are old and new component type paths, swapped in the raw scene
+/// so it deserializes against the current registry. `apply` then rewrites
+/// the deserialized components through reflection.
+pub struct Migration {
+    pub from: u32,
+    pub renames: &'static [(&'static str, &'static str)],
+    pub apply: fn(&mut DynamicScene, &TypeRegistry),
+}
+
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    renames: &[],
+    apply: v1_player_stats,
+}];
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationError {
+    /// The save was written by a newer build of the game.
+    TooNew(u32),
+    /// There's no migration from this version.
+    Missing(u32),
+    Parse(String),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::TooNew(version) => write!(
+                f,
+                "save version {version} is newer than this game supports ({SAVE_VERSION}), \
+                update the game to load it"
+            ),
+            MigrationError::Missing(version) => {
+                write!(f, "no migration from save version {version}")
+            }
+            MigrationError::Parse(e) => write!(f, "could not read save: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Reads a saved scene, upgrading it to `SAVE_VERSION` if it's older.
+pub fn load_scene(text: &str, registry: &TypeRegistry) -> Result<DynamicScene, MigrationError> {
+    migrate(text, registry, MIGRATIONS)
+}
+
+fn migrate(
+    text: &str,
+    registry: &TypeRegistry,
+    migrations: &[Migration],
+) -> Result<DynamicScene, MigrationError> {
+    let version = save_version(text)?;
+    if version > SAVE_VERSION {
+        return Err(MigrationError::TooNew(version));
+    }
+    let steps = (version..SAVE_VERSION)
+        .map(|v| {
+            migrations
+                .iter()
+                .find(|x| x.from == v)
+                .ok_or(MigrationError::Missing(v))
+        })
+        .collect::<Result<Vec<&Migration>, MigrationError>>()?;
+
+    let mut text = text.to_string();
+    for (old, new) in steps.iter().flat_map(|x| x.renames) {
+        text = text.replace(&format!("\"{old}\""), &format!("\"{new}\""));
+    }
+    let mut deserializer =
+        ron::de::Deserializer::from_str(&text).map_err(|e| MigrationError::Parse(e.to_string()))?;
+    let mut scene = SceneDeserializer {
+        type_registry: registry,
+    }
+    .deserialize(&mut deserializer)
+    .map_err(|e| MigrationError::Parse(e.to_string()))?;
+    for step in steps {
+        (step.apply)(&mut scene, registry);
+    }
+
+    scene.resources.retain(|x| !is::<SaveVersion>(x.as_ref()));
+    scene.resources.push(Box::new(SaveVersion(SAVE_VERSION)));
+    Ok(scene)
+}
+
+/// The version of a saved scene, read without deserializing its components
+/// so that it works for saves whose components no longer exist.
+pub fn save_version(text: &str) -> Result<u32, MigrationError> {
+    #[derive(Deserialize)]
+    struct Header {
+        #[serde(default)]
+        resources: HashMap<String, ron::Value>,
+    }
+    let header: Header = ron::from_str(text).map_err(|e| MigrationError::Parse(e.to_string()))?;
+    let Some(value) = header.resources.get(SaveVersion::type_path()) else {
+        return Ok(UNVERSIONED);
+    };
+    let number = match value {
+        ron::Value::Seq(x) => x.first(),
+        x => Some(x),
+    };
+    match number {
+        Some(ron::Value::Number(x)) => Ok(x.as_i64().unwrap_or(0) as u32),
+        _ => Err(MigrationError::Parse(format!(
+            "unreadable save version {value:?}"
+        ))),
+    }
+}
+
+fn is<T: TypePath>(value: &dyn Reflect) -> bool {
+    value
+        .get_represented_type_info()
+        .is_some_and(|x| x.type_path() == T::type_path())
+}
+
+fn component_mut<'a, T: TypePath>(entity: &'a mut DynamicEntity) -> Option<&'a mut dyn Reflect> {
+    entity
+        .components
+        .iter_mut()
+        .find(|x| is::<T>(x.as_ref()))
+        .map(|x| x.as_mut())
+}
+
+/// Version 1 players were spawned with skill totals that left out the
+/// ability modifier and abilities that didn't list their skills as
+/// dependencies, so racial increases never reached the skills. They also
+/// predate `PlayTime`.
+fn v1_player_stats(scene: &mut DynamicScene, _registry: &TypeRegistry) {
+    for entity in scene
+        .entities
+        .iter_mut()
+        .filter(|x| x.components.iter().any(|x| is::<Player>(x.as_ref())))
+    {
+        for ability in StatEnum::ABILITIES {
+            let skills = StatEnum::SKILLS
+                .into_iter()
+                .filter(|x| x.ability() == Some(ability.clone()))
+                .collect::<Vec<StatEnum>>();
+            let Some(component) = ability_mut(entity, &ability) else {
+                continue;
+            };
+            let modifier = component
+                .path::<f64>("0.stat.total")
+                .map(|x| ((x - 10.) / 2.).floor())
+                .unwrap_or(0.);
+            if let Ok(deps) = component.reflect_path_mut("0.stat.deps") {
+                deps.apply(&skills);
+            }
+            for skill in skills {
+                let Some(component) = skill_mut(entity, &skill) else {
+                    continue;
+                };
+                let base = component.path::<f64>("0.stat.base").copied().unwrap_or(0.);
+                if let Ok(total) = component.path_mut::<f64>("0.stat.total") {
+                    *total = base + modifier;
+                }
+            }
+        }
+        if component_mut::<PlayTime>(entity).is_none() {
+            entity.components.push(Box::new(PlayTime::default()));
+        }
+    }
+}
+
+fn ability_mut<'a>(
+    entity: &'a mut DynamicEntity,
+    ability: &StatEnum,
+) -> Option<&'a mut dyn Reflect> {
+    match ability {
+        StatEnum::Strength => component_mut::<Strength>(entity),
+        StatEnum::Constitution => component_mut::<Constitution>(entity),
+        StatEnum::Dexterity => component_mut::<Dexterity>(entity),
+        StatEnum::Intelligence => component_mut::<Intelligence>(entity),
+        StatEnum::Wisdom => component_mut::<Wisdom>(entity),
+        StatEnum::Charisma => component_mut::<Charisma>(entity),
+        _ => None,
+    }
+}
+
+fn skill_mut<'a>(entity: &'a mut DynamicEntity, skill: &StatEnum) -> Option<&'a mut dyn Reflect> {
+    match skill {
+        StatEnum::Athletics => component_mut::<Athletics>(entity),
+        StatEnum::Acrobatics => component_mut::<Acrobatics>(entity),
+        StatEnum::SleightOfHand => component_mut::<SleightOfHand>(entity),
+        StatEnum::Stealth => component_mut::<Stealth>(entity),
+        StatEnum::Arcana => component_mut::<Arcana>(entity),
+        StatEnum::History => component_mut::<History>(entity),
+        StatEnum::Investigation => component_mut::<Investigation>(entity),
+        StatEnum::Nature => component_mut::<Nature>(entity),
+        StatEnum::Religion => component_mut::<Religion>(entity),
+        StatEnum::AnimalHandling => component_mut::<AnimalHandling>(entity),
+        StatEnum::Insight => component_mut::<Insight>(entity),
+        StatEnum::Medicine => component_mut::<Medicine>(entity),
+        StatEnum::Perception => component_mut::<Perception>(entity),
+        StatEnum::Survival => component_mut::<Survival>(entity),
+        StatEnum::Deception => component_mut::<Deception>(entity),
+        StatEnum::Intimidation => component_mut::<Intimidation>(entity),
+        StatEnum::Performance => component_mut::<Performance>(entity),
+        StatEnum::Persuasion => component_mut::<Persuasion>(entity),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::entity::EntityHashMap;
+
+    const V1: &str = include_str!("../tests/fixtures/saves/v1.scn.ron");
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.register_type::<ComponentRegistry>();
+        app.register_type::<SaveVersion>();
+        app
+    }
+
+    fn load(app: &mut App, text: &str, migrations: &[Migration]) -> Result<(), MigrationError> {
+        let scene = {
+            let registry = app.world().resource::<AppTypeRegistry>().read();
+            migrate(text, &registry, migrations)?
+        };
+        scene
+            .write_to_world(app.world_mut(), &mut EntityHashMap::default())
+            .unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn unversioned_saves_are_version_1() {
+        assert_eq!(save_version(V1), Ok(1));
+        let current = V1.replace(
+            "resources: {}",
+            "resources: {\"newtable::migrations::SaveVersion\": (2)}",
+        );
+        assert_eq!(save_version(&current), Ok(2));
+    }
+
+    #[test]
+    fn v1_saves_are_migrated() {
+        let mut app = app();
+        load(&mut app, V1, MIGRATIONS).unwrap();
+        let world = app.world_mut();
+        assert_eq!(world.resource::<SaveVersion>(), &SaveVersion(SAVE_VERSION));
+
+        let (strength, athletics, perception, arcana, play_time) = world
+            .query_filtered::<(&Strength, &Athletics, &Perception, &Arcana, &PlayTime), With<Player>>()
+            .single(world);
+        assert_eq!(strength.0.stat.deps, vec![StatEnum::Athletics]);
+        // STR 15, WIS 13 and INT 8 modifiers are now part of the totals.
+        assert_eq!(athletics.0.stat.total, 2.);
+        assert_eq!(athletics.0.proficiency, Proficiency::Proficient);
+        assert_eq!(perception.0.stat.total, 1.);
+        assert_eq!(arcana.0.stat.total, -1.);
+        assert_eq!(play_time, &PlayTime(0.));
+    }
+
+    #[test]
+    fn renamed_components_are_migrated() {
+        let old = V1.replace(
+            "\"newtable::components::UnitName\"",
+            "\"newtable::components::CharacterName\"",
+        );
+        let mut app = app();
+        assert!(load(&mut app, &old, MIGRATIONS).is_err());
+
+        let migrations = [Migration {
+            from: 1,
+            renames: &[(
+                "newtable::components::CharacterName",
+                "newtable::components::UnitName",
+            )],
+            apply: |_, _| {},
+        }];
+        let mut app = self::app();
+        load(&mut app, &old, &migrations).unwrap();
+        let world = app.world_mut();
+        let name = world
+            .query_filtered::<&UnitName, With<Player>>()
+            .single(world);
+        assert_eq!(name.0, "Brom");
+    }
+
+    #[test]
+    fn newer_saves_are_rejected() {
+        let newer = V1.replace(
+            "resources: {}",
+            "resources: {\"newtable::migrations::SaveVersion\": (99)}",
+        );
+        assert_eq!(
+            load(&mut app(), &newer, MIGRATIONS),
+            Err(MigrationError::TooNew(99))
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::components::*;
+use crate::items::WeaponData;
+use crate::scenario::{Side, UnitSpec};
+use bevy::{ecs::world::Command, prelude::*};
+use serde::Deserialize;
+
+pub struct MonstersPlugin;
+
+impl Plugin for MonstersPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MonsterCatalog::from_ron(include_str!(
+            "../assets/data/monsters.ron"
+        )));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MonsterCatalog(pub Vec<MonsterData>);
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct MonsterData {
+    pub name: String,
+    pub challenge: f64,
+    pub xp: u32,
+    pub armor_class: f64,
+    pub hit_points: f64,
+    pub speed: f64,
+    /// In feet, 0 for none.
+    pub darkvision: f64,
+    pub abilities: AbilityBlock,
+    pub attacks: Vec<WeaponData>,
+}
+
+impl Default for MonsterData {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            challenge: 0.,
+            xp: 0,
+            armor_class: 10.,
+            hit_points: 1.,
+            speed: 30.,
+            darkvision: 0.,
+            abilities: AbilityBlock::default(),
+            attacks: vec![],
+        }
+    }
+}
+
+/// Ability scores in stat block order.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct AbilityBlock {
+    pub str: f64,
+    pub dex: f64,
+    pub con: f64,
+    pub int: f64,
+    pub wis: f64,
+    pub cha: f64,
+}
+
+impl Default for AbilityBlock {
+    fn default() -> Self {
+        Self {
+            str: 10.,
+            dex: 10.,
+            con: 10.,
+            int: 10.,
+            wis: 10.,
+            cha: 10.,
+        }
+    }
+}
+
+impl MonsterCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("monsters.ron to be a valid monster catalog"))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MonsterData> {
+        let data = self.0.iter().find(|x| x.name == name);
+        if data.is_none() {
+            warn!("No monster called {name}");
+        }
+        data
+    }
+}
+
+impl MonsterData {
+    /// Monsters add their proficiency bonus to every attack. It grows with
+    /// challenge the way a character's grows with level.
+    pub fn proficiency_bonus(&self) -> ProficiencyBonus {
+        ProficiencyBonus::from_level(self.challenge.ceil().max(1.) as i64)
+    }
+
+    /// Challenge as written in a stat block: "1/4", "2".
+    pub fn challenge_label(&self) -> String {
+        match self.challenge {
+            x if x > 0. && x < 1. => format!("1/{}", (1. / x).round()),
+            x => x.to_string(),
+        }
+    }
+
+    /// Spawns the monster as an enemy carrying its attacks.
+    pub fn spawn(&self, world: &mut World) -> Entity {
+        let a = self.abilities;
+        let unit = UnitSpec {
+            name: self.name.clone(),
+            side: Side::Enemy,
+            abilities: [a.str, a.con, a.dex, a.int, a.wis, a.cha],
+            max_health: self.hit_points,
+            ac: self.armor_class,
+            speed: self.speed,
+            ..default()
+        }
+        .spawn(world);
+        let attacks = self
+            .attacks
+            .iter()
+            .map(|x| x.spawn(world))
+            .collect::<Vec<Entity>>();
+        world.entity_mut(unit).push_children(&attacks).insert((
+            self.proficiency_bonus(),
+            DarkVision(Stat::new(self.darkvision, vec![])),
+            SimpleWeaponProficiency(Proficiency::Proficient),
+            MartialWeaponProficiency(Proficiency::Proficient),
+        ));
+        unit
+    }
+}
+
+/// Spawns a monster from the catalog as an enemy.
+pub struct SpawnMonster(pub String);
+
+impl Command for SpawnMonster {
+    fn apply(self, world: &mut World) {
+        if let Some(data) = world.resource::<MonsterCatalog>().get(&self.0).cloned() {
+            data.spawn(world);
+        }
+    }
+}
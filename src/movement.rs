@@ -0,0 +1,288 @@
+//! Moving units around the battle map: shortest paths around walls and
+//! other units, the movement a unit has left on its turn, and the
+//! opportunity attacks it provokes on the way.
+
+use crate::combat::{Attack, Downed, TurnStarted};
+use crate::components::*;
+use crate::map::{BattleMap, DiagonalRule, GridPosition, CELL_FEET};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Movement>();
+        app.init_resource::<OpportunityAttacks>();
+        app.observe(reset_movement);
+        app.observe(move_unit);
+        app.add_systems(Update, free_movement_after_combat);
+    }
+}
+
+/// Feet of movement a unit has left this turn. Units without it, like
+/// everyone outside combat, move as far as they like.
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct Movement(pub f64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// The squares stepped on, ending with the destination.
+    pub cells: Vec<GridPosition>,
+    /// In feet.
+    pub cost: f64,
+}
+
+/// The cheapest way from `from` to `to` with A*, or None if there's no way
+/// through. Walls and `occupied` squares can't be entered and a diagonal
+/// step can't squeeze past the corner of a wall.
+pub fn find_path(
+    map: &BattleMap,
+    rule: DiagonalRule,
+    from: GridPosition,
+    to: GridPosition,
+    occupied: &HashSet<GridPosition>,
+) -> Option<Path> {
+    if !map.is_open(to) || occupied.contains(&to) {
+        return None;
+    }
+    // Under the 5/10/5 rule the cost of the next diagonal depends on how
+    // many came before, so that's part of the state searched.
+    type State = (GridPosition, bool);
+    let heuristic = |pos: GridPosition| pos.cells_to(to) as u32 * CELL_FEET as u32;
+    let mut open = BinaryHeap::new();
+    let mut best = HashMap::<State, u32>::default();
+    let mut came_from = HashMap::<State, State>::default();
+    let start = (from, false);
+    best.insert(start, 0);
+    open.push(Reverse((heuristic(from), 0, from.x, from.y, false)));
+
+    while let Some(Reverse((_, cost, x, y, odd))) = open.pop() {
+        let state = (GridPosition::new(x, y), odd);
+        if state.0 == to {
+            let mut cells = vec![state.0];
+            let mut current = state;
+            while let Some(previous) = came_from.get(&current) {
+                current = *previous;
+                cells.push(current.0);
+            }
+            cells.pop();
+            cells.reverse();
+            return Some(Path {
+                cells,
+                cost: cost as f64,
+            });
+        }
+        if best.get(&state).is_some_and(|x| *x < cost) {
+            continue;
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = GridPosition::new(x + dx, y + dy);
+                if (dx, dy) == (0, 0) || !map.is_open(next) || occupied.contains(&next) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dy != 0;
+                if diagonal
+                    && (map.blocks(GridPosition::new(x + dx, y))
+                        || map.blocks(GridPosition::new(x, y + dy)))
+                {
+                    continue;
+                }
+                let (mut step, next_odd) = match (diagonal, rule) {
+                    (false, _) => (5, odd),
+                    (true, DiagonalRule::Uniform) => (5, odd),
+                    (true, DiagonalRule::Alternating) => (if odd { 10 } else { 5 }, !odd),
+                };
+                if map.difficult.contains(&next) {
+                    step *= 2;
+                }
+                let next_state = (next, next_odd);
+                let next_cost = cost + step;
+                if best.get(&next_state).is_some_and(|x| *x <= next_cost) {
+                    continue;
+                }
+                best.insert(next_state, next_cost);
+                came_from.insert(next_state, state);
+                open.push(Reverse((
+                    next_cost + heuristic(next),
+                    next_cost,
+                    next.x,
+                    next.y,
+                    next_odd,
+                )));
+            }
+        }
+    }
+    None
+}
+
+/// How far a unit threatens: 5 ft, or 10 ft with a reach weapon.
+pub fn reach(children: Option<&Children>, reach_weapons: &Query<(), ReachWeapon>) -> f64 {
+    match children.is_some_and(|x| x.iter().any(|x| reach_weapons.contains(*x))) {
+        true => 2. * CELL_FEET,
+        false => CELL_FEET,
+    }
+}
+
+pub type ReachWeapon = (With<Weapon>, With<Reach>);
+
+/// The enemies, with their squares and reach, that a unit moving along
+/// `path` from `from` leaves the reach of, each with the last square in
+/// its reach.
+pub fn provoked_by(
+    from: GridPosition,
+    path: &[GridPosition],
+    enemies: &[(Entity, GridPosition, f64)],
+    rule: DiagonalRule,
+) -> Vec<(Entity, GridPosition)> {
+    let steps = std::iter::once(&from).chain(path).collect::<Vec<_>>();
+    enemies
+        .iter()
+        .filter_map(|(enemy, at, reach)| {
+            steps
+                .windows(2)
+                .find(|x| x[0].distance(*at, rule) <= *reach && x[1].distance(*at, rule) > *reach)
+                .map(|x| (*enemy, *x[0]))
+        })
+        .collect()
+}
+
+/// What a unit makes opportunity attacks with: its reach weapon if it has
+/// one, since that's the reach being left, or else its first melee weapon.
+pub fn opportunity_weapon(
+    children: Option<&Children>,
+    weapons: &Query<(&WeaponType, Has<Reach>), With<Weapon>>,
+) -> Option<Entity> {
+    let melee = children?
+        .iter()
+        .copied()
+        .filter_map(|x| match weapons.get(x) {
+            Ok((WeaponType::SimpleMelee | WeaponType::MartialMelee, reach)) => Some((x, reach)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let reach = melee.iter().find(|x| x.1).or(melee.first());
+    reach.map(|x| x.0)
+}
+
+/// An enemy that may take a swing at a unit leaving its reach.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpportunityAttack {
+    pub attacker: Entity,
+    pub target: Entity,
+    /// The weapon whose reach the target left, if the attacker has one.
+    pub with: Option<Entity>,
+    /// The target's last square in reach, where the attack lands.
+    pub at: GridPosition,
+}
+
+impl OpportunityAttack {
+    /// The attack, if the attacker has a weapon to make it with.
+    pub fn attack(&self) -> Option<Attack> {
+        Some(Attack {
+            at: Some(self.at),
+            ..Attack::new(self.attacker, self.with?, self.target)
+        })
+    }
+}
+
+/// Opportunity attacks waiting on a decision.
+#[derive(Resource, Default)]
+pub struct OpportunityAttacks(pub Vec<OpportunityAttack>);
+
+/// Moves a unit along the cheapest path to a square, if it has the
+/// movement left for it.
+#[derive(Event)]
+pub struct MoveUnit {
+    pub unit: Entity,
+    pub to: GridPosition,
+}
+
+fn reset_movement(trigger: Trigger<TurnStarted>, mut commands: Commands, speeds: Query<&Speed>) {
+    let unit = trigger.event().0;
+    if let Ok(speed) = speeds.get(unit) {
+        commands.entity(unit).insert(Movement(speed.0.total));
+    }
+}
+
+type Mover = (
+    Entity,
+    &'static mut GridPosition,
+    Option<&'static mut Movement>,
+    Has<Player>,
+    Has<Downed>,
+    Option<&'static Children>,
+);
+
+fn move_unit(
+    trigger: Trigger<MoveUnit>,
+    map: Res<BattleMap>,
+    rule: Res<DiagonalRule>,
+    mut opportunity_attacks: ResMut<OpportunityAttacks>,
+    mut units: Query<Mover, With<Unit>>,
+    reach_weapons: Query<(), ReachWeapon>,
+    weapons: Query<(&WeaponType, Has<Reach>), With<Weapon>>,
+) {
+    let event = trigger.event();
+    let occupied = units
+        .iter()
+        .filter(|x| x.0 != event.unit)
+        .map(|x| *x.1)
+        .collect::<HashSet<GridPosition>>();
+    let enemies_of = |player: bool| {
+        units
+            .iter()
+            .filter(|x| x.0 != event.unit && x.3 != player && !x.4)
+            .map(|x| (x.0, *x.1, reach(x.5, &reach_weapons)))
+            .collect::<Vec<_>>()
+    };
+    let Ok((_, from, movement, player, downed, _)) = units.get(event.unit) else {
+        return;
+    };
+    if downed {
+        return;
+    }
+    let Some(path) = find_path(&map, *rule, *from, event.to, &occupied) else {
+        warn!("No way to {:?}", event.to);
+        return;
+    };
+    if movement.is_some_and(|x| path.cost > x.0) {
+        warn!("{} ft is too far", path.cost);
+        return;
+    }
+    let from = *from;
+    let enemies = enemies_of(player);
+    for (attacker, at) in provoked_by(from, &path.cells, &enemies, *rule) {
+        let children = units.get(attacker).ok().and_then(|x| x.5);
+        opportunity_attacks.0.push(OpportunityAttack {
+            attacker,
+            target: event.unit,
+            with: opportunity_weapon(children, &weapons),
+            at,
+        });
+    }
+    let Ok((_, mut pos, movement, ..)) = units.get_mut(event.unit) else {
+        return;
+    };
+    *pos = event.to;
+    if let Some(mut movement) = movement {
+        movement.0 -= path.cost;
+    }
+}
+
+fn free_movement_after_combat(
+    mut commands: Commands,
+    enemies: Query<(), With<Enemy>>,
+    limited: Query<Entity, With<Movement>>,
+) {
+    if !enemies.is_empty() {
+        return;
+    }
+    for unit in &limited {
+        commands.entity(unit).remove::<Movement>();
+    }
+}
@@ -0,0 +1,276 @@
+//! The party: every player character in the session. One of them is the
+//! active character, who items and rolls go to. A party is saved as a
+//! `<slot>.party.ron` file listing the slots its members are saved in.
+
+use crate::components::*;
+use crate::migrations::MigrationError;
+use crate::saves::{
+    copy_slots, ensure_dir, io, serialize_character, slot_name, slot_names, spawn_slot,
+    write_atomic, write_slot, SaveError, SaveMetadata,
+};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The version of `PartyFile` this build writes.
+pub const PARTY_VERSION: u32 = 1;
+
+pub struct PartyPlugin;
+
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActiveCharacter>();
+        app.add_systems(Update, keep_active_character);
+    }
+}
+
+/// The player character that items and rolls go to.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct ActiveCharacter(pub Option<Entity>);
+
+/// The slot a party member is saved to. It isn't saved itself, so renaming
+/// a slot can't leave it pointing at the old name.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct CharacterSlot(pub String);
+
+/// The party's members and which of them is active.
+#[derive(SystemParam)]
+pub struct Party<'w, 's> {
+    pub active: ResMut<'w, ActiveCharacter>,
+    pub members: Query<'w, 's, (Entity, &'static UnitName), With<Player>>,
+}
+
+impl Party<'_, '_> {
+    /// Every member, sorted by name.
+    pub fn members(&self) -> Vec<Entity> {
+        let mut members = self.members.iter().collect::<Vec<_>>();
+        members.sort_by(|a, b| a.1 .0.cmp(&b.1 .0));
+        members.into_iter().map(|x| x.0).collect()
+    }
+
+    /// The active character, or the first member if nobody is.
+    pub fn active(&self) -> Option<Entity> {
+        self.active
+            .0
+            .filter(|x| self.members.contains(*x))
+            .or_else(|| self.members().first().copied())
+    }
+}
+
+/// Makes the first member active whenever nobody is, e.g. when the active
+/// character leaves the party.
+fn keep_active_character(mut party: Party) {
+    let member = party.active();
+    party.active.set_if_neq(ActiveCharacter(member));
+}
+
+/// A party as it's saved next to the character saves:
+///
+/// ```ron
+/// (
+///     version: 1,
+///     name: "The Iron Company",
+///     members: ["Brom", "Ilsa"],
+///     active: Some("Ilsa"),
+/// )
+/// ```
+///
+/// `members` and `active` are the members' slots.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PartyFile {
+    pub version: u32,
+    pub name: String,
+    pub members: Vec<String>,
+    pub active: Option<String>,
+}
+
+impl Default for PartyFile {
+    fn default() -> Self {
+        Self {
+            version: PARTY_VERSION,
+            name: String::new(),
+            members: Vec::new(),
+            active: None,
+        }
+    }
+}
+
+pub fn party_path(dir: &Path, slot: &str) -> PathBuf {
+    dir.join(format!("{slot}.party.ron"))
+}
+
+/// The slot of each of `members`, named after them if they weren't loaded
+/// from one. Members who share a name get `<slot>-copy`, `<slot>-copy-2`,
+/// ... so they don't overwrite each other.
+fn member_slots(world: &World, members: &[Entity]) -> Vec<String> {
+    let mut taken = members
+        .iter()
+        .filter_map(|x| world.get::<CharacterSlot>(*x))
+        .map(|x| x.0.clone())
+        .collect::<Vec<_>>();
+    members
+        .iter()
+        .map(|member| {
+            if let Some(slot) = world.get::<CharacterSlot>(*member) {
+                return slot.0.clone();
+            }
+            let name = world.get::<UnitName>(*member).map(|x| x.0.as_str());
+            let slot = slot_name(name.unwrap_or_default());
+            let slot = std::iter::once(slot.clone())
+                .chain(copy_slots(&slot))
+                .find(|x| !taken.contains(x))
+                .unwrap();
+            taken.push(slot.clone());
+            slot
+        })
+        .collect()
+}
+
+/// Everything needed to save the party in a world, gathered up so it can be
+/// written off the main thread.
+pub struct PartySave {
+    pub slot: String,
+    pub file: PartyFile,
+    /// The slot, scene and metadata of each member.
+    pub members: Vec<(String, String, SaveMetadata)>,
+}
+
+impl PartySave {
+    /// Saves every player character in `world` to their own slot, and the
+    /// party to `slot`, or a slot named after its members. Members keep the
+    /// slot they're given, so the next save goes to the same one.
+    pub fn from_world(world: &mut World, slot: Option<&str>) -> Self {
+        let mut players = world.query_filtered::<(Entity, &UnitName), With<Player>>();
+        let mut players = players
+            .iter(world)
+            .map(|(entity, name)| (entity, name.0.clone()))
+            .collect::<Vec<_>>();
+        players.sort_by(|a, b| a.1.cmp(&b.1));
+        let names = players.iter().map(|x| x.1.as_str()).collect::<Vec<_>>();
+        let slot = slot_name(slot.unwrap_or(&names.join(" ")));
+        let name = names.join(", ");
+        let active = world.resource::<ActiveCharacter>().0;
+        let mut file = PartyFile { name, ..default() };
+        let mut members = Vec::new();
+        let entities = players.iter().map(|x| x.0).collect::<Vec<_>>();
+        let slots = member_slots(world, &entities);
+        for (entity, member) in entities.into_iter().zip(slots) {
+            world
+                .entity_mut(entity)
+                .insert(CharacterSlot(member.clone()));
+            if Some(entity) == active {
+                file.active = Some(member.clone());
+            }
+            file.members.push(member.clone());
+            let scene = serialize_character(world, entity);
+            let metadata = SaveMetadata::for_unit(world, entity);
+            members.push((member, scene, metadata));
+        }
+        Self {
+            slot,
+            file,
+            members,
+        }
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<(), SaveError> {
+        for (slot, scene, metadata) in &self.members {
+            write_slot(dir, slot, scene, metadata)?;
+        }
+        write_party(dir, &self.slot, &self.file)
+    }
+}
+
+fn write_party(dir: &Path, slot: &str, file: &PartyFile) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(file, ron::ser::PrettyConfig::default())
+        .map_err(|e| SaveError::Serialize(e.to_string()))?;
+    ensure_dir(dir)?;
+    write_atomic(&party_path(dir, slot), &text)
+}
+
+pub fn read_party(dir: &Path, slot: &str) -> Result<PartyFile, SaveError> {
+    let path = party_path(dir, slot);
+    let text = io(&path, fs::read_to_string(&path))?;
+    let file: PartyFile =
+        ron::from_str(&text).map_err(|e| SaveError::Parse(path, e.to_string()))?;
+    if file.version > PARTY_VERSION {
+        return Err(SaveError::Version(MigrationError::TooNew(file.version)));
+    }
+    Ok(file)
+}
+
+/// The slots of every saved party, sorted by name.
+pub fn list_parties(dir: &Path) -> Result<Vec<String>, SaveError> {
+    slot_names(dir, ".party.ron")
+}
+
+/// The saved parties with a member saved in `slot`.
+pub fn parties_with(dir: &Path, slot: &str) -> Result<Vec<String>, SaveError> {
+    let mut parties = Vec::new();
+    for party in list_parties(dir)? {
+        if read_party(dir, &party)?.members.iter().any(|x| x == slot) {
+            parties.push(party);
+        }
+    }
+    Ok(parties)
+}
+
+/// Points the saved parties with a member in `from` at `to`, once the
+/// member's slot has been renamed.
+pub fn rename_member(dir: &Path, from: &str, to: &str) -> Result<(), SaveError> {
+    for party in parties_with(dir, from)? {
+        let mut file = read_party(dir, &party)?;
+        for member in file.members.iter_mut().chain(file.active.as_mut()) {
+            if member == from {
+                *member = to.to_string();
+            }
+        }
+        write_party(dir, &party, &file)?;
+    }
+    Ok(())
+}
+
+/// Spawns a saved character into the party, remembering its slot. A
+/// character already in the party isn't spawned twice.
+pub fn add_member(world: &mut World, dir: &Path, slot: &str) -> Result<Entity, SaveError> {
+    let mut slots = world.query::<(Entity, &CharacterSlot)>();
+    if let Some((member, _)) = slots.iter(world).find(|x| x.1 .0 == slot) {
+        return Ok(member);
+    }
+    let member = spawn_slot(world, dir, slot)?;
+    world
+        .entity_mut(member)
+        .insert(CharacterSlot(slot.to_string()));
+    Ok(member)
+}
+
+/// Spawns every member of a saved party and makes the one it was saved
+/// with active. If any member can't be loaded, none of the ones it spawned
+/// are kept; members already in the party stay.
+pub fn spawn_party(world: &mut World, dir: &Path, slot: &str) -> Result<Vec<Entity>, SaveError> {
+    let file = read_party(dir, slot)?;
+    let mut already = world.query_filtered::<Entity, With<CharacterSlot>>();
+    let already = already.iter(world).collect::<Vec<_>>();
+    let mut members = Vec::new();
+    for member in &file.members {
+        match add_member(world, dir, member) {
+            Ok(entity) if members.contains(&entity) => {}
+            Ok(entity) => members.push(entity),
+            Err(e) => {
+                for entity in members.into_iter().filter(|x| !already.contains(x)) {
+                    world.entity_mut(entity).despawn_recursive();
+                }
+                return Err(e);
+            }
+        }
+    }
+    let active = members.iter().copied().find(|x| {
+        let slot = world.get::<CharacterSlot>(*x).map(|x| &x.0);
+        slot.is_some() && slot == file.active.as_ref()
+    });
+    world.resource_mut::<ActiveCharacter>().0 = active;
+    Ok(members)
+}
@@ -0,0 +1,621 @@
+//! Character sheets printed to PDF, laid out like the standard 5e sheet:
+//! the header, abilities, saves, skills, combat numbers, attacks and
+//! equipment on the first page, and features and spells from the second
+//! page on. Everything is read from the character's components and the
+//! items under it at the moment the sheet is exported.
+
+use crate::areas::{save_modifier, Saver};
+use crate::checks::{passive_score, skill_modifier, Belonging, Belongings, Skills};
+use crate::combat::{weapon_bonuses, AttackerQuery};
+use crate::components::*;
+use crate::races::RaceCatalog;
+use crate::saves::{ensure_dir, io, slot_name, SaveError, SaveErrors};
+use bevy::ecs::world::Command;
+use bevy::prelude::*;
+use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str, TextStr};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where exported sheets are written.
+pub const SHEET_DIR: &str = "sheets";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbilityLine {
+    pub stat: StatEnum,
+    pub score: f64,
+    pub modifier: i64,
+    pub save: i64,
+    pub proficiency: Proficiency,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillLine {
+    pub stat: StatEnum,
+    pub modifier: i64,
+    pub proficiency: Proficiency,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttackLine {
+    pub name: String,
+    pub bonus: i64,
+    /// Like "1d6+2 Bludgeoning".
+    pub damage: String,
+}
+
+/// A character's sheet as it's printed, with every total worked out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintedSheet {
+    pub name: String,
+    pub player: String,
+    pub race: String,
+    pub class: String,
+    pub level: i64,
+    pub background: String,
+    pub alignment: String,
+    pub xp: f64,
+    pub armor_class: f64,
+    pub initiative: i64,
+    pub speed: f64,
+    pub health: f64,
+    pub max_health: f64,
+    pub temp_health: f64,
+    pub proficiency_bonus: i64,
+    pub abilities: Vec<AbilityLine>,
+    pub skills: Vec<SkillLine>,
+    /// Passive Perception, Investigation and Insight.
+    pub passives: Vec<(String, i64)>,
+    pub attacks: Vec<AttackLine>,
+    pub equipment: Vec<String>,
+    pub features: Vec<Feature>,
+    pub spells: Vec<String>,
+}
+
+type WeaponLine = (
+    &'static ItemName,
+    &'static WeaponType,
+    &'static Dice,
+    &'static BaseDamage,
+    Option<&'static DamageType>,
+    Has<Finesse>,
+    Option<&'static AttackModifier>,
+    Option<&'static DamageModifier>,
+);
+
+impl PrintedSheet {
+    /// The sheet of `unit`, or None if it isn't a character with abilities
+    /// and skills.
+    pub fn from_world(world: &mut World, unit: Entity) -> Option<Self> {
+        let mut savers = world.query::<Saver>();
+        let mut skills = world.query::<Skills>();
+        let mut attackers = world.query::<AttackerQuery>();
+        let mut weapons = world.query::<WeaponLine>();
+        let mut belongings = world.query::<Belonging>();
+        let world = &*world;
+        let entity = world.get_entity(unit)?;
+        let saver = savers.get(world, unit).ok()?;
+        let skills = skills.get(world, unit).ok()?;
+        let bonus = saver.6.map_or(0, |x| x.0);
+
+        let mut sheet = PrintedSheet {
+            name: entity.get::<UnitName>()?.0.clone(),
+            player: entity
+                .get::<PlayerName>()
+                .map(|x| x.0.clone())
+                .unwrap_or_default(),
+            race: match (entity.get::<Race>(), world.get_resource::<RaceCatalog>()) {
+                (Some(race), Some(races)) => races.name(race),
+                _ => String::new(),
+            },
+            class: entity
+                .get::<Class>()
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            level: entity.get::<Level>().map_or(1, |x| x.0),
+            background: entity
+                .get::<Background>()
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            alignment: entity
+                .get::<Alignment>()
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            xp: entity.get::<Xp>().map_or(0., |x| x.0),
+            armor_class: entity.get::<ArmorClass>().map_or(10., |x| x.0.total),
+            initiative: saver.2 .0.calculate_modifier() as i64,
+            speed: entity.get::<Speed>().map_or(0., |x| x.0.total),
+            health: entity.get::<Health>().map_or(0., |x| x.0),
+            max_health: entity.get::<MaxHealth>().map_or(0., |x| x.0.total),
+            temp_health: entity.get::<TempHealth>().map_or(0., |x| x.0),
+            proficiency_bonus: bonus,
+            ..default()
+        };
+
+        let abilities = [
+            &saver.0 .0,
+            &saver.1 .0,
+            &saver.2 .0,
+            &saver.3 .0,
+            &saver.4 .0,
+            &saver.5 .0,
+        ];
+        for (stat, ability) in StatEnum::ABILITIES.into_iter().zip(abilities) {
+            let modifier = ability.calculate_modifier() as i64;
+            sheet.abilities.push(AbilityLine {
+                save: save_modifier(saver, &stat).unwrap_or(modifier),
+                stat,
+                score: ability.stat.total,
+                modifier,
+                proficiency: ability.proficiency.clone(),
+            });
+        }
+        for stat in StatEnum::SKILLS {
+            if let Some(skill) = skills.get(&stat) {
+                sheet.skills.push(SkillLine {
+                    stat,
+                    modifier: skill_modifier(skill, bonus),
+                    proficiency: skill.proficiency.clone(),
+                });
+            }
+        }
+        sheet.passives = [
+            ("Perception", &skills.perception.0),
+            ("Investigation", &skills.investigation.0),
+            ("Insight", &skills.insight.0),
+        ]
+        .into_iter()
+        .map(|(name, skill)| (name.to_string(), passive_score(skill, bonus)))
+        .collect();
+
+        let children = entity.get::<Children>().map(|x| x.to_vec());
+        let children = children.unwrap_or_default();
+        let belongings = Belongings::sort(belongings.iter_many(world, &children));
+        sheet.equipment = belongings.items.iter().map(|x| x.0.clone()).collect();
+        sheet.features = belongings.features.into_iter().cloned().collect();
+        sheet.spells = belongings.spells.iter().map(|x| x.0.clone()).collect();
+        if let Ok(attacker) = attackers.get(world, unit) {
+            for weapon in weapons.iter_many(world, &children) {
+                let (name, wep_type, dice, base, damage_type, finesse, att_mod, dmg_mod) = weapon;
+                let (bonus, ability) = weapon_bonuses(attacker, name, wep_type, finesse);
+                let damage = base.0 + ability + dmg_mod.map_or(0, |x| x.0);
+                let dice = format!("{}{}", dice.number, dice.dice_type).to_lowercase();
+                let damage = match damage {
+                    0 => dice,
+                    x => format!("{dice}{x:+}"),
+                };
+                sheet.attacks.push(AttackLine {
+                    name: name.0.clone(),
+                    bonus: bonus + att_mod.map_or(0, |x| x.0),
+                    damage: match damage_type {
+                        Some(x) => format!("{damage} {x}"),
+                        None => damage,
+                    },
+                });
+            }
+        }
+        Some(sheet)
+    }
+
+    /// The sheet as a US Letter PDF.
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut pages = vec![self.first_page()];
+        pages.extend(self.later_pages());
+
+        let mut next = Ref::new(1);
+        let catalog = next.bump();
+        let tree = next.bump();
+        let regular = next.bump();
+        let bold = next.bump();
+        let info = next.bump();
+        let ids = pages
+            .iter()
+            .map(|_| (next.bump(), next.bump()))
+            .collect::<Vec<_>>();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog).pages(tree);
+        pdf.pages(tree)
+            .kids(ids.iter().map(|x| x.0))
+            .count(ids.len() as i32);
+        for ((page_id, content_id), content) in ids.into_iter().zip(pages) {
+            let mut page = pdf.page(page_id);
+            page.media_box(Rect::new(0., 0., WIDTH, HEIGHT))
+                .parent(tree)
+                .contents(content_id);
+            page.resources()
+                .fonts()
+                .pair(REGULAR, regular)
+                .pair(BOLD, bold);
+            drop(page);
+            pdf.stream(content_id, &content.finish());
+        }
+        for (id, font) in [(regular, "Helvetica"), (bold, "Helvetica-Bold")] {
+            pdf.type1_font(id)
+                .base_font(Name(font.as_bytes()))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+        pdf.document_info(info)
+            .title(TextStr(&self.name))
+            .creator(TextStr("newtable"));
+        pdf.finish()
+    }
+
+    fn first_page(&self) -> Content {
+        let mut page = Page::default();
+        let top = HEIGHT - MARGIN;
+
+        // The header: the name on the left, the rest boxed on the right.
+        page.text(MARGIN, top - 30., 20., true, &self.name);
+        page.text(MARGIN, top - 42., 6., false, "CHARACTER NAME");
+        page.frame(200., top - 52., WIDTH - MARGIN - 200., 52.);
+        let fields = [
+            (format!("{} {}", self.class, self.level), "CLASS & LEVEL"),
+            (self.background.clone(), "BACKGROUND"),
+            (self.player.clone(), "PLAYER NAME"),
+            (self.race.clone(), "RACE"),
+            (self.alignment.clone(), "ALIGNMENT"),
+            (self.xp.to_string(), "EXPERIENCE POINTS"),
+        ];
+        for (i, (value, label)) in fields.iter().enumerate() {
+            let x = 208. + (i % 3) as f32 * 124.;
+            let y = top - 20. - (i / 3) as f32 * 24.;
+            page.text(x, y, 9., false, value);
+            page.text(x, y - 8., 5.5, false, label);
+        }
+
+        // Abilities down the left edge.
+        let mut y = top - 70.;
+        for ability in &self.abilities {
+            y -= 68.;
+            page.frame(MARGIN, y, 60., 62.);
+            let name = format!("{:?}", ability.stat).to_uppercase();
+            page.centred(MARGIN + 30., y + 52., 6., true, &name);
+            page.centred(MARGIN + 30., y + 26., 20., false, &signed(ability.modifier));
+            page.frame(MARGIN + 16., y + 4., 28., 14.);
+            page.centred(MARGIN + 30., y + 8., 9., false, &ability.score.to_string());
+        }
+
+        // Proficiency bonus, saves, skills and passives beside them.
+        let x = MARGIN + 68.;
+        let width = 160.;
+        let mut y = top - 88.;
+        page.frame(x, y, width, 18.);
+        page.text(x + 6., y + 5., 10., true, &signed(self.proficiency_bonus));
+        page.text(x + 30., y + 6., 7., false, "PROFICIENCY BONUS");
+
+        let lines = |n: usize| n as f32 * LINE + 18.;
+        y -= lines(self.abilities.len()) + 6.;
+        page.frame(x, y, width, lines(self.abilities.len()));
+        let mut line = y + lines(self.abilities.len()) - 14.;
+        for ability in &self.abilities {
+            let name = format!("{:?}", ability.stat);
+            page.proficiency_line(x + 6., line, &ability.proficiency, ability.save, &name);
+            line -= LINE;
+        }
+        page.centred(x + width / 2., y + 4., 6., true, "SAVING THROWS");
+
+        y -= lines(self.skills.len()) + 6.;
+        page.frame(x, y, width, lines(self.skills.len()));
+        let mut line = y + lines(self.skills.len()) - 14.;
+        for skill in &self.skills {
+            let name = format!("{} ({})", spaced(&skill.stat), ability_short(&skill.stat));
+            page.proficiency_line(x + 6., line, &skill.proficiency, skill.modifier, &name);
+            line -= LINE;
+        }
+        page.centred(x + width / 2., y + 4., 6., true, "SKILLS");
+
+        for (name, score) in &self.passives {
+            y -= 22.;
+            page.frame(x, y, width, 18.);
+            page.text(x + 6., y + 5., 10., true, &score.to_string());
+            let label = format!("PASSIVE WISDOM ({})", name.to_uppercase());
+            let label = match name.as_str() {
+                "Investigation" => "PASSIVE INTELLIGENCE (INVESTIGATION)".to_string(),
+                _ => label,
+            };
+            page.text(x + 30., y + 6., 6., false, &label);
+        }
+
+        // Combat numbers, attacks and equipment on the right.
+        let x = MARGIN + 236.;
+        let width = WIDTH - MARGIN - x;
+        let mut y = top - 122.;
+        let boxes = [
+            (self.armor_class.to_string(), "ARMOR CLASS"),
+            (signed(self.initiative), "INITIATIVE"),
+            (format!("{} ft", self.speed), "SPEED"),
+        ];
+        let third = (width - 16.) / 3.;
+        for (i, (value, label)) in boxes.iter().enumerate() {
+            let left = x + i as f32 * (third + 8.);
+            page.frame(left, y, third, 48.);
+            page.centred(left + third / 2., y + 20., 16., false, value);
+            page.centred(left + third / 2., y + 6., 6., true, label);
+        }
+
+        y -= 58.;
+        page.frame(x, y, width, 48.);
+        page.text(
+            x + 6.,
+            y + 36.,
+            7.,
+            false,
+            &format!("Hit point maximum: {}", self.max_health),
+        );
+        page.centred(
+            x + width / 2.,
+            y + 16.,
+            16.,
+            false,
+            &self.health.to_string(),
+        );
+        page.centred(x + width / 2., y + 6., 6., true, "CURRENT HIT POINTS");
+        y -= 28.;
+        page.frame(x, y, width, 24.);
+        page.text(x + 6., y + 8., 10., false, &self.temp_health.to_string());
+        page.text(x + 40., y + 9., 6., true, "TEMPORARY HIT POINTS");
+
+        let rows = self.attacks.len().max(3);
+        y -= lines(rows) + 14.;
+        page.frame(x, y, width, lines(rows) + 8.);
+        let mut line = y + lines(rows) - 6.;
+        page.text(x + 6., line, 6., true, "NAME");
+        page.text(x + 150., line, 6., true, "ATK BONUS");
+        page.text(x + 200., line, 6., true, "DAMAGE/TYPE");
+        for attack in &self.attacks {
+            line -= LINE;
+            page.text(x + 6., line, 8., false, &attack.name);
+            page.text(x + 150., line, 8., false, &signed(attack.bonus));
+            page.text(x + 200., line, 8., false, &attack.damage);
+        }
+        page.centred(x + width / 2., y + 4., 6., true, "ATTACKS & SPELLCASTING");
+
+        let bottom = MARGIN + 14.;
+        page.frame(x, bottom - 14., width, y - 6. - bottom + 14.);
+        let mut line = y - 20.;
+        for item in &self.equipment {
+            for text in wrap(item, width - 12., 8.) {
+                if line < bottom {
+                    break;
+                }
+                page.text(x + 6., line, 8., false, &text);
+                line -= LINE;
+            }
+        }
+        page.centred(x + width / 2., bottom - 10., 6., true, "EQUIPMENT");
+        page.0
+    }
+
+    /// Features and spells, running on to more pages if they don't fit.
+    fn later_pages(&self) -> Vec<Content> {
+        let mut flow = Flow::new(&self.name);
+        flow.heading("FEATURES & TRAITS");
+        if self.features.is_empty() {
+            flow.paragraph(false, "None");
+        }
+        for feature in &self.features {
+            flow.paragraph(true, &feature.name);
+            flow.paragraph(false, &feature.description);
+            flow.gap();
+        }
+        flow.gap();
+        flow.heading("SPELLS");
+        if self.spells.is_empty() {
+            flow.paragraph(false, "None");
+        }
+        for spell in &self.spells {
+            flow.paragraph(false, spell);
+        }
+        flow.finish()
+    }
+}
+
+/// US Letter, in points.
+const WIDTH: f32 = 612.;
+const HEIGHT: f32 = 792.;
+const MARGIN: f32 = 36.;
+const LINE: f32 = 11.;
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+fn signed(x: i64) -> String {
+    format!("{x:+}")
+}
+
+/// "SleightOfHand" as "Sleight Of Hand".
+fn spaced(stat: &StatEnum) -> String {
+    let name = format!("{stat:?}");
+    let mut spaced = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            spaced.push(' ');
+        }
+        spaced.push(c);
+    }
+    spaced
+}
+
+fn ability_short(stat: &StatEnum) -> String {
+    let ability = stat.ability().map(|x| format!("{x:?}")).unwrap_or_default();
+    ability.chars().take(3).collect()
+}
+
+/// Text in the standard fonts' encoding. Anything they can't show is
+/// printed as "?".
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            x @ (0x20..=0x7e | 0xa0..=0xff) => x as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Roughly how wide `text` is in Helvetica; close enough to centre labels
+/// and wrap lines.
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * 0.52
+}
+
+/// `text` broken into lines no wider than `width`.
+fn wrap(text: &str, width: f32, size: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = match line.is_empty() {
+            true => word.to_string(),
+            false => format!("{line} {word}"),
+        };
+        if !line.is_empty() && text_width(&candidate, size) > width {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        } else {
+            line = candidate;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// One page's drawing, in points from its bottom left.
+struct Page(Content);
+
+impl Default for Page {
+    fn default() -> Self {
+        Page(Content::new())
+    }
+}
+
+impl Page {
+    fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        let font = if bold { BOLD } else { REGULAR };
+        self.0
+            .begin_text()
+            .set_font(font, size)
+            .next_line(x, y)
+            .show(Str(&encode(text)))
+            .end_text();
+    }
+
+    fn centred(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        self.text(x - text_width(text, size) / 2., y, size, bold, text);
+    }
+
+    fn frame(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.0
+            .set_line_width(0.75)
+            .rect(x, y, width, height)
+            .stroke();
+    }
+
+    /// A save or skill: a box filled in if proficient, with a second one
+    /// for expertise, then the modifier and the name.
+    fn proficiency_line(
+        &mut self,
+        x: f32,
+        y: f32,
+        proficiency: &Proficiency,
+        modifier: i64,
+        name: &str,
+    ) {
+        for (i, filled) in [
+            *proficiency != Proficiency::None,
+            *proficiency == Proficiency::Expert,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            self.0.rect(x + i as f32 * 7., y, 5., 5.);
+            match filled {
+                true => self.0.fill_nonzero(),
+                false => self.0.stroke(),
+            };
+        }
+        self.text(x + 16., y, 8., false, &signed(modifier));
+        self.text(x + 36., y, 8., false, name);
+    }
+}
+
+/// Text flowing down the page, onto a new page when it runs out.
+struct Flow {
+    name: String,
+    pages: Vec<Content>,
+    page: Page,
+    y: f32,
+}
+
+impl Flow {
+    fn new(name: &str) -> Self {
+        let mut flow = Flow {
+            name: name.to_string(),
+            pages: Vec::new(),
+            page: Page::default(),
+            y: 0.,
+        };
+        flow.start_page();
+        flow
+    }
+
+    fn start_page(&mut self) {
+        let mut page = Page::default();
+        page.text(MARGIN, HEIGHT - MARGIN - 16., 14., true, &self.name);
+        let done = std::mem::replace(&mut self.page, page);
+        if self.y != 0. {
+            self.pages.push(done.0);
+        }
+        self.y = HEIGHT - MARGIN - 44.;
+    }
+
+    fn line(&mut self, size: f32, bold: bool, text: &str) {
+        if self.y < MARGIN {
+            self.start_page();
+        }
+        self.page.text(MARGIN, self.y, size, bold, text);
+        self.y -= size + 3.;
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.line(10., true, text);
+        self.y -= 4.;
+    }
+
+    fn paragraph(&mut self, bold: bool, text: &str) {
+        for line in wrap(text, WIDTH - 2. * MARGIN, 9.) {
+            self.line(9., bold, &line);
+        }
+    }
+
+    fn gap(&mut self) {
+        self.y -= 6.;
+    }
+
+    fn finish(mut self) -> Vec<Content> {
+        self.pages.push(self.page.0);
+        self.pages
+    }
+}
+
+/// Writes `unit`'s sheet to `<dir>/<name>.pdf` and returns where it went.
+pub fn export_sheet(world: &mut World, dir: &Path, unit: Entity) -> Result<PathBuf, SaveError> {
+    let Some(sheet) = PrintedSheet::from_world(world, unit) else {
+        return Err(SaveError::NotACharacter(unit));
+    };
+    ensure_dir(dir)?;
+    let path = dir.join(format!("{}.pdf", slot_name(&sheet.name)));
+    io(&path, fs::write(&path, sheet.to_pdf()))?;
+    Ok(path)
+}
+
+/// Exports a character's sheet to `SHEET_DIR`, reporting failures in
+/// `SaveErrors`.
+pub struct ExportSheet(pub Entity);
+
+impl Command for ExportSheet {
+    fn apply(self, world: &mut World) {
+        match export_sheet(world, Path::new(SHEET_DIR), self.0) {
+            Ok(path) => info!("Exported the character sheet to {}", path.display()),
+            Err(e) => world.resource_mut::<SaveErrors>().0.push(e),
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::components::*;
+use crate::items::{SetProficiency, UpdateStat};
+use bevy::{ecs::world::Command, prelude::*};
+use serde::Deserialize;
+
+pub struct RacesPlugin;
+
+impl Plugin for RacesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RaceCatalog::from_ron(include_str!(
+            "../assets/data/races.ron"
+        )));
+        app.register_type::<Size>();
+        app.register_type::<Resistances>();
+        app.register_type::<SaveAdvantages>();
+        app.register_type::<Lucky>();
+        app.register_type::<Languages>();
+        app.register_type::<ArmorProficiencies>();
+        app.register_type::<ToolProficiencies>();
+        app.register_type::<DraconicAncestry>();
+        app.register_type::<Feature>();
+        app.register_type::<RacialTrait>();
+        app.register_type::<RacialSpell>();
+        app.register_type::<BreathWeapon>();
+        app.register_type::<DarkVision>();
+        app.register_type::<StatModList>();
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RaceCatalog(pub Vec<RaceData>);
+
+#[derive(Deserialize, Clone)]
+pub struct RaceData {
+    pub name: String,
+    #[serde(default)]
+    pub race: Option<Race>,
+    pub traits: RacialTraits,
+    #[serde(default)]
+    pub subraces: Vec<SubraceData>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SubraceData {
+    pub name: String,
+    pub race: Race,
+    pub traits: RacialTraits,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RacialTraits {
+    pub ability_increases: Vec<(StatEnum, f64)>,
+    pub ability_choices: Option<AbilityChoice>,
+    pub speed: Option<f64>,
+    pub size: Option<Size>,
+    pub darkvision: Option<f64>,
+    pub max_health_per_level: f64,
+    pub resistances: Vec<DamageType>,
+    /// Damage types the race has advantage on saving throws against.
+    pub save_advantages: Vec<DamageType>,
+    /// Whether natural 1s on the d20 are rerolled.
+    pub lucky: bool,
+    pub languages: Vec<Language>,
+    pub extra_languages: usize,
+    pub skills: Vec<StatEnum>,
+    pub skill_choices: usize,
+    pub weapons: Vec<String>,
+    pub armor: Vec<ArmorCategory>,
+    pub tools: Vec<String>,
+    pub tool_choices: Vec<String>,
+    pub spells: Vec<RacialSpellData>,
+    pub features: Vec<FeatureData>,
+    pub ancestries: Vec<AncestryData>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct AbilityChoice {
+    pub count: usize,
+    pub amount: f64,
+    #[serde(default)]
+    pub exclude: Vec<StatEnum>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RacialSpellData {
+    pub name: String,
+    pub min_level: i64,
+    pub ability: StatEnum,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct FeatureData {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AncestryData {
+    pub ancestry: DraconicAncestry,
+    pub damage_type: DamageType,
+    pub area: String,
+    pub save: StatEnum,
+}
+
+impl RaceCatalog {
+    pub fn from_ron(data: &str) -> Self {
+        Self(ron::from_str(data).expect("races.ron to be a valid race catalog"))
+    }
+
+    /// The display name of a race, including the parent race for subraces.
+    pub fn name(&self, race: &Race) -> String {
+        for data in &self.0 {
+            if data.race.as_ref() == Some(race) {
+                return data.name.clone();
+            }
+            if let Some(sub) = data.subraces.iter().find(|x| &x.race == race) {
+                return sub.name.clone();
+            }
+        }
+        race.to_string()
+    }
+
+    /// Every set of traits that applies to a race: the parent race first,
+    /// then the subrace if there is one.
+    pub fn traits(&self, race: &Race) -> Vec<&RacialTraits> {
+        for data in &self.0 {
+            if data.race.as_ref() == Some(race) {
+                return vec![&data.traits];
+            }
+            if let Some(sub) = data.subraces.iter().find(|x| &x.race == race) {
+                return vec![&data.traits, &sub.traits];
+            }
+        }
+        warn!("No racial traits defined for {race}");
+        vec![]
+    }
+
+    /// All fixed ability score increases for a race, parent and subrace summed.
+    pub fn ability_increase(&self, race: &Race, ability: &StatEnum) -> f64 {
+        self.traits(race)
+            .iter()
+            .flat_map(|x| x.ability_increases.iter())
+            .filter(|x| &x.0 == ability)
+            .map(|x| x.1)
+            .sum()
+    }
+
+    /// The full racial bonus to an ability, counting the player's picks for
+    /// races that let them choose which abilities to increase.
+    pub fn ability_bonus(&self, race: &Race, choices: &RacialChoices, ability: &StatEnum) -> f64 {
+        let chosen: f64 = self
+            .traits(race)
+            .iter()
+            .filter_map(|x| x.ability_choices.as_ref())
+            .filter(|choice| {
+                choices
+                    .abilities
+                    .iter()
+                    .filter(|x| !choice.exclude.contains(x))
+                    .take(choice.count)
+                    .any(|x| x == ability)
+            })
+            .map(|choice| choice.amount)
+            .sum();
+        self.ability_increase(race, ability) + chosen
+    }
+
+    pub fn speed(&self, race: &Race) -> f64 {
+        self.traits(race).iter().filter_map(|x| x.speed).sum()
+    }
+
+    pub fn size(&self, race: &Race) -> Size {
+        self.traits(race)
+            .iter()
+            .filter_map(|x| x.size)
+            .last()
+            .unwrap_or_default()
+    }
+
+    pub fn darkvision(&self, race: &Race) -> Option<f64> {
+        self.traits(race)
+            .iter()
+            .filter_map(|x| x.darkvision)
+            .reduce(f64::max)
+    }
+}
+
+/// Choices a race leaves up to the player, made in the new character UI.
+#[derive(Resource, Default, Clone)]
+pub struct RacialChoices {
+    pub ancestry: DraconicAncestry,
+    pub abilities: Vec<StatEnum>,
+    pub skills: Vec<StatEnum>,
+    pub tool: Option<String>,
+    pub languages: Vec<Language>,
+}
+
+/// Applies every trait of a race (and subrace) to a freshly spawned unit.
+///
+/// Ability increases, speed, darkvision and hit point bonuses are collected
+/// into a StatModList on a `RacialTrait` child of the unit, then each touched
+/// stat is recalculated with UpdateStat. Features, racial spells and the
+/// dragonborn breath weapon are spawned as children as well, so they are
+/// saved along with the unit.
+/// Resistances, advantage on saves against a damage type and `Lucky` go on
+/// the unit itself, where the rolls that use them read them.
+pub struct ApplyRace {
+    pub unit: Entity,
+    pub race: Race,
+    pub choices: RacialChoices,
+}
+
+impl Command for ApplyRace {
+    fn apply(self, world: &mut World) {
+        let unit = self.unit;
+        let traits = world
+            .resource::<RaceCatalog>()
+            .traits(&self.race)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<RacialTraits>>();
+        let level = world.get::<Level>(unit).map(|x| x.0).unwrap_or(1);
+
+        let mut mods = Vec::new();
+        let mut resistances = Vec::new();
+        let mut save_advantages = Vec::new();
+        let mut languages = Vec::new();
+        let mut armor = Vec::new();
+        let mut tools = Vec::new();
+        let mut weapons = Vec::new();
+        let mut skills = Vec::new();
+        let mut size = Size::Medium;
+        let mut children = Vec::new();
+
+        for t in &traits {
+            for (stat, value) in &t.ability_increases {
+                mods.push(StatMod {
+                    stat: stat.clone(),
+                    value: *value,
+                    mod_type: ModType::Add,
+                });
+            }
+            if let Some(choice) = &t.ability_choices {
+                for stat in self
+                    .choices
+                    .abilities
+                    .iter()
+                    .filter(|x| !choice.exclude.contains(x))
+                    .take(choice.count)
+                {
+                    mods.push(StatMod {
+                        stat: stat.clone(),
+                        value: choice.amount,
+                        mod_type: ModType::Add,
+                    });
+                }
+            }
+            if let Some(speed) = t.speed {
+                mods.push(StatMod {
+                    stat: StatEnum::Speed,
+                    value: speed,
+                    mod_type: ModType::Add,
+                });
+            }
+            if let Some(dv) = t.darkvision {
+                mods.push(StatMod {
+                    stat: StatEnum::DarkVision,
+                    value: dv,
+                    mod_type: ModType::BestOf,
+                });
+            }
+            if t.max_health_per_level != 0. {
+                mods.push(StatMod {
+                    stat: StatEnum::MaxHealth,
+                    value: t.max_health_per_level * level as f64,
+                    mod_type: ModType::Add,
+                });
+            }
+            if let Some(s) = t.size {
+                size = s;
+            }
+            // Subraces repeat some of their race's traits.
+            extend_unique(&mut resistances, t.resistances.iter().copied());
+            extend_unique(&mut save_advantages, t.save_advantages.iter().copied());
+            extend_unique(&mut languages, t.languages.iter().copied());
+            extend_unique(
+                &mut languages,
+                self.choices
+                    .languages
+                    .iter()
+                    .copied()
+                    .take(t.extra_languages),
+            );
+            extend_unique(&mut armor, t.armor.iter().copied());
+            extend_unique(&mut tools, t.tools.iter().cloned());
+            if !t.tool_choices.is_empty() {
+                let tool = match &self.choices.tool {
+                    Some(tool) if t.tool_choices.contains(tool) => tool,
+                    _ => &t.tool_choices[0],
+                };
+                extend_unique(&mut tools, [tool.clone()]);
+            }
+            weapons.extend(t.weapons.iter().map(|x| ItemName(x.clone())));
+            skills.extend(t.skills.iter().cloned());
+            skills.extend(self.choices.skills.iter().cloned().take(t.skill_choices));
+
+            for feature in &t.features {
+                children.push(
+                    world
+                        .spawn((
+                            RacialTrait,
+                            Feature {
+                                name: feature.name.clone(),
+                                description: feature.description.clone(),
+                            },
+                        ))
+                        .id(),
+                );
+            }
+            for spell in &t.spells {
+                children.push(
+                    world
+                        .spawn((
+                            RacialTrait,
+                            Spell,
+                            SpellName(spell.name.clone()),
+                            RacialSpell {
+                                min_level: spell.min_level,
+                                ability: spell.ability.clone(),
+                            },
+                        ))
+                        .id(),
+                );
+            }
+            if let Some(anc) = t
+                .ancestries
+                .iter()
+                .find(|x| x.ancestry == self.choices.ancestry)
+            {
+                resistances.push(anc.damage_type);
+                world.entity_mut(unit).insert(anc.ancestry);
+                children.push(
+                    world
+                        .spawn((
+                            RacialTrait,
+                            Feature {
+                                name: "Breath Weapon".into(),
+                                description: format!(
+                                    "You can use your action to exhale {} energy in a {}. \
+                                    Each creature in the area must make a {:?} saving throw \
+                                    (DC 8 + your Constitution modifier + your proficiency bonus), \
+                                    taking full damage on a failed save and half as much on a success.",
+                                    anc.damage_type, anc.area, anc.save
+                                ),
+                            },
+                            BreathWeapon {
+                                area: anc.area.clone(),
+                                save: anc.save.clone(),
+                            },
+                            DamageBundle {
+                                damage_type: anc.damage_type,
+                                base_damage: BaseDamage(0),
+                                dice: Dice {
+                                    dice_type: DiceType::D6,
+                                    number: breath_weapon_dice(level),
+                                },
+                            },
+                        ))
+                        .id(),
+                );
+            }
+        }
+
+        let stat_mods = world
+            .spawn((
+                RacialTrait,
+                Feature {
+                    name: world.resource::<RaceCatalog>().name(&self.race),
+                    description: "Racial ability score increases and senses".into(),
+                },
+                StatModList(mods.clone()),
+            ))
+            .id();
+        children.insert(0, stat_mods);
+
+        let mut unit_mut = world.entity_mut(unit);
+        unit_mut.push_children(&children);
+        unit_mut.insert((
+            size,
+            Resistances(resistances),
+            SaveAdvantages(save_advantages),
+            Languages(languages),
+            ArmorProficiencies(armor),
+            ToolProficiencies(tools),
+        ));
+        if traits.iter().any(|x| x.lucky) {
+            unit_mut.insert(Lucky);
+        }
+        if !unit_mut.contains::<DarkVision>() {
+            unit_mut.insert(DarkVision(Stat::new(0., vec![])));
+        }
+        if let Some(mut ind) = unit_mut.get_mut::<IndividualWeaponProficiency>() {
+            ind.0.extend(weapons);
+        }
+
+        for skill in skills {
+            SetProficiency(unit, skill, Proficiency::Proficient).apply(world);
+        }
+        let mut stats = Vec::new();
+        for m in mods {
+            if !stats.contains(&m.stat) {
+                stats.push(m.stat);
+            }
+        }
+        for stat in stats {
+            UpdateStat(unit, stat).apply(world);
+        }
+    }
+}
+
+/// Adds the `items` that `list` doesn't have yet, in order.
+pub(crate) fn extend_unique<T: PartialEq>(list: &mut Vec<T>, items: impl IntoIterator<Item = T>) {
+    for item in items {
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+}
+
+fn breath_weapon_dice(level: i64) -> i64 {
+    match level {
+        ..=5 => 2,
+        6..=10 => 3,
+        11..=15 => 4,
+        _ => 5,
+    }
+}
@@ -0,0 +1,864 @@
+use crate::components::*;
+use crate::migrations::{load_scene, MigrationError, SaveVersion, SAVE_VERSION};
+use crate::party::{self, PartySave};
+use crate::races::RaceCatalog;
+use crate::AppState;
+use bevy::ecs::entity::EntityHashMap;
+use bevy::ecs::system::SystemState;
+use bevy::ecs::world::Command;
+use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future, IoTaskPool, Task};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SAVE_DIR: &str = "assets/saves";
+
+/// How many earlier versions of each slot are kept in `backups/`.
+pub const BACKUPS: usize = 3;
+
+pub struct SavesPlugin;
+
+impl Plugin for SavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveDir>();
+        app.init_resource::<SaveSlot>();
+        app.init_resource::<SaveErrors>();
+        app.init_resource::<PendingSaves>();
+        app.register_type::<PlayTime>();
+        app.register_type::<SaveVersion>();
+        app.add_systems(Update, tick_play_time.run_if(in_state(AppState::InGame)));
+        app.add_systems(Update, poll_pending_saves);
+        app.add_systems(OnEnter(AppState::SaveCharacter), save_game);
+    }
+}
+
+/// Shows `SaveErrors` to the player.
+pub struct SavesUiPlugin;
+
+impl Plugin for SavesUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, save_errors_ui);
+    }
+}
+
+/// Where the game's slots are written and read from. `SAVE_DIR` unless
+/// something, like a test, points it elsewhere.
+#[derive(Resource, Clone, Debug)]
+pub struct SaveDir(pub PathBuf);
+
+impl Default for SaveDir {
+    fn default() -> Self {
+        Self(PathBuf::from(SAVE_DIR))
+    }
+}
+
+/// The slot the current game saves to. When None the slot is named after
+/// the player character, or the party's members.
+#[derive(Resource, Default)]
+pub struct SaveSlot(pub Option<String>);
+
+/// Written next to each save as `<slot>.meta.ron`, so slots can be listed
+/// without loading their scenes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct SaveMetadata {
+    pub name: String,
+    pub race: String,
+    pub class: Class,
+    pub level: i64,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    /// Seconds spent in game.
+    pub play_time: f64,
+    pub version: String,
+    /// The `SAVE_VERSION` of the scene. Headers from before it was recorded
+    /// read as 0.
+    pub schema: u32,
+}
+
+impl SaveMetadata {
+    /// Metadata for the player character in `world`, stamped with the
+    /// current time and game version. With a party, that's whoever comes
+    /// first.
+    pub fn from_world(world: &mut World) -> Self {
+        let mut players = world.query_filtered::<Entity, With<Player>>();
+        match players.iter(world).next() {
+            Some(player) => Self::for_unit(world, player),
+            None => Self::default(),
+        }
+    }
+
+    /// Metadata for one player character, for when each member of a party
+    /// is saved on their own.
+    pub fn for_unit(world: &mut World, unit: Entity) -> Self {
+        let mut players = world.query::<(&UnitName, &Race, &Class, &Level, Option<&PlayTime>)>();
+        let Ok((name, race, class, level, play_time)) = players.get(world, unit) else {
+            return Self::default();
+        };
+        let race = race.clone();
+        let mut metadata = Self {
+            name: name.0.clone(),
+            race: race.to_string(),
+            class: class.clone(),
+            level: level.0,
+            play_time: play_time.map(|x| x.0).unwrap_or(0.),
+            ..default()
+        };
+        if let Some(races) = world.get_resource::<RaceCatalog>() {
+            metadata.race = races.name(&race);
+        }
+        metadata.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        metadata.version = env!("CARGO_PKG_VERSION").to_string();
+        metadata.schema = SAVE_VERSION;
+        metadata
+    }
+
+    /// "Level 3 Hill Dwarf Fighter"
+    pub fn summary(&self) -> String {
+        format!("Level {} {} {}", self.level, self.race, self.class)
+    }
+}
+
+/// A save slot found on disk. `metadata` is None for saves written before
+/// slots had a header, or when the header can't be read.
+#[derive(Clone, Debug)]
+pub struct SlotInfo {
+    pub slot: String,
+    pub metadata: Option<SaveMetadata>,
+}
+
+/// Turns a character or user-chosen name into a file name safe slot.
+pub fn slot_name(name: &str) -> String {
+    let slot = name
+        .trim()
+        .chars()
+        .map(|x| match x {
+            x if x.is_alphanumeric() || x == '-' || x == '_' => x,
+            _ => '-',
+        })
+        .collect::<String>();
+    match slot.is_empty() {
+        true => "unnamed".to_string(),
+        false => slot,
+    }
+}
+
+pub fn scene_path(dir: &Path, slot: &str) -> PathBuf {
+    dir.join(format!("{slot}.scn.ron"))
+}
+
+pub fn metadata_path(dir: &Path, slot: &str) -> PathBuf {
+    dir.join(format!("{slot}.meta.ron"))
+}
+
+/// The files that make up a slot.
+const SLOT_FILES: [fn(&Path, &str) -> PathBuf; 2] = [scene_path, metadata_path];
+
+pub fn backup_dir(dir: &Path) -> PathBuf {
+    dir.join("backups")
+}
+
+/// Backups are slots in `backups/` named `<slot>.1` (the newest) up to
+/// `<slot>.BACKUPS`. Slot names never contain a dot, so they can't clash.
+fn backup_slot(slot: &str, n: usize) -> String {
+    format!("{slot}.{n}")
+}
+
+/// Everything that can go wrong reading, writing or managing save slots.
+#[derive(Debug)]
+pub enum SaveError {
+    Io(PathBuf, io::Error),
+    Serialize(String),
+    /// A file other than a save, like an encounter, that couldn't be read.
+    Parse(PathBuf, String),
+    Version(MigrationError),
+    Spawn(String),
+    SlotExists(String),
+    NoBackup(String),
+    /// A slot can't be deleted while a saved party, the second field, has
+    /// it as a member.
+    InParty(String, String),
+    /// A loaded save must hold exactly one player character.
+    PlayerCount(usize),
+    /// Only player characters have a sheet to export.
+    NotACharacter(Entity),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            SaveError::Serialize(e) => write!(f, "could not write save: {e}"),
+            SaveError::Parse(path, e) => write!(f, "{}: {e}", path.display()),
+            SaveError::Version(e) => e.fmt(f),
+            SaveError::Spawn(e) => write!(f, "could not spawn save: {e}"),
+            SaveError::SlotExists(slot) => write!(f, "a save called {slot} already exists"),
+            SaveError::NoBackup(slot) => write!(f, "{slot} has no backups"),
+            SaveError::InParty(slot, party) => {
+                write!(f, "{slot} is a member of the saved party {party}")
+            }
+            SaveError::PlayerCount(count) => {
+                write!(f, "save has {count} player characters instead of one")
+            }
+            SaveError::NotACharacter(unit) => write!(f, "{unit:?} has no character sheet"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<MigrationError> for SaveError {
+    fn from(e: MigrationError) -> Self {
+        SaveError::Version(e)
+    }
+}
+
+impl SaveError {
+    /// Whether the save file itself is damaged, rather than missing or from
+    /// a newer game.
+    pub fn is_corrupt(&self) -> bool {
+        matches!(self, SaveError::Version(MigrationError::Parse(_)))
+    }
+}
+
+/// Errors from saving and loading, shown to the player until dismissed.
+#[derive(Resource, Default)]
+pub struct SaveErrors(pub Vec<SaveError>);
+
+pub(crate) fn io<T>(path: &Path, result: io::Result<T>) -> Result<T, SaveError> {
+    result.map_err(|e| SaveError::Io(path.to_path_buf(), e))
+}
+
+/// Creates the save directory if it doesn't exist yet.
+pub fn ensure_dir(dir: &Path) -> Result<(), SaveError> {
+    io(dir, fs::create_dir_all(dir))
+}
+
+/// Writes `contents` to a temporary file next to `path`, flushes it to disk
+/// and renames it over `path`. A crash part way through leaves either the
+/// old file or the new one, never a truncated mix. Every write gets a
+/// temporary file of its own, so two writers can't clobber each other's.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), SaveError> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = PathBuf::from(tmp);
+    let written = fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(SaveError::Io(path.to_path_buf(), e));
+    }
+    // Flush the rename too. Not every platform can open a directory for it.
+    if let Some(dir) = path.parent().and_then(|x| fs::File::open(x).ok()) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Shifts the backups of `slot` down by one, dropping the oldest, and copies
+/// the slot as it is now into the newest backup.
+fn rotate_backups(dir: &Path, slot: &str) -> Result<(), SaveError> {
+    if !scene_path(dir, slot).exists() {
+        return Ok(());
+    }
+    let backups = backup_dir(dir);
+    ensure_dir(&backups)?;
+    for n in (1..BACKUPS).rev() {
+        for file in SLOT_FILES {
+            let path = file(&backups, &backup_slot(slot, n));
+            if path.exists() {
+                io(
+                    &path,
+                    fs::rename(&path, file(&backups, &backup_slot(slot, n + 1))),
+                )?;
+            }
+        }
+    }
+    for file in SLOT_FILES {
+        let path = file(dir, slot);
+        if path.exists() {
+            let contents = io(&path, fs::read_to_string(&path))?;
+            write_atomic(&file(&backups, &backup_slot(slot, 1)), &contents)?;
+        }
+    }
+    Ok(())
+}
+
+/// The lock for writes to `slot`. A manual save and an autosave can both be
+/// in flight, and each must rotate the backups and write the slot in one go.
+fn slot_lock(dir: &Path, slot: &str) -> Arc<Mutex<()>> {
+    static LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> =
+        LazyLock::new(Default::default);
+    let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    locks.entry(scene_path(dir, slot)).or_default().clone()
+}
+
+/// Writes a slot, keeping its previous versions as backups. Writes to the
+/// same slot take turns.
+pub fn write_slot(
+    dir: &Path,
+    slot: &str,
+    scene: &str,
+    metadata: &SaveMetadata,
+) -> Result<(), SaveError> {
+    let header = ron::ser::to_string_pretty(metadata, ron::ser::PrettyConfig::default())
+        .map_err(|e| SaveError::Serialize(e.to_string()))?;
+    ensure_dir(dir)?;
+    let lock = slot_lock(dir, slot);
+    let _writing = lock.lock().unwrap_or_else(PoisonError::into_inner);
+    rotate_backups(dir, slot)?;
+    write_atomic(&scene_path(dir, slot), scene)?;
+    write_atomic(&metadata_path(dir, slot), &header)
+}
+
+/// The newest backup of `slot`, if it has any.
+pub fn latest_backup(dir: &Path, slot: &str) -> Option<String> {
+    (1..=BACKUPS)
+        .map(|n| backup_slot(slot, n))
+        .find(|x| scene_path(&backup_dir(dir), x).exists())
+}
+
+/// Replaces `slot` with its newest backup.
+pub fn restore_backup(dir: &Path, slot: &str) -> Result<(), SaveError> {
+    let backups = backup_dir(dir);
+    let lock = slot_lock(dir, slot);
+    let _writing = lock.lock().unwrap_or_else(PoisonError::into_inner);
+    let backup = latest_backup(dir, slot).ok_or(SaveError::NoBackup(slot.to_string()))?;
+    for file in SLOT_FILES {
+        let path = file(&backups, &backup);
+        if path.exists() {
+            let contents = io(&path, fs::read_to_string(&path))?;
+            write_atomic(&file(dir, slot), &contents)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_metadata(dir: &Path, slot: &str) -> Option<SaveMetadata> {
+    let header = fs::read_to_string(metadata_path(dir, slot)).ok()?;
+    ron::from_str(&header).ok()
+}
+
+/// The slots of every file in `dir` named `<slot><suffix>`, sorted by name.
+pub fn slot_names(dir: &Path, suffix: &str) -> Result<Vec<String>, SaveError> {
+    ensure_dir(dir)?;
+    let mut slots = Vec::new();
+    for entry in io(dir, fs::read_dir(dir))? {
+        let file_name = io(dir, entry)?.file_name();
+        if let Some(slot) = file_name.to_str().and_then(|x| x.strip_suffix(suffix)) {
+            slots.push(slot.to_string());
+        }
+    }
+    slots.sort();
+    Ok(slots)
+}
+
+/// Every slot in `dir`, most recently saved first.
+pub fn list_slots(dir: &Path) -> Result<Vec<SlotInfo>, SaveError> {
+    let mut slots = slot_names(dir, ".scn.ron")?
+        .into_iter()
+        .map(|slot| SlotInfo {
+            metadata: read_metadata(dir, &slot),
+            slot,
+        })
+        .collect::<Vec<_>>();
+    slots.sort_by(|a, b| {
+        let time = |x: &SlotInfo| x.metadata.as_ref().map(|x| x.timestamp).unwrap_or(0);
+        time(b).cmp(&time(a)).then(a.slot.cmp(&b.slot))
+    });
+    Ok(slots)
+}
+
+/// Deletes a slot along with its backups. A member of a saved party can't
+/// be deleted.
+pub fn delete_slot(dir: &Path, slot: &str) -> Result<(), SaveError> {
+    if let Some(party) = party::parties_with(dir, slot)?.into_iter().next() {
+        return Err(SaveError::InParty(slot.to_string(), party));
+    }
+    let path = scene_path(dir, slot);
+    io(&path, fs::remove_file(&path))?;
+    let backups = backup_dir(dir);
+    let paths = (1..=BACKUPS).flat_map(|n| SLOT_FILES.map(|x| x(&backups, &backup_slot(slot, n))));
+    for path in paths.chain([metadata_path(dir, slot)]) {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(SaveError::Io(path, e)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Renames a slot, and the saved parties it's a member of follow it. The
+/// character inside keeps its name.
+pub fn rename_slot(dir: &Path, from: &str, to: &str) -> Result<String, SaveError> {
+    let to = slot_name(to);
+    if scene_path(dir, &to).exists() {
+        return Err(SaveError::SlotExists(to));
+    }
+    let path = scene_path(dir, from);
+    io(&path, fs::rename(&path, scene_path(dir, &to)))?;
+    let path = metadata_path(dir, from);
+    if path.exists() {
+        io(&path, fs::rename(&path, metadata_path(dir, &to)))?;
+    }
+    let backups = backup_dir(dir);
+    for n in 1..=BACKUPS {
+        for file in SLOT_FILES {
+            let path = file(&backups, &backup_slot(from, n));
+            if path.exists() {
+                io(
+                    &path,
+                    fs::rename(&path, file(&backups, &backup_slot(&to, n))),
+                )?;
+            }
+        }
+    }
+    party::rename_member(dir, from, &to)?;
+    Ok(to)
+}
+
+/// `<slot>-copy`, `<slot>-copy-2`, ... for as many copies of `slot` as it
+/// takes to find a free one.
+pub(crate) fn copy_slots(slot: &str) -> impl Iterator<Item = String> + '_ {
+    (1..).map(move |i| match i {
+        1 => format!("{slot}-copy"),
+        i => format!("{slot}-copy-{i}"),
+    })
+}
+
+/// Copies a slot to the first free `<slot>-copy`, `<slot>-copy-2`, ... and
+/// returns the new slot.
+pub fn duplicate_slot(dir: &Path, slot: &str) -> Result<String, SaveError> {
+    let copy = copy_slots(slot)
+        .find(|x| !scene_path(dir, x).exists())
+        .unwrap();
+    let path = scene_path(dir, slot);
+    io(&path, fs::copy(&path, scene_path(dir, &copy)))?;
+    let path = metadata_path(dir, slot);
+    if path.exists() {
+        io(&path, fs::copy(&path, metadata_path(dir, &copy)))?;
+    }
+    Ok(copy)
+}
+
+/// Reads a slot, upgrades it to the current save version and writes it into
+/// the world. The save is only kept if it holds exactly one player, which is
+/// returned; otherwise everything it spawned is despawned again.
+pub fn spawn_slot(world: &mut World, dir: &Path, slot: &str) -> Result<Entity, SaveError> {
+    let path = scene_path(dir, slot);
+    let text = io(&path, fs::read_to_string(&path))?;
+    spawn_scene(world, &text)
+}
+
+pub fn spawn_scene(world: &mut World, text: &str) -> Result<Entity, SaveError> {
+    let scene = {
+        let registry = world.resource::<AppTypeRegistry>().read();
+        load_scene(text, &registry)?
+    };
+    let mut entity_map = EntityHashMap::default();
+    let result = scene
+        .write_to_world(world, &mut entity_map)
+        .map_err(|e| SaveError::Spawn(e.to_string()))
+        .and_then(|_| {
+            let players = entity_map
+                .values()
+                .filter(|x| world.get::<Player>(**x).is_some())
+                .collect::<Vec<&Entity>>();
+            match players[..] {
+                [player] => Ok(*player),
+                _ => Err(SaveError::PlayerCount(players.len())),
+            }
+        });
+    if result.is_err() {
+        for entity in entity_map.values() {
+            world.despawn(*entity);
+        }
+    }
+    result
+}
+
+/// "2024-09-01 18:30 UTC"
+pub fn format_timestamp(secs: u64) -> String {
+    // Days since the epoch to a civil date, after Howard Hinnant's
+    // days_from_civil inverse.
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let time = secs % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        time / 3600,
+        time % 3600 / 60
+    )
+}
+
+/// "1h 05m"
+pub fn format_play_time(secs: f64) -> String {
+    let minutes = (secs / 60.) as u64;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Serializes every unit, item and spell in the world, along with their
+/// children, into scene RON stamped with the save version.
+pub fn serialize_scene(world: &mut World) -> String {
+    let mut units = world.query_filtered::<Entity, Or<(With<Unit>, With<Item>, With<Spell>)>>();
+    let parents = units.iter(world).collect::<Vec<_>>();
+    serialize_entities(world, parents)
+}
+
+/// Serializes one character and their items, spells and features, for a
+/// party member saved on their own.
+pub fn serialize_character(world: &mut World, unit: Entity) -> String {
+    serialize_entities(world, vec![unit])
+}
+
+fn serialize_entities(world: &mut World, parents: Vec<Entity>) -> String {
+    let children = parents
+        .iter()
+        .filter_map(|x| world.get::<Children>(*x))
+        .flat_map(|x| x.iter().copied())
+        .collect::<Vec<_>>();
+    let builder = DynamicSceneBuilder::from_world(world)
+        .extract_entities(parents.into_iter())
+        .extract_entities(children.into_iter());
+    let mut scene = builder.build();
+    scene.resources.push(Box::new(SaveVersion(SAVE_VERSION)));
+    let registry = world.resource::<AppTypeRegistry>();
+    scene.serialize(&registry.read()).unwrap()
+}
+
+fn save_game(world: &mut World, params: &mut SystemState<ResMut<NextState<AppState>>>) {
+    SaveGame.apply(world);
+    {
+        let mut state = params.get_mut(world);
+        state.set(AppState::InGame);
+    }
+    params.apply(world);
+}
+
+/// Saves the game to its slot in the background. Errors show up in
+/// `SaveErrors` once the write finishes. A party saves each member to
+/// their own slot and the party to the game's slot.
+pub struct SaveGame;
+
+impl Command for SaveGame {
+    fn apply(self, world: &mut World) {
+        let dir = world.resource::<SaveDir>().0.clone();
+        let mut players = world.query_filtered::<(), With<Player>>();
+        if players.iter(world).count() > 1 {
+            let slot = world.resource::<SaveSlot>().0.clone();
+            let party = PartySave::from_world(world, slot.as_deref());
+            world.resource_mut::<SaveSlot>().0 = Some(party.slot.clone());
+            let task = IoTaskPool::get().spawn(async move { party.write(&dir) });
+            world.resource_mut::<PendingSaves>().0.push(task);
+            return;
+        }
+        let scene = serialize_scene(world);
+        let metadata = SaveMetadata::from_world(world);
+        let slot = slot_name(
+            world
+                .resource::<SaveSlot>()
+                .0
+                .as_ref()
+                .unwrap_or(&metadata.name),
+        );
+        world.resource_mut::<SaveSlot>().0 = Some(slot.clone());
+        let task = IoTaskPool::get().spawn(async move {
+            // Write the scene RON data and its metadata header to the slot
+            write_slot(&dir, &slot, &scene, &metadata)
+        });
+        world.resource_mut::<PendingSaves>().0.push(task);
+    }
+}
+
+/// Slot writes running on the io task pool.
+#[derive(Resource, Default)]
+pub struct PendingSaves(pub Vec<Task<Result<(), SaveError>>>);
+
+fn poll_pending_saves(mut pending: ResMut<PendingSaves>, mut errors: ResMut<SaveErrors>) {
+    pending
+        .0
+        .retain_mut(|task| match block_on(future::poll_once(task)) {
+            Some(result) => {
+                if let Err(e) = result {
+                    errors.0.push(e);
+                }
+                false
+            }
+            None => true,
+        });
+}
+
+fn save_errors_ui(mut contexts: EguiContexts, mut errors: ResMut<SaveErrors>) {
+    if errors.0.is_empty() {
+        return;
+    }
+    egui::Window::new("Save error")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for error in &errors.0 {
+                ui.colored_label(egui::Color32::LIGHT_RED, error.to_string());
+            }
+            if ui.button("Dismiss").clicked() {
+                errors.0.clear();
+            }
+        });
+}
+
+fn tick_play_time(time: Res<Time>, mut players: Query<&mut PlayTime, With<Player>>) {
+    for mut play_time in &mut players {
+        play_time.0 += time.delta_seconds_f64();
+    }
+}
+
+/// An empty directory for a test to write into, left from any earlier run.
+#[cfg(test)]
+pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("newtable-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(name: &str, timestamp: u64) -> SaveMetadata {
+        SaveMetadata {
+            name: name.into(),
+            race: "Hill Dwarf".into(),
+            class: Class::Fighter,
+            level: 3,
+            timestamp,
+            play_time: 3900.,
+            version: "0.1.0".into(),
+            schema: 2,
+        }
+    }
+
+    #[test]
+    fn slot_names_are_file_safe() {
+        assert_eq!(slot_name("Thora Ironfist"), "Thora-Ironfist");
+        assert_eq!(slot_name("../etc/passwd"), "---etc-passwd");
+        assert_eq!(slot_name("  "), "unnamed");
+    }
+
+    #[test]
+    fn slots_can_be_listed_renamed_duplicated_and_deleted() {
+        let dir = scratch_dir("slots");
+        write_slot(&dir, "thora", "()", &metadata("Thora", 20)).unwrap();
+        write_slot(&dir, "brom", "()", &metadata("Brom", 10)).unwrap();
+        fs::write(scene_path(&dir, "old"), "()").unwrap();
+
+        let slots = list_slots(&dir).unwrap();
+        let names = slots.iter().map(|x| x.slot.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["thora", "brom", "old"]);
+        assert_eq!(slots[0].metadata, Some(metadata("Thora", 20)));
+        assert_eq!(slots[2].metadata, None);
+
+        assert_eq!(duplicate_slot(&dir, "thora").unwrap(), "thora-copy");
+        assert_eq!(duplicate_slot(&dir, "thora").unwrap(), "thora-copy-2");
+        assert_eq!(
+            read_metadata(&dir, "thora-copy"),
+            Some(metadata("Thora", 20))
+        );
+
+        assert!(matches!(
+            rename_slot(&dir, "thora-copy", "brom"),
+            Err(SaveError::SlotExists(_))
+        ));
+        assert_eq!(
+            rename_slot(&dir, "thora-copy", "Thora 2").unwrap(),
+            "Thora-2"
+        );
+        assert!(!scene_path(&dir, "thora-copy").exists());
+        assert_eq!(read_metadata(&dir, "Thora-2"), Some(metadata("Thora", 20)));
+
+        delete_slot(&dir, "Thora-2").unwrap();
+        delete_slot(&dir, "old").unwrap();
+        let names = list_slots(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.slot)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["thora", "thora-copy-2", "brom"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_save_directories_are_created() {
+        let dir = scratch_dir("missing").join("saves");
+        assert_eq!(list_slots(&dir).unwrap().len(), 0);
+        write_slot(&dir.join("nested"), "brom", "()", &metadata("Brom", 10)).unwrap();
+        assert!(scene_path(&dir.join("nested"), "brom").exists());
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn writes_keep_backups_that_can_be_restored() {
+        let dir = scratch_dir("backups");
+        for i in 1..=5 {
+            write_slot(&dir, "brom", &format!("({i})"), &metadata("Brom", i)).unwrap();
+        }
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap();
+        let backups = backup_dir(&dir);
+        assert_eq!(read(scene_path(&dir, "brom")), "(5)");
+        assert_eq!(read(scene_path(&backups, "brom.1")), "(4)");
+        assert_eq!(read(scene_path(&backups, "brom.3")), "(2)");
+        assert!(!scene_path(&backups, "brom.4").exists());
+        assert_eq!(read_metadata(&backups, "brom.1"), Some(metadata("Brom", 4)));
+        // Only finished files are left behind, and backups aren't slots.
+        let names = list_slots(&dir).unwrap().into_iter().map(|x| x.slot);
+        assert_eq!(names.collect::<Vec<_>>(), ["brom"]);
+        assert!(fs::read_dir(&dir).unwrap().all(|x| !x
+            .unwrap()
+            .path()
+            .to_string_lossy()
+            .ends_with(".tmp")));
+
+        // A half written save from before atomic writes.
+        fs::write(scene_path(&dir, "brom"), "(resources: {}, entit").unwrap();
+        assert_eq!(latest_backup(&dir, "brom").as_deref(), Some("brom.1"));
+        restore_backup(&dir, "brom").unwrap();
+        assert_eq!(read(scene_path(&dir, "brom")), "(4)");
+        assert_eq!(read_metadata(&dir, "brom"), Some(metadata("Brom", 4)));
+
+        rename_slot(&dir, "brom", "thora").unwrap();
+        assert_eq!(latest_backup(&dir, "brom"), None);
+        assert_eq!(read(scene_path(&backups, "thora.2")), "(3)");
+        delete_slot(&dir, "thora").unwrap();
+        assert_eq!(latest_backup(&dir, "thora"), None);
+        assert!(matches!(
+            restore_backup(&dir, "thora"),
+            Err(SaveError::NoBackup(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saves_to_the_same_slot_take_turns() {
+        let dir = scratch_dir("concurrent");
+        let writers = (0..2)
+            .map(|writer| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    for i in 0..10 {
+                        let ts = writer * 100 + i;
+                        write_slot(&dir, "brom", &format!("({ts})"), &metadata("Brom", ts))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        // The slot and every backup hold one whole save, with the header
+        // written alongside it.
+        let backups = backup_dir(&dir);
+        let mut saves = vec![(dir.clone(), "brom".to_string())];
+        saves.extend((1..=BACKUPS).map(|n| (backups.clone(), backup_slot("brom", n))));
+        let mut seen = Vec::new();
+        for (dir, slot) in &saves {
+            let scene = fs::read_to_string(scene_path(dir, slot)).unwrap();
+            let ts = read_metadata(dir, slot).unwrap().timestamp;
+            assert_eq!(scene, format!("({ts})"));
+            assert!(!seen.contains(&ts));
+            seen.push(ts);
+        }
+        for dir in [&dir, &backups] {
+            assert!(fs::read_dir(dir).unwrap().all(|x| !x
+                .unwrap()
+                .path()
+                .to_string_lossy()
+                .ends_with(".tmp")));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_saves_are_corrupt() {
+        let mut world = world();
+        let error = spawn_scene(&mut world, "(resources: {}, entit").unwrap_err();
+        assert!(error.is_corrupt());
+        let newer = V1.replace(
+            "resources: {}",
+            "resources: {\"newtable::migrations::SaveVersion\": (99)}",
+        );
+        assert!(!spawn_scene(&mut world, &newer).unwrap_err().is_corrupt());
+    }
+
+    const V1: &str = include_str!("../tests/fixtures/saves/v1.scn.ron");
+
+    fn world() -> World {
+        let mut app = App::new();
+        app.register_type::<ComponentRegistry>();
+        app.register_type::<SaveVersion>();
+        std::mem::take(app.world_mut())
+    }
+
+    fn player_count(world: &mut World) -> usize {
+        world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .count()
+    }
+
+    #[test]
+    fn saves_spawn_only_with_exactly_one_player() {
+        let mut world = world();
+        let player = spawn_scene(&mut world, V1).unwrap();
+        assert_eq!(world.get::<UnitName>(player).unwrap().0, "Brom");
+
+        spawn_scene(&mut world, V1).unwrap();
+        let party = serialize_scene(&mut world);
+        let mut world = self::world();
+        assert!(matches!(
+            spawn_scene(&mut world, &party),
+            Err(SaveError::PlayerCount(2))
+        ));
+        assert_eq!(player_count(&mut world), 0);
+        assert_eq!(world.entities().len(), 0);
+
+        assert!(matches!(
+            spawn_scene(&mut world, "(resources: {}, entities: {})"),
+            Err(SaveError::PlayerCount(0))
+        ));
+        assert!(matches!(
+            spawn_scene(&mut world, "not a save"),
+            Err(SaveError::Version(_))
+        ));
+    }
+
+    #[test]
+    fn timestamps_and_play_time_are_readable() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(1_725_215_400), "2024-09-01 18:30 UTC");
+        assert_eq!(format_play_time(3900.), "1h 05m");
+    }
+}
@@ -0,0 +1,128 @@
+use crate::character::BasePlayer;
+use crate::components::*;
+use crate::items::{EquipItem, ItemsEnum};
+use crate::RulesRng;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// A compact description of units and the items they carry, for setting up
+/// tests and simulations without going through character creation:
+///
+/// ```ron
+/// (
+///     seed: 7,
+///     units: [
+///         (name: "Brom", side: Player, level: 3, max_health: 28, items: [RingOfHealth]),
+///         (name: "Goblin", max_health: 7, ac: 15),
+///     ],
+/// )
+/// ```
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Scenario {
+    /// Seeds `RulesRng`, so every run of the scenario rolls the same.
+    pub seed: u64,
+    pub units: Vec<UnitSpec>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+pub enum Side {
+    Player,
+    #[default]
+    Enemy,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct UnitSpec {
+    pub name: String,
+    pub side: Side,
+    pub level: i64,
+    /// Ability scores in `StatEnum::ABILITIES` order, written as a tuple:
+    /// `(15, 14, 12, 8, 13, 10)`.
+    pub abilities: [f64; 6],
+    pub max_health: f64,
+    pub ac: f64,
+    pub speed: f64,
+    /// Spawned and equipped once the unit is.
+    pub items: Vec<ItemsEnum>,
+}
+
+impl Default for UnitSpec {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            side: Side::Enemy,
+            level: 1,
+            abilities: [10.; 6],
+            max_health: 10.,
+            ac: 10.,
+            speed: 30.,
+            items: vec![],
+        }
+    }
+}
+
+impl UnitSpec {
+    fn sheet(&self) -> BasePlayer {
+        let [strength, constitution, dexterity, intelligence, wisdom, charisma] = self.abilities;
+        BasePlayer {
+            name: self.name.clone(),
+            ac: self.ac,
+            speed: self.speed,
+            strength,
+            constitution,
+            dexterity,
+            intelligence,
+            wisdom,
+            charisma,
+            max_health: self.max_health,
+            level: self.level,
+            ..default()
+        }
+    }
+
+    /// Spawns the unit and sends an `EquipItem` for each of its items, which
+    /// lands on the next update.
+    pub fn spawn(&self, world: &mut World) -> Entity {
+        let player = self.sheet().to_bundle();
+        let unit = match self.side {
+            Side::Player => world.spawn(player).id(),
+            Side::Enemy => world
+                .spawn(EnemyBundle {
+                    unit_tag: Unit,
+                    enemy_tag: Enemy,
+                    name: player.name,
+                    ac: player.ac,
+                    speed: player.speed,
+                    abilities: player.abilities,
+                    skills: player.skills,
+                    wep_profs: player.wep_profs,
+                    prof_bonus: player.prof_bonus,
+                    health: player.health,
+                    max_health: player.max_health,
+                    alignment: player.alignment,
+                    hit_dice: player.hit_dice,
+                })
+                .id(),
+        };
+        for item in &self.items {
+            let item = item.spawn_id(&mut world.commands());
+            world.flush();
+            world.send_event(EquipItem { unit, item });
+        }
+        unit
+    }
+}
+
+impl Scenario {
+    pub fn from_ron(data: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(data)
+    }
+
+    /// Seeds the rules and spawns every unit, in order.
+    pub fn spawn(&self, world: &mut World) -> Vec<Entity> {
+        world.insert_resource(RulesRng::seeded(self.seed));
+        self.units.iter().map(|x| x.spawn(world)).collect()
+    }
+}
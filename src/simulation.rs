@@ -0,0 +1,248 @@
+//! Runs an encounter headlessly many times over, through the same attack,
+//! damage and initiative observers the game uses, to see how it tends to go.
+
+use crate::combat::{Attack, Downed, EndTurn, StartCombat, TurnOrder};
+use crate::components::*;
+use crate::items::{ArmWeapons, WeaponCatalog, UNARMED_STRIKE};
+use crate::monsters::{MonsterCatalog, MonsterData};
+use crate::saves::{spawn_scene, SaveError};
+use crate::{RulesPlugin, RulesRng};
+use bevy::{ecs::world::Command, prelude::*};
+use std::fmt;
+
+/// A fight still going after this many rounds counts as a draw.
+pub const MAX_ROUNDS: u32 = 100;
+
+#[derive(Debug)]
+pub enum SimulationError {
+    /// A party member's save couldn't be spawned.
+    Save(usize, SaveError),
+    UnknownMonster(String),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulationError::Save(i, e) => write!(f, "party member {}: {e}", i + 1),
+            SimulationError::UnknownMonster(name) => write!(f, "no monster called {name}"),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct CharacterStats {
+    pub name: String,
+    pub downed: u32,
+    pub health_left: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub runs: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub rounds: u32,
+    pub health_left: f64,
+    pub characters: Vec<CharacterStats>,
+}
+
+impl SimulationReport {
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.runs.max(1) as f64
+    }
+
+    pub fn average_rounds(&self) -> f64 {
+        self.rounds as f64 / self.runs.max(1) as f64
+    }
+
+    /// Summed over the party, counting downed members as 0.
+    pub fn average_health_left(&self) -> f64 {
+        self.health_left / self.runs.max(1) as f64
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let runs = self.runs.max(1) as f64;
+        writeln!(f, "Runs:           {}", self.runs)?;
+        writeln!(
+            f,
+            "Wins:           {} ({:.1}%), {} losses, {} draws",
+            self.wins,
+            self.win_rate() * 100.,
+            self.losses,
+            self.draws
+        )?;
+        writeln!(f, "Average rounds: {:.2}", self.average_rounds())?;
+        writeln!(f, "Average HP:     {:.1}", self.average_health_left())?;
+        for character in &self.characters {
+            writeln!(
+                f,
+                "  {:<20} downed {:.1}%, {:.1} HP left",
+                character.name,
+                character.downed as f64 / runs * 100.,
+                character.health_left / runs,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// Fights `monsters` with the party saved in `party` (scene texts) `runs`
+/// times. Every unit attacks the living opponent with the least health using
+/// its first weapon.
+pub fn simulate(
+    party: &[String],
+    monsters: &[String],
+    runs: u32,
+    seed: u64,
+) -> Result<SimulationReport, SimulationError> {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, RulesPlugin));
+    app.insert_resource(RulesRng::seeded(seed));
+    let world = app.world_mut();
+    let monsters = monsters
+        .iter()
+        .map(|name| {
+            world
+                .resource::<MonsterCatalog>()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SimulationError::UnknownMonster(name.clone()))
+        })
+        .collect::<Result<Vec<MonsterData>, SimulationError>>()?;
+
+    let mut report = SimulationReport::default();
+    for _ in 0..runs {
+        let players = spawn_encounter(world, party, &monsters)?;
+        if report.characters.is_empty() {
+            report.characters = players
+                .iter()
+                .map(|x| CharacterStats {
+                    name: world
+                        .get::<UnitName>(*x)
+                        .map(|x| x.0.clone())
+                        .unwrap_or_default(),
+                    ..default()
+                })
+                .collect();
+        }
+        match fight(world) {
+            Outcome::Win => report.wins += 1,
+            Outcome::Loss => report.losses += 1,
+            Outcome::Draw => report.draws += 1,
+        }
+        report.runs += 1;
+        report.rounds += world.resource::<TurnOrder>().round;
+        for (player, stats) in players.iter().zip(report.characters.iter_mut()) {
+            let health = world.get::<Health>(*player).map_or(0., |x| x.0.max(0.));
+            if world.get::<Downed>(*player).is_some() {
+                stats.downed += 1;
+            }
+            stats.health_left += health;
+            report.health_left += health;
+        }
+    }
+    Ok(report)
+}
+
+/// Clears out the last fight and spawns a fresh one, returning the players.
+fn spawn_encounter(
+    world: &mut World,
+    party: &[String],
+    monsters: &[MonsterData],
+) -> Result<Vec<Entity>, SimulationError> {
+    let units = world
+        .query_filtered::<Entity, With<Unit>>()
+        .iter(world)
+        .collect::<Vec<Entity>>();
+    for unit in units {
+        world.entity_mut(unit).despawn_recursive();
+    }
+    let mut players = vec![];
+    for (i, text) in party.iter().enumerate() {
+        let player = spawn_scene(world, text).map_err(|e| SimulationError::Save(i, e))?;
+        ArmWeapons(player).apply(world);
+        let max_health = world.get::<MaxHealth>(player).map_or(1., |x| x.0.total);
+        world
+            .entity_mut(player)
+            .insert(Health(max_health))
+            .remove::<Downed>();
+        players.push(player);
+    }
+    for monster in monsters {
+        monster.spawn(world);
+    }
+    Ok(players)
+}
+
+fn fight(world: &mut World) -> Outcome {
+    world.trigger(StartCombat);
+    world.flush();
+    loop {
+        let enemies = world
+            .query_filtered::<(), With<Enemy>>()
+            .iter(world)
+            .count();
+        let players = world
+            .query_filtered::<(), (With<Player>, Without<Downed>)>()
+            .iter(world)
+            .count();
+        let turn_order = world.resource::<TurnOrder>();
+        match (enemies, players) {
+            (0, _) => return Outcome::Win,
+            (_, 0) => return Outcome::Loss,
+            _ if turn_order.round > MAX_ROUNDS => return Outcome::Draw,
+            _ => {}
+        }
+        if let Some(unit) = turn_order.active() {
+            take_turn(world, unit);
+        }
+        world.trigger(EndTurn);
+        world.flush();
+    }
+}
+
+/// Attacks the opponent with the least health left.
+pub fn take_turn(world: &mut World, unit: Entity) {
+    let is_player = world.get::<Player>(unit).is_some();
+    let target = world
+        .query_filtered::<(Entity, &Health, Has<Player>), (With<Unit>, Without<Downed>)>()
+        .iter(world)
+        .filter(|x| x.2 != is_player)
+        .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+        .map(|x| x.0);
+    let Some(target) = target else {
+        return;
+    };
+    let weapon = world
+        .get::<Children>(unit)
+        .and_then(|x| x.iter().find(|x| world.get::<Weapon>(**x).is_some()))
+        .copied();
+    let weapon = match weapon {
+        Some(weapon) => weapon,
+        None => {
+            let Some(data) = world
+                .resource::<WeaponCatalog>()
+                .get(UNARMED_STRIKE)
+                .cloned()
+            else {
+                return;
+            };
+            let weapon = data.spawn(world);
+            world.entity_mut(unit).add_child(weapon);
+            weapon
+        }
+    };
+    world.trigger(Attack::new(unit, weapon, target));
+    world.flush();
+}
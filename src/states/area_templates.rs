@@ -0,0 +1,289 @@
+use crate::areas::{AreaEffect, Shape, Template};
+use crate::components::*;
+use crate::map::{BattleMap, GridPosition};
+use crate::states::battle_map::{cell_center, world_point, MapCursor, MapView, CELL_PIXELS};
+use crate::AppState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::PI;
+use strum::IntoEnumIterator;
+
+pub struct AreaTemplatePlugin;
+
+impl Plugin for AreaTemplatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TemplateDraft>();
+        app.add_systems(
+            Update,
+            (template_ui, place_template, draw_template)
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// A shape of each kind, at the size of a typical spell.
+const SHAPES: [Shape; 5] = [
+    Shape::Sphere { radius: 20. },
+    Shape::Cylinder { radius: 10. },
+    Shape::Cone { length: 15. },
+    Shape::Line {
+        length: 100.,
+        width: 5.,
+    },
+    Shape::Cube { size: 10. },
+];
+
+/// How far Q and E turn a template.
+const TURN: f32 = PI / 12.;
+
+/// The template being laid on the map and the effect it resolves.
+#[derive(Resource, Clone, PartialEq)]
+pub struct TemplateDraft {
+    pub template: Option<Template>,
+    /// Whether the template follows the mouse until a click puts it down.
+    pub placing: bool,
+    pub shape: Shape,
+    pub save: StatEnum,
+    pub dc: i64,
+    pub damage: Dice,
+    pub damage_type: DamageType,
+    pub half_on_save: bool,
+}
+
+impl Default for TemplateDraft {
+    fn default() -> Self {
+        // A fireball.
+        Self {
+            template: None,
+            placing: false,
+            shape: SHAPES[0],
+            save: StatEnum::Dexterity,
+            dc: 13,
+            damage: Dice {
+                dice_type: DiceType::D6,
+                number: 8,
+            },
+            damage_type: DamageType::Fire,
+            half_on_save: true,
+        }
+    }
+}
+
+/// Whether clicks on the map are putting down a template.
+pub fn placing_template(draft: Res<TemplateDraft>) -> bool {
+    draft.placing
+}
+
+/// Marks the squares a template covers.
+#[derive(Component)]
+struct TemplatePreview;
+
+fn feet(ui: &mut egui::Ui, label: &str, value: &mut f64) {
+    ui.label(label);
+    ui.add(
+        egui::DragValue::new(value)
+            .speed(5.)
+            .range(5..=500)
+            .suffix(" ft"),
+    );
+}
+
+fn template_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut draft: ResMut<TemplateDraft>,
+    map: Res<BattleMap>,
+    units: Query<(Entity, &GridPosition, &UnitName), With<Unit>>,
+) {
+    let mut changed = draft.clone();
+    egui::Window::new("Area effect")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for shape in SHAPES {
+                    if ui
+                        .selectable_label(changed.shape.name() == shape.name(), shape.name())
+                        .clicked()
+                        && changed.shape.name() != shape.name()
+                    {
+                        changed.shape = shape;
+                    }
+                }
+            });
+            ui.horizontal(|ui| match &mut changed.shape {
+                Shape::Sphere { radius } | Shape::Cylinder { radius } => feet(ui, "Radius", radius),
+                Shape::Cone { length } => feet(ui, "Length", length),
+                Shape::Line { length, width } => {
+                    feet(ui, "Length", length);
+                    feet(ui, "Width", width);
+                }
+                Shape::Cube { size } => feet(ui, "Size", size),
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("save")
+                    .selected_text(format!("{:?}", changed.save))
+                    .show_ui(ui, |ui| {
+                        for ability in StatEnum::ABILITIES {
+                            let name = format!("{ability:?}");
+                            ui.selectable_value(&mut changed.save, ability, name);
+                        }
+                    });
+                ui.label("DC");
+                ui.add(egui::DragValue::new(&mut changed.dc).range(1..=30));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Damage");
+                ui.add(egui::DragValue::new(&mut changed.damage.number).range(1..=40));
+                egui::ComboBox::from_id_source("damage dice")
+                    .selected_text(changed.damage.dice_type.to_string())
+                    .show_ui(ui, |ui| {
+                        for dice in DiceType::iter() {
+                            let name = dice.to_string();
+                            ui.selectable_value(&mut changed.damage.dice_type, dice, name);
+                        }
+                    });
+                egui::ComboBox::from_id_source("damage type")
+                    .selected_text(changed.damage_type.to_string())
+                    .show_ui(ui, |ui| {
+                        for damage_type in DamageType::iter() {
+                            let name = damage_type.to_string();
+                            ui.selectable_value(&mut changed.damage_type, damage_type, name);
+                        }
+                    });
+                ui.checkbox(&mut changed.half_on_save, "Half on a save");
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let label = match changed.placing {
+                    true => "Click the map",
+                    false => "Place",
+                };
+                if ui.button(label).clicked() {
+                    changed.placing = !changed.placing;
+                }
+                if changed.template.is_some() && ui.button("Clear").clicked() {
+                    changed.template = None;
+                    changed.placing = false;
+                }
+            });
+            let Some(template) = changed.template else {
+                return;
+            };
+            if template.shape.has_facing() {
+                ui.label(format!(
+                    "Facing {:.0}°, Q and E to turn",
+                    template.facing.to_degrees()
+                ));
+            }
+            let caught = template.units(&map, units.iter().map(|x| (x.0, x.1)));
+            let names = units
+                .iter_many(&caught)
+                .map(|x| x.2 .0.clone())
+                .collect::<Vec<_>>();
+            ui.label(match names.is_empty() {
+                true => "Nobody caught".to_string(),
+                false => format!("Caught: {}", names.join(", ")),
+            });
+            let resolve = ui.add_enabled(!changed.placing, egui::Button::new("Resolve"));
+            if resolve.clicked() {
+                commands.trigger(AreaEffect {
+                    template,
+                    save: changed.save.clone(),
+                    dc: changed.dc,
+                    damage: changed.damage.clone(),
+                    damage_type: changed.damage_type,
+                    half_on_save: changed.half_on_save,
+                });
+                changed.template = None;
+            }
+        });
+    if let Some(template) = &mut changed.template {
+        template.shape = changed.shape;
+    }
+    if changed != *draft {
+        *draft = changed;
+    }
+}
+
+/// The template follows the mouse, snapped to where squares meet, until a
+/// click puts it down. Q and E turn it.
+fn place_template(
+    mut draft: ResMut<TemplateDraft>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    cursor: Res<MapCursor>,
+) {
+    let turn = match (
+        keys.just_pressed(KeyCode::KeyQ),
+        keys.just_pressed(KeyCode::KeyE),
+    ) {
+        (true, false) => TURN,
+        (false, true) => -TURN,
+        _ => 0.,
+    };
+    if turn != 0. {
+        if let Some(template) = &mut draft.template {
+            template.rotate(turn);
+        }
+    }
+    if !draft.placing || cursor.over_ui {
+        return;
+    }
+    let shape = draft.shape;
+    // Lines and cubes can also start halfway along the side of a square.
+    let Some(origin) = cursor.point.map(|x| match shape.has_facing() {
+        true => (x * 2.).round() / 2.,
+        false => x.round(),
+    }) else {
+        return;
+    };
+    match &mut draft.template {
+        Some(template) if template.origin == origin => {}
+        Some(template) => template.origin = origin,
+        None => draft.template = Some(Template::new(shape, origin)),
+    }
+    if mouse.just_pressed(MouseButton::Left) {
+        draft.placing = false;
+        // The click is spent, so it doesn't also pick a token.
+        mouse.clear_just_pressed(MouseButton::Left);
+    }
+}
+
+fn draw_template(
+    mut commands: Commands,
+    draft: Res<TemplateDraft>,
+    map: Res<BattleMap>,
+    previews: Query<Entity, With<TemplatePreview>>,
+) {
+    if !draft.is_changed() && !map.is_changed() {
+        return;
+    }
+    for preview in &previews {
+        commands.entity(preview).despawn();
+    }
+    let Some(template) = draft.template else {
+        return;
+    };
+    let color = Color::Srgba(Srgba::rgb(1., 0.5, 0.));
+    let mut square = |at: Vec2, size: f32, alpha: f32| {
+        commands.spawn((
+            MapView,
+            TemplatePreview,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: color.with_alpha(alpha),
+                    custom_size: Some(Vec2::splat(size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(at.extend(0.4)),
+                ..default()
+            },
+        ));
+    };
+    for pos in template.cells(&map) {
+        square(cell_center(pos, &map), CELL_PIXELS, 0.35);
+    }
+    square(world_point(template.origin, &map), CELL_PIXELS * 0.15, 1.);
+}
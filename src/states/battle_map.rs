@@ -0,0 +1,660 @@
+use crate::combat::InGameState;
+use crate::components::*;
+use crate::map::{BattleMap, DiagonalRule, Door, GridPosition, ToggleDoor};
+use crate::movement::{find_path, MoveUnit, Movement, OpportunityAttacks, Path};
+use crate::states::area_templates::placing_template;
+use crate::vision::{FogOfWar, LightLevel, LightSource};
+use crate::AppState;
+use bevy::prelude::*;
+use bevy::sprite::{Anchor, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashSet;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+
+/// How big a grid square is drawn.
+pub const CELL_PIXELS: f32 = 64.;
+
+const TOKEN_RADIUS: f32 = CELL_PIXELS * 0.4;
+const BAR_WIDTH: f32 = CELL_PIXELS * 0.8;
+
+pub struct BattleMapPlugin;
+
+impl Plugin for BattleMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>();
+        app.init_resource::<MapCursor>();
+        app.add_systems(OnExit(AppState::InGame), despawn_map);
+        // The editor shows the whole map.
+        app.add_systems(OnEnter(InGameState::MapEditor), clear_fog);
+        app.add_systems(OnExit(InGameState::MapEditor), redraw_fog);
+        app.add_systems(
+            Update,
+            (
+                draw_grid,
+                spawn_tokens,
+                track_cursor,
+                select_and_drag
+                    .run_if(not(placing_template))
+                    .run_if(not(in_state(InGameState::MapEditor))),
+                open_doors.run_if(not(in_state(InGameState::MapEditor))),
+                preview_path,
+                sync_tokens,
+                draw_fog.run_if(not(in_state(InGameState::MapEditor))),
+            )
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+        app.add_systems(
+            Update,
+            (map_ui, opportunity_attack_prompt).run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Everything the battle map draws, cleared when leaving the game.
+#[derive(Component)]
+pub struct MapView;
+
+/// Draws the unit it points to.
+#[derive(Component)]
+pub struct Token(pub Entity);
+
+#[derive(Component)]
+struct HealthBar;
+
+/// The grid, walls and terrain, redrawn when the map changes.
+#[derive(Component)]
+struct GridView;
+
+/// Covers a square the party can't see.
+#[derive(Component)]
+struct FogView;
+
+/// Marks the path a dragged token would take.
+#[derive(Component)]
+struct PathPreview;
+
+/// The unit picked on the map, and the square its token is being dragged
+/// over, if it is.
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub unit: Option<Entity>,
+    pub dragging: bool,
+    pub drag_to: Option<GridPosition>,
+    /// The path to `drag_to`, if there is one.
+    pub preview: Option<Path>,
+}
+
+/// The square under the mouse, unless it's over a window.
+#[derive(Resource, Default)]
+pub struct MapCursor {
+    pub cell: Option<GridPosition>,
+    /// Where the mouse is, in squares.
+    pub point: Option<Vec2>,
+    pub over_ui: bool,
+}
+
+/// The middle of a square, with the map centred on the origin.
+pub fn cell_center(pos: GridPosition, map: &BattleMap) -> Vec2 {
+    Vec2::new(
+        (pos.x as f32 - (map.width - 1) as f32 / 2.) * CELL_PIXELS,
+        (pos.y as f32 - (map.height - 1) as f32 / 2.) * CELL_PIXELS,
+    )
+}
+
+/// Converts a point in squares, as `GridPosition` counts them, to where it's
+/// drawn.
+pub fn world_point(point: Vec2, map: &BattleMap) -> Vec2 {
+    (point - Vec2::new(map.width as f32, map.height as f32) / 2.) * CELL_PIXELS
+}
+
+/// Converts a point where it's drawn to squares.
+pub fn map_point(point: Vec2, map: &BattleMap) -> Vec2 {
+    point / CELL_PIXELS + Vec2::new(map.width as f32, map.height as f32) / 2.
+}
+
+/// The square under a point, if it's on the map.
+pub fn cell_at(point: Vec2, map: &BattleMap) -> Option<GridPosition> {
+    let point = map_point(point, map).floor();
+    let pos = GridPosition::new(point.x as i32, point.y as i32);
+    map.contains(pos).then_some(pos)
+}
+
+fn draw_grid(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    map: Res<BattleMap>,
+    grid: Query<Entity, With<GridView>>,
+) {
+    if !map.is_changed() && !grid.is_empty() {
+        return;
+    }
+    for entity in &grid {
+        commands.entity(entity).despawn();
+    }
+    if let Some(background) = &map.background {
+        let scale = CELL_PIXELS / background.pixels_per_square;
+        let (x, y) = background.offset;
+        let corner = world_point(Vec2::new(0., map.height as f32), &map);
+        let at = corner + Vec2::new(-x, y) * scale;
+        commands.spawn((
+            MapView,
+            GridView,
+            SpriteBundle {
+                texture: asset_server.load(&background.image),
+                sprite: Sprite {
+                    anchor: Anchor::TopLeft,
+                    ..default()
+                },
+                transform: Transform::from_translation(at.extend(0.01))
+                    .with_scale(Vec3::splat(scale)),
+                ..default()
+            },
+        ));
+    }
+    let size = Vec2::new(map.width as f32, map.height as f32) * CELL_PIXELS;
+    let mut square = |color: Color, at: Vec2, size: Vec2, z: f32| {
+        commands.spawn((
+            MapView,
+            GridView,
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(at.extend(z)),
+                ..default()
+            },
+        ));
+    };
+    if map.background.is_none() {
+        square(Color::srgb(0.18, 0.24, 0.16), Vec2::ZERO, size, 0.);
+    }
+    let mut cell = |color: Color, pos: GridPosition, z: f32| {
+        square(color, cell_center(pos, &map), Vec2::splat(CELL_PIXELS), z);
+    };
+    for (pos, light) in &map.lit {
+        let color = match light {
+            LightLevel::Bright => Color::srgba(1., 0.9, 0.5, 0.12),
+            LightLevel::Dim => Color::srgba(0., 0., 0., 0.2),
+            LightLevel::Dark => Color::srgba(0., 0., 0., 0.45),
+        };
+        cell(color, *pos, 0.04);
+    }
+    for pos in &map.difficult {
+        cell(Color::srgba(0.45, 0.3, 0.1, 0.6), *pos, 0.05);
+    }
+    for pos in &map.hazards {
+        cell(Color::srgba(0.8, 0.1, 0.1, 0.45), *pos, 0.05);
+    }
+    for pos in &map.walls {
+        cell(Color::srgb(0.25, 0.25, 0.25), *pos, 0.06);
+    }
+    for (pos, door) in &map.doors {
+        let color = match door {
+            Door::Open => Color::srgba(0.55, 0.35, 0.15, 0.35),
+            Door::Closed => Color::srgb(0.55, 0.35, 0.15),
+            Door::Locked => Color::srgb(0.4, 0.15, 0.1),
+        };
+        cell(color, *pos, 0.06);
+    }
+    for pos in map.lights.keys() {
+        let at = cell_center(*pos, &map);
+        square(
+            Color::srgb(1., 0.85, 0.3),
+            at,
+            Vec2::splat(CELL_PIXELS * 0.2),
+            0.07,
+        );
+    }
+    let line = Color::srgba(0., 0., 0., 0.5);
+    for x in 0..=map.width {
+        let at = Vec2::new(x as f32 * CELL_PIXELS - size.x / 2., 0.);
+        square(line, at, Vec2::new(1., size.y), 0.1);
+    }
+    for y in 0..=map.height {
+        let at = Vec2::new(0., y as f32 * CELL_PIXELS - size.y / 2.);
+        square(line, at, Vec2::new(size.x, 1.), 0.1);
+    }
+}
+
+fn despawn_map(
+    mut commands: Commands,
+    mut selection: ResMut<Selection>,
+    views: Query<Entity, With<MapView>>,
+) {
+    for view in &views {
+        commands.entity(view).despawn_recursive();
+    }
+    *selection = Selection::default();
+}
+
+fn spawn_tokens(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    units: Query<(Entity, &UnitName, Has<Player>), With<GridPosition>>,
+    tokens: Query<&Token>,
+) {
+    let drawn = tokens.iter().map(|x| x.0).collect::<HashSet<Entity>>();
+    for (unit, name, player) in &units {
+        if drawn.contains(&unit) {
+            continue;
+        }
+        let color = match player {
+            true => Color::Srgba(Srgba::BLUE),
+            false => Color::Srgba(Srgba::RED),
+        };
+        let bar = |color: Color, z: f32| SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(BAR_WIDTH, 6.)),
+                anchor: Anchor::CenterLeft,
+                ..default()
+            },
+            transform: Transform::from_xyz(-BAR_WIDTH / 2., TOKEN_RADIUS + 6., z),
+            ..default()
+        };
+        commands
+            .spawn((
+                MapView,
+                Token(unit),
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(Circle {
+                        radius: TOKEN_RADIUS,
+                    })),
+                    material: materials.add(color),
+                    transform: Transform::from_xyz(0., 0., 1.),
+                    ..default()
+                },
+            ))
+            .with_children(|token| {
+                token.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        name.0.clone(),
+                        TextStyle {
+                            font_size: 14.,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., -TOKEN_RADIUS - 10., 0.2),
+                    ..default()
+                });
+                token.spawn(bar(Color::BLACK, 0.1));
+                token.spawn((HealthBar, bar(Color::Srgba(Srgba::GREEN), 0.2)));
+            });
+    }
+}
+
+/// Moves tokens onto their units' squares, sizes their health bars and
+/// clears away tokens of units that are gone.
+fn sync_tokens(
+    mut commands: Commands,
+    map: Res<BattleMap>,
+    selection: Res<Selection>,
+    units: Query<(&GridPosition, &Health, &MaxHealth)>,
+    mut tokens: Query<(Entity, &Token, &mut Transform, &Children)>,
+    mut bars: Query<&mut Sprite, With<HealthBar>>,
+) {
+    for (entity, token, mut transform, children) in &mut tokens {
+        let Ok((pos, health, max_health)) = units.get(token.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        let selected = selection.unit == Some(token.0);
+        transform.translation = match selection.drag_to {
+            Some(cell) if selected && selection.dragging => cell_center(cell, &map).extend(2.),
+            _ => cell_center(*pos, &map).extend(1.),
+        };
+        transform.scale = Vec3::splat(if selected { 1.15 } else { 1. });
+        let fraction = (health.0 / max_health.0.total.max(1.)).clamp(0., 1.) as f32;
+        let mut bars = bars.iter_many_mut(children);
+        while let Some(mut bar) = bars.fetch_next() {
+            bar.custom_size = Some(Vec2::new(BAR_WIDTH * fraction, 6.));
+            bar.color = match fraction {
+                x if x > 0.5 => Color::Srgba(Srgba::GREEN),
+                x if x > 0.25 => Color::Srgba(Srgba::rgb(1., 0.8, 0.)),
+                _ => Color::Srgba(Srgba::RED),
+            };
+        }
+    }
+}
+
+fn track_cursor(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<CameraMarker>>,
+    map: Res<BattleMap>,
+    mut contexts: EguiContexts,
+    mut cursor: ResMut<MapCursor>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let point = window
+        .cursor_position()
+        .and_then(|x| camera.viewport_to_world_2d(camera_transform, x));
+    *cursor = MapCursor {
+        cell: point.and_then(|x| cell_at(x, &map)),
+        point: point.map(|x| map_point(x, &map)),
+        over_ui: contexts.ctx_mut().wants_pointer_input(),
+    };
+}
+
+/// Clicking a token selects its unit; dragging it moves the unit to another
+/// square if it can get there.
+fn select_and_drag(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cursor: Res<MapCursor>,
+    mut selection: ResMut<Selection>,
+    positions: Query<(Entity, &GridPosition)>,
+) {
+    let cell = cursor.cell;
+    if mouse.just_pressed(MouseButton::Left) && !cursor.over_ui {
+        selection.unit = cell.and_then(|cell| positions.iter().find(|x| *x.1 == cell).map(|x| x.0));
+        selection.dragging = selection.unit.is_some();
+    }
+    let Some(unit) = selection.unit.filter(|_| selection.dragging) else {
+        return;
+    };
+    if mouse.pressed(MouseButton::Left) {
+        if cell.is_some() && cell != selection.drag_to {
+            selection.drag_to = cell;
+        }
+        return;
+    }
+    if let (Some(to), Some(_)) = (selection.drag_to, &selection.preview) {
+        commands.trigger(MoveUnit { unit, to });
+    }
+    selection.dragging = false;
+    selection.drag_to = None;
+    selection.preview = None;
+}
+
+/// Marks out the path to the square a token is dragged over, with its cost
+/// in red if the unit hasn't the movement left for it.
+fn preview_path(
+    mut commands: Commands,
+    map: Res<BattleMap>,
+    rule: Res<DiagonalRule>,
+    mut selection: ResMut<Selection>,
+    units: Query<(Entity, &GridPosition, Option<&Movement>), With<Unit>>,
+    previews: Query<Entity, With<PathPreview>>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    for preview in &previews {
+        commands.entity(preview).despawn_recursive();
+    }
+    let (Some(unit), Some(to)) = (selection.unit, selection.drag_to) else {
+        selection.preview = None;
+        return;
+    };
+    let Ok((_, from, movement)) = units.get(unit) else {
+        return;
+    };
+    let occupied = units
+        .iter()
+        .filter(|x| x.0 != unit)
+        .map(|x| *x.1)
+        .collect::<HashSet<GridPosition>>();
+    let path = (*from != to)
+        .then(|| find_path(&map, *rule, *from, to, &occupied))
+        .flatten();
+    let Some(path) = path else {
+        selection.bypass_change_detection().preview = None;
+        return;
+    };
+    let color = match movement {
+        Some(movement) if path.cost > movement.0 => Color::Srgba(Srgba::RED),
+        _ => Color::Srgba(Srgba::rgb(1., 1., 0.6)),
+    };
+    for pos in &path.cells {
+        commands.spawn((
+            MapView,
+            PathPreview,
+            SpriteBundle {
+                sprite: Sprite {
+                    color: color.with_alpha(0.6),
+                    custom_size: Some(Vec2::splat(CELL_PIXELS * 0.25)),
+                    ..default()
+                },
+                transform: Transform::from_translation(cell_center(*pos, &map).extend(0.5)),
+                ..default()
+            },
+        ));
+    }
+    commands.spawn((
+        MapView,
+        PathPreview,
+        Text2dBundle {
+            text: Text::from_section(
+                format!("{} ft", path.cost),
+                TextStyle {
+                    font_size: 16.,
+                    color,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_translation(
+                (cell_center(to, &map) + Vec2::new(0., CELL_PIXELS * 0.6)).extend(3.),
+            ),
+            ..default()
+        },
+    ));
+    let path = match movement {
+        Some(movement) if path.cost > movement.0 => None,
+        _ => Some(path),
+    };
+    selection.bypass_change_detection().preview = path;
+}
+
+/// Covers what the selected player, or else the whole party, can't see,
+/// darker where they've never looked, and hides the enemies they can't see.
+fn draw_fog(
+    mut commands: Commands,
+    map: Res<BattleMap>,
+    selection: Res<Selection>,
+    players: Query<(Entity, Ref<FogOfWar>)>,
+    views: Query<Entity, With<FogView>>,
+    enemies: Query<&GridPosition, With<Enemy>>,
+    mut tokens: Query<(&Token, &mut Visibility)>,
+) {
+    let fogs = match selection.unit.and_then(|x| players.get(x).ok()) {
+        Some(fog) => vec![fog.1],
+        None => players.iter().map(|x| x.1).collect(),
+    };
+    if !selection.is_changed() && !map.is_changed() && !fogs.iter().any(|x| x.is_changed()) {
+        return;
+    }
+    for view in &views {
+        commands.entity(view).despawn();
+    }
+    if fogs.is_empty() {
+        return;
+    }
+    let visible = fogs.iter().flat_map(|x| &x.visible).collect::<HashSet<_>>();
+    let explored = fogs
+        .iter()
+        .flat_map(|x| &x.explored)
+        .collect::<HashSet<_>>();
+    for x in 0..map.width {
+        for y in 0..map.height {
+            let pos = GridPosition::new(x, y);
+            if visible.contains(&pos) {
+                continue;
+            }
+            let alpha = match explored.contains(&pos) {
+                true => 0.5,
+                false => 0.9,
+            };
+            commands.spawn((
+                MapView,
+                FogView,
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgba(0., 0., 0., alpha),
+                        custom_size: Some(Vec2::splat(CELL_PIXELS)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(cell_center(pos, &map).extend(0.45)),
+                    ..default()
+                },
+            ));
+        }
+    }
+    for (token, mut visibility) in &mut tokens {
+        let hidden = enemies.get(token.0).is_ok_and(|x| !visible.contains(x));
+        visibility.set_if_neq(match hidden {
+            true => Visibility::Hidden,
+            false => Visibility::Inherited,
+        });
+    }
+}
+
+/// The editor shows the whole map, enemies and all.
+fn clear_fog(
+    mut commands: Commands,
+    views: Query<Entity, With<FogView>>,
+    mut tokens: Query<&mut Visibility, With<Token>>,
+) {
+    for view in &views {
+        commands.entity(view).despawn();
+    }
+    for mut visibility in &mut tokens {
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
+
+fn redraw_fog(mut selection: ResMut<Selection>) {
+    selection.set_changed();
+}
+
+/// Right-clicking a door opens or closes it.
+fn open_doors(
+    mut commands: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cursor: Res<MapCursor>,
+    map: Res<BattleMap>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) || cursor.over_ui {
+        return;
+    }
+    if let Some(cell) = cursor.cell.filter(|x| map.doors.contains_key(x)) {
+        commands.trigger(ToggleDoor(cell));
+    }
+}
+
+/// The diagonal rule, the map's light, and how far the selected unit can
+/// still move and what light it carries.
+fn map_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut rule: ResMut<DiagonalRule>,
+    mut map: ResMut<BattleMap>,
+    selection: Res<Selection>,
+    units: Query<(&UnitName, Option<&Movement>, Option<&LightSource>)>,
+    (state, mut set_state): (Res<State<InGameState>>, ResMut<NextState<InGameState>>),
+) {
+    egui::Window::new("Map")
+        .collapsible(true)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Diagonals:");
+                let mut selected = *rule;
+                ui.radio_value(&mut selected, DiagonalRule::Uniform, "5/5/5");
+                ui.radio_value(&mut selected, DiagonalRule::Alternating, "5/10/5");
+                if selected != *rule {
+                    *rule = selected;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Light:");
+                let mut light = map.light;
+                ui.radio_value(&mut light, LightLevel::Bright, "Bright");
+                ui.radio_value(&mut light, LightLevel::Dim, "Dim");
+                ui.radio_value(&mut light, LightLevel::Dark, "Dark");
+                if light != map.light {
+                    map.light = light;
+                }
+            });
+            let editing = *state.get() == InGameState::MapEditor;
+            if ui
+                .add_enabled(!editing, egui::Button::new("Edit map"))
+                .clicked()
+            {
+                set_state.set(InGameState::MapEditor);
+            }
+            let Some(unit) = selection.unit else {
+                return;
+            };
+            let Ok((name, movement, carried)) = units.get(unit) else {
+                return;
+            };
+            match movement {
+                Some(movement) => {
+                    ui.label(format!("{}: {} ft of movement left", name.0, movement.0))
+                }
+                None => ui.label(name.0.clone()),
+            };
+            let carrying = LightSource::KINDS
+                .iter()
+                .find(|x| Some(&x.1) == carried)
+                .map_or("Nothing", |x| x.0);
+            egui::ComboBox::from_label("carried light")
+                .selected_text(carrying)
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(carried.is_none(), "Nothing").clicked() {
+                        commands.entity(unit).remove::<LightSource>();
+                    }
+                    for (kind, source) in LightSource::KINDS {
+                        if ui.selectable_label(carrying == kind, kind).clicked() {
+                            commands.entity(unit).insert(source);
+                        }
+                    }
+                });
+        });
+}
+
+/// Asks whether an enemy takes the opportunity attack a unit provoked by
+/// leaving its reach.
+fn opportunity_attack_prompt(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut pending: ResMut<OpportunityAttacks>,
+    names: Query<&UnitName>,
+) {
+    let Some(attack) = pending.0.first().copied() else {
+        return;
+    };
+    let name = |unit| names.get(unit).map(|x| x.0.clone()).unwrap_or_default();
+    let mut done = false;
+    egui::Window::new("Opportunity attack")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{} left {}'s reach.",
+                name(attack.target),
+                name(attack.attacker)
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Attack").clicked() {
+                    match attack.attack() {
+                        Some(attack) => commands.trigger(attack),
+                        None => warn!("{} has no melee weapon", name(attack.attacker)),
+                    }
+                    done = true;
+                }
+                if ui.button("Let them go").clicked() {
+                    done = true;
+                }
+            });
+        });
+    if done {
+        pending.0.remove(0);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::areas::{save_modifier, Saver};
+use crate::checks::{
+    passive_score, skill_modifier, Belonging, Belongings, Check, CheckResult, RollCheck, Skills,
+};
+use crate::components::*;
+use crate::party::Party;
+use crate::pdf::{ExportSheet, SHEET_DIR};
+use crate::races::RaceCatalog;
+use crate::AppState;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct CharacterSheetPlugin;
+
+impl Plugin for CharacterSheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SheetRolls>();
+        app.observe(remember_roll);
+        app.add_systems(Update, character_sheet.run_if(in_state(AppState::InGame)));
+    }
+}
+
+/// How many of the latest rolls the sheet shows.
+const ROLLS_SHOWN: usize = 5;
+
+/// The latest checks rolled, newest last.
+#[derive(Resource, Default)]
+struct SheetRolls(Vec<CheckResult>);
+
+fn remember_roll(trigger: Trigger<CheckResult>, mut rolls: ResMut<SheetRolls>) {
+    rolls.0.push(trigger.event().clone());
+    let extra = rolls.0.len().saturating_sub(ROLLS_SHOWN);
+    rolls.0.drain(..extra);
+}
+
+type Vitals = (
+    &'static UnitName,
+    &'static Level,
+    Option<&'static Race>,
+    Option<&'static Class>,
+    &'static ArmorClass,
+    &'static Speed,
+    &'static Health,
+    &'static MaxHealth,
+    Option<&'static TempHealth>,
+    Option<&'static ProficiencyBonus>,
+);
+
+/// Everything on the active character's sheet.
+#[derive(SystemParam)]
+struct Sheet<'w, 's> {
+    party: Party<'w, 's>,
+    vitals: Query<'w, 's, Vitals>,
+    savers: Query<'w, 's, Saver>,
+    skills: Query<'w, 's, Skills>,
+    children: Query<'w, 's, &'static Children>,
+    belongings: Query<'w, 's, Belonging>,
+    races: Res<'w, RaceCatalog>,
+}
+
+fn marker(proficiency: &Proficiency) -> &'static str {
+    match proficiency {
+        Proficiency::None => "",
+        Proficiency::Proficient => "*",
+        Proficiency::Expert => "**",
+    }
+}
+
+/// The active character's sheet, read from their components every frame so
+/// it follows items, level ups and damage as they happen. Clicking a
+/// modifier, save or skill rolls it.
+fn character_sheet(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    sheet: Sheet,
+    rolls: Res<SheetRolls>,
+) {
+    let Some(unit) = sheet.party.active() else {
+        return;
+    };
+    let (Ok(vitals), Ok(saver), Ok(skills)) = (
+        sheet.vitals.get(unit),
+        sheet.savers.get(unit),
+        sheet.skills.get(unit),
+    ) else {
+        return;
+    };
+    let (name, level, race, class, ac, speed, health, max_health, temp_health, prof_bonus) = vitals;
+    let bonus = prof_bonus.map_or(0, |x| x.0);
+    let abilities = [
+        &saver.0 .0,
+        &saver.1 .0,
+        &saver.2 .0,
+        &saver.3 .0,
+        &saver.4 .0,
+        &saver.5 .0,
+    ];
+    let mut roll = None;
+    let mut export = false;
+    egui::Window::new("Character sheet")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(&name.0);
+                let hover = format!("Writes a printable sheet to {SHEET_DIR}/");
+                if ui.button("Export PDF").on_hover_text(hover).clicked() {
+                    export = true;
+                }
+            });
+            let race = race.map(|x| sheet.races.name(x)).unwrap_or_default();
+            let class = class.map(|x| x.to_string()).unwrap_or_default();
+            ui.label(format!("Level {} {race} {class}", level.0));
+            ui.separator();
+
+            ui.columns(3, |columns| {
+                egui::Grid::new("sheetabilities")
+                    .striped(true)
+                    .show(&mut columns[0], |ui| {
+                        ui.strong("Ability");
+                        ui.strong("Score");
+                        ui.strong("Check");
+                        ui.strong("Save");
+                        ui.end_row();
+                        for (stat, ability) in StatEnum::ABILITIES.into_iter().zip(abilities) {
+                            ui.label(format!("{stat:?}"));
+                            ui.label(ability.stat.total.to_string());
+                            let modifier = ability.calculate_modifier() as i64;
+                            if ui.button(format!("{modifier:+}")).clicked() {
+                                roll = Some(Check::Ability(stat.clone()));
+                            }
+                            let save = save_modifier(saver, &stat).unwrap_or(modifier);
+                            let label = format!("{save:+}{}", marker(&ability.proficiency));
+                            if ui.button(label).clicked() {
+                                roll = Some(Check::Save(stat));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                egui::Grid::new("sheetskills")
+                    .striped(true)
+                    .show(&mut columns[1], |ui| {
+                        for stat in StatEnum::SKILLS {
+                            let Some(skill) = skills.get(&stat) else {
+                                continue;
+                            };
+                            let modifier = skill_modifier(skill, bonus);
+                            if ui.button(format!("{stat:?}")).clicked() {
+                                roll = Some(Check::Skill(stat));
+                            }
+                            ui.label(format!("{modifier:+}{}", marker(&skill.proficiency)));
+                            ui.end_row();
+                        }
+                    });
+                egui::Grid::new("sheetcombat")
+                    .striped(true)
+                    .show(&mut columns[2], |ui| {
+                        ui.label("Armor Class");
+                        ui.label(ac.0.total.to_string());
+                        ui.end_row();
+                        ui.label("Hit points");
+                        ui.label(format!("{} / {}", health.0, max_health.0.total));
+                        ui.end_row();
+                        ui.label("Temporary");
+                        let mut temp = temp_health.map_or(0., |x| x.0);
+                        if ui
+                            .add(egui::DragValue::new(&mut temp).range(0..=999))
+                            .changed()
+                        {
+                            commands.entity(unit).insert(TempHealth(temp));
+                        }
+                        ui.end_row();
+                        ui.label("Speed");
+                        ui.label(format!("{} ft", speed.0.total));
+                        ui.end_row();
+                        ui.label("Proficiency bonus");
+                        ui.label(format!("{bonus:+}"));
+                        ui.end_row();
+                        for (label, skill) in [
+                            ("Passive Perception", &skills.perception.0),
+                            ("Passive Investigation", &skills.investigation.0),
+                            ("Passive Insight", &skills.insight.0),
+                        ] {
+                            ui.label(label);
+                            ui.label(passive_score(skill, bonus).to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+            ui.separator();
+
+            let children = sheet.children.get(unit).into_iter().flatten();
+            let belongings = Belongings::sort(sheet.belongings.iter_many(children));
+            let items = belongings.items.iter().map(|x| x.0.clone()).collect();
+            let spells = belongings.spells.iter().map(|x| x.0.clone()).collect();
+            let features = belongings.features;
+            let list = |names: Vec<String>| match names.is_empty() {
+                true => "None".to_string(),
+                false => names.join(", "),
+            };
+            ui.label(format!("Equipped: {}", list(items)));
+            ui.label(format!("Spells: {}", list(spells)));
+            ui.collapsing(format!("Features ({})", features.len()), |ui| {
+                for feature in features {
+                    ui.label(&feature.name).on_hover_text(&feature.description);
+                }
+            });
+
+            let rolls = rolls.0.iter().rev().filter(|x| x.unit == unit);
+            for result in rolls {
+                ui.label(format!(
+                    "{}: rolled {}, {} in all",
+                    result.check.name(),
+                    result.roll,
+                    result.total
+                ));
+            }
+        });
+    if let Some(check) = roll {
+        commands.trigger(RollCheck { unit, check });
+    }
+    if export {
+        commands.add(ExportSheet(unit));
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use crate::combat::InGameState;
+use crate::components::{Enemy, Level, Player};
+use crate::encounters::{self, Difficulty, Encounter, StartEncounter, ENCOUNTER_DIR};
+use crate::monsters::MonsterCatalog;
+use crate::saves::SaveErrors;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct EncounterBuilderPlugin;
+
+impl Plugin for EncounterBuilderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EncounterDraft>();
+        app.add_systems(OnEnter(InGameState::Encounter), open_builder);
+        app.add_systems(
+            Update,
+            encounter_builder.run_if(in_state(InGameState::Encounter)),
+        );
+        app.add_systems(
+            Update,
+            build_encounter_button.run_if(in_state(InGameState::Combat)),
+        );
+    }
+}
+
+/// The encounter being put together, and the party it's rated against.
+#[derive(Resource, Default)]
+struct EncounterDraft {
+    encounter: Encounter,
+    levels: Vec<i64>,
+    saved: Vec<String>,
+}
+
+fn open_builder(
+    mut draft: ResMut<EncounterDraft>,
+    mut errors: ResMut<SaveErrors>,
+    players: Query<&Level, With<Player>>,
+) {
+    draft.levels = players.iter().map(|x| x.0).collect();
+    if draft.levels.is_empty() {
+        draft.levels.push(1);
+    }
+    draft.saved = read_encounters(&mut errors);
+}
+
+fn read_encounters(errors: &mut SaveErrors) -> Vec<String> {
+    encounters::list_encounters(Path::new(ENCOUNTER_DIR)).unwrap_or_else(|e| {
+        errors.0.push(e);
+        Vec::new()
+    })
+}
+
+/// Offers the builder once there's nobody left to fight.
+fn build_encounter_button(
+    mut contexts: EguiContexts,
+    mut set_state: ResMut<NextState<InGameState>>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    if !enemies.is_empty() {
+        return;
+    }
+    egui::Window::new("Encounters")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Build encounter").clicked() {
+                set_state.set(InGameState::Encounter);
+            }
+        });
+}
+
+fn difficulty_color(difficulty: Difficulty) -> egui::Color32 {
+    match difficulty {
+        Difficulty::Trivial => egui::Color32::GRAY,
+        Difficulty::Easy => egui::Color32::LIGHT_GREEN,
+        Difficulty::Medium => egui::Color32::YELLOW,
+        Difficulty::Hard => egui::Color32::from_rgb(255, 140, 0),
+        Difficulty::Deadly => egui::Color32::RED,
+    }
+}
+
+fn encounter_builder(
+    mut contexts: EguiContexts,
+    mut draft: ResMut<EncounterDraft>,
+    mut errors: ResMut<SaveErrors>,
+    mut set_state: ResMut<NextState<InGameState>>,
+    mut commands: Commands,
+    catalog: Res<MonsterCatalog>,
+) {
+    let ctx = contexts.ctx_mut();
+    let dir = Path::new(ENCOUNTER_DIR);
+    let draft = &mut *draft;
+    egui::Window::new("Encounter builder")
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Party levels:");
+                for level in draft.levels.iter_mut() {
+                    ui.add(egui::DragValue::new(level).range(1..=20));
+                }
+                if ui.button("+").clicked() {
+                    draft.levels.push(draft.levels.last().copied().unwrap_or(1));
+                }
+                if draft.levels.len() > 1 && ui.button("-").clicked() {
+                    draft.levels.pop();
+                }
+            });
+
+            let rating = draft.encounter.rate(&catalog, &draft.levels);
+            let t = rating.thresholds;
+            ui.label(format!(
+                "Easy {}  Medium {}  Hard {}  Deadly {}",
+                t.easy, t.medium, t.hard, t.deadly
+            ));
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                columns[0].heading("Monsters");
+                egui::ScrollArea::vertical()
+                    .id_source("catalog")
+                    .max_height(300.)
+                    .show(&mut columns[0], |ui| {
+                        for monster in &catalog.0 {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Add").clicked() {
+                                    draft.encounter.add(&monster.name);
+                                }
+                                ui.label(format!(
+                                    "{} (CR {}, {} XP)",
+                                    monster.name,
+                                    monster.challenge_label(),
+                                    monster.xp
+                                ));
+                            });
+                        }
+                    });
+
+                columns[1].heading("Encounter");
+                let mut removed = None;
+                for monster in &draft.encounter.monsters {
+                    columns[1].horizontal(|ui| {
+                        if ui.small_button("-").clicked() {
+                            removed = Some(monster.name.clone());
+                        }
+                        ui.label(format!("{} × {}", monster.count, monster.name));
+                    });
+                }
+                if let Some(name) = removed {
+                    draft.encounter.remove(&name);
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} XP × {} = {}",
+                    rating.xp, rating.multiplier, rating.adjusted_xp
+                ));
+                ui.colored_label(
+                    difficulty_color(rating.difficulty),
+                    rating.difficulty.to_string(),
+                );
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut draft.encounter.name);
+                if ui.button("Save").clicked() {
+                    match encounters::save_encounter(dir, &draft.encounter) {
+                        Ok(_) => draft.saved = read_encounters(&mut errors),
+                        Err(e) => errors.0.push(e),
+                    }
+                }
+            });
+            if !draft.saved.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Load:");
+                    for slot in &draft.saved {
+                        if ui.button(slot).clicked() {
+                            match encounters::load_encounter(dir, slot) {
+                                Ok(encounter) => draft.encounter = encounter,
+                                Err(e) => errors.0.push(e),
+                            }
+                        }
+                    }
+                });
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let start = ui.add_enabled(
+                    draft.encounter.monster_count() > 0,
+                    egui::Button::new("Start encounter"),
+                );
+                if start.clicked() {
+                    commands.add(StartEncounter(draft.encounter.clone()));
+                }
+                if ui.button("Cancel").clicked() {
+                    set_state.set(InGameState::Combat);
+                }
+            });
+        });
+}
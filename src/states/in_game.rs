@@ -1,8 +1,8 @@
+use crate::combat::InGameState;
 use crate::components::*;
 use crate::items::{ItemsEnum, SpawnItem};
 use crate::AppState;
 use bevy::prelude::*;
-use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use bevy::sprite::{Wireframe2dConfig, Wireframe2dPlugin};
 use bevy::utils::tracing::info;
 use rand::Rng;
@@ -24,11 +24,7 @@ impl Plugin for InGamePlugin {
         //     narrative_ui.run_if(in_state(InGameState::Narrative)),
         // );
         app.add_systems(Update, combat_ui.run_if(in_state(InGameState::Combat)));
-        app.add_event::<Attack>();
-        // app.observe(handle_attack);
-        app.observe(handle_taking_damage);
         // app.insert_resource(InCombat(false));
-        app.add_sub_state::<InGameState>();
     }
 }
 
@@ -48,22 +44,7 @@ impl Plugin for SkillRollPlugin {
     }
 }
 
-fn in_game_setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    player_query: Query<Entity, With<Player>>,
-) {
-    let ent = player_query.single();
-    commands.entity(ent).insert(MaterialMesh2dBundle {
-        mesh: Mesh2dHandle(meshes.add(Circle { radius: 50.0 })),
-        material: materials.add(Color::Srgba(Srgba::BLUE)),
-        transform: Transform::from_xyz(
-            // Distribute shapes from -X_EXTENT/2 to +X_EXTENT/2.
-            0.0, 0.0, 0.0,
-        ),
-        ..default()
-    });
+fn in_game_setup(mut commands: Commands) {
     commands.trigger(SpawnItem(ItemsEnum::RingOfHealth));
 }
 
@@ -191,15 +172,6 @@ fn roll(dice: &Dice) -> i64 {
     total
 }
 
-#[derive(SubStates, Clone, PartialEq, Eq, Hash, Debug, Default)]
-#[source(AppState = AppState::InGame)]
-enum InGameState {
-    #[default]
-    // Narrative,
-    Combat,
-    Paused,
-}
-
 #[derive(Resource, Default)]
 struct KeyCombo(Vec<KeyCode>);
 
@@ -223,19 +195,6 @@ enum RollType {
     SavingThrow,
 }
 
-#[derive(Event)]
-struct Attack {
-    from: Entity,
-    with: Entity,
-    to: Entity,
-}
-
-#[derive(Event)]
-struct TakeDamage {
-    unit: Entity,
-    amount: f64,
-}
-
 fn paused_menu(mut commands: Commands) {}
 
 // fn narrative_ui(mut contexts: EguiContexts, mut commands: Commands, mut combat: ResMut<InCombat>) {
@@ -284,171 +243,3 @@ fn paused_menu(mut commands: Commands) {}
 //     info! {"Rolling {resp}!"}
 //     info! {"Rolled {rolled}, plus modifiers equals {total}"};
 // }
-
-// fn handle_attack(
-//     trigger: Trigger<Attack>,
-//     mut commands: Commands,
-//     from_query: Query<(
-//         &StrengthModifier,
-//         &DexterityModifier,
-//         &SimpleWeaponProficiency,
-//         &MartialWeaponProficiency,
-//         &IndividualWeaponProficiency,
-//         &ProficiencyBonus,
-//         &CritType,
-//     )>,
-//     with_query: Query<(
-//         &WeaponType,
-//         Option<&AttackModifier>,
-//         Option<&Advantage>,
-//         Option<&Disadvantage>,
-//     )>,
-//     to_query: Query<(&ArmorClass, Option<&Cover>)>,
-//     damage_query: Query<(&BaseDamage, &Dice, &DamageType, Option<&DamageModifier>)>,
-// ) {
-//     info!("Inside handle attack fn");
-//     let event = trigger.event();
-//     let (strmod, dexmod, simp, mart, ind, profbonus, crit_type) = from_query
-//         .get(event.from)
-//         .expect("The Attack.from entity to exist");
-//
-//     let (wep_type, att_mod, adv, disadv) = with_query
-//         .get(event.with)
-//         .expect("the Attack.with entity to exist");
-//
-//     let (ac, cover) = to_query
-//         .get(event.to)
-//         .expect("the Attack.to entity to exist");
-//
-//     let mut dice_addition = match *wep_type {
-//         WeaponType::SimpleMelee => match simp.0 {
-//             Proficiency::None => strmod.0,
-//             Proficiency::Proficient => strmod.0 + profbonus.0,
-//             Proficiency::Expert => strmod.0 + (profbonus.0 * 2),
-//         },
-//         WeaponType::SimpleRanged => match simp.0 {
-//             Proficiency::None => dexmod.0,
-//             Proficiency::Proficient => dexmod.0 + profbonus.0,
-//             Proficiency::Expert => dexmod.0 + (profbonus.0 * 2),
-//         },
-//         WeaponType::MartialMelee => match mart.0 {
-//             Proficiency::None => strmod.0,
-//             Proficiency::Proficient => strmod.0 + profbonus.0,
-//             Proficiency::Expert => strmod.0 + (profbonus.0 * 2),
-//         },
-//         WeaponType::MartialRanged => match mart.0 {
-//             Proficiency::None => dexmod.0,
-//             Proficiency::Proficient => dexmod.0 + profbonus.0,
-//             Proficiency::Expert => dexmod.0 + (profbonus.0 * 2),
-//         },
-//     };
-//     if let Some(modd) = att_mod {
-//         dice_addition += modd.0;
-//     }
-//     let mut rng = rand::thread_rng();
-//     let first_roll: i64 = rng.gen_range(1..21);
-//     let second_roll: i64 = rng.gen_range(1..21);
-//     let mut final_roll = match (adv, disadv) {
-//         (Some(_), Some(_)) => first_roll,
-//         (None, None) => first_roll,
-//         (Some(_), _) => {
-//             if first_roll >= second_roll {
-//                 first_roll
-//             } else {
-//                 second_roll
-//             }
-//         }
-//         (_, Some(_)) => {
-//             if first_roll <= second_roll {
-//                 first_roll
-//             } else {
-//                 second_roll
-//             }
-//         }
-//     };
-//     info!("Roll: {final_roll}");
-//     let critical_success = final_roll == 20;
-//     // Not sure what to do with crit_failure right now
-//     // let critical_failure = final_roll == 1;
-//     final_roll += dice_addition;
-//     if critical_success {
-//         info!("CRIT!");
-//     }
-//     info!("Final attack number: {final_roll}");
-//     let total_ac = match cover {
-//         None => ac.0,
-//         Some(c) => match *c {
-//             Cover::Half => ac.0 + 2,
-//             Cover::ThreeQuarters => ac.0 + 5,
-//             Cover::Total => ac.0 + 999,
-//         },
-//     };
-//     info!("AC: {total_ac}");
-//
-//     if final_roll > total_ac {
-//         info!("HIT!");
-//         let (base, dice, dmg_type, dmg_mod) = damage_query
-//             .get(event.with)
-//             .expect("There to be a damage bundle from Event.with");
-//         let mut dice_total = 0;
-//         let dice_max = match dice.dice_type {
-//             DiceType::D2 => 3,
-//             DiceType::D4 => 5,
-//             DiceType::D6 => 7,
-//             DiceType::D8 => 9,
-//             DiceType::D10 => 11,
-//             DiceType::D12 => 13,
-//             DiceType::D20 => 21,
-//             DiceType::D100 => 101,
-//         };
-//         for _ in 0..dice.number {
-//             let roll = rng.gen_range(1..dice_max);
-//             info!(roll);
-//             dice_total += roll
-//         }
-//         info!("Rolled Damage: {dice_total}");
-//         let mut dmg_total = match dmg_mod {
-//             None => dice_total + base.0,
-//             Some(x) => dice_total + base.0 + x.0,
-//         };
-//
-//         // Currently defaults to double damage crit type, even though we query for it
-//         if critical_success {
-//             dmg_total *= 2;
-//         }
-//
-//         info!("Total damage: {dmg_total}");
-//
-//         commands.trigger(TakeDamage {
-//             unit: event.to,
-//             amount: dmg_total,
-//         })
-//     }
-// }
-
-fn handle_taking_damage(
-    trigger: Trigger<TakeDamage>,
-    mut commands: Commands,
-    mut health_query: Query<(&mut Health, Option<&Player>, &MaxHealth)>,
-) {
-    info!("Inside taking damage function");
-    let event = trigger.event();
-    let (mut health, player, max_health) = health_query
-        .get_mut(event.unit)
-        .expect("The event.unit to exist and point to an existing entity");
-    info!("Previous health: {}", health.0);
-    health.0 -= event.amount;
-    if health.0 > 0. {
-        info!("Current health: {}", health.0);
-        return;
-    } else {
-        info!("Uh oh, somebody's in trouble!");
-        match player {
-            None => commands.entity(event.unit).despawn(),
-            Some(_) => {
-                let dead = health.0.abs() >= (max_health.0.total * 2.);
-                if dead {}
-            }
-        }
-    }
-}
@@ -1,91 +1,320 @@
-use std::fs;
-use std::path::Path;
-
+use crate::components::Player;
+use crate::migrations::SAVE_VERSION;
+use crate::party::{self, Party};
+use crate::saves::{self, SaveDir, SaveErrors, SaveSlot, SlotInfo};
 use crate::AppState;
-use bevy::prelude::*;
+use bevy::{ecs::world::Command, prelude::*};
 use bevy_egui::{egui, EguiContexts};
 
 pub struct LoadCharacterPlugin;
 
 impl Plugin for LoadCharacterPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<SaveFileNames>();
+        app.init_resource::<SaveSlots>();
+        app.init_resource::<PartySlots>();
+        app.init_resource::<SlotEdit>();
+        app.init_resource::<RestoreOffer>();
         app.add_systems(OnEnter(AppState::LoadCharacter), populate_savefile_names);
         app.add_event::<LoadGame>();
-        app.add_event::<GoToInGame>();
-        app.add_systems(Update, setup.run_if(in_state(AppState::LoadCharacter)));
         app.add_systems(
             Update,
-            transition_to_in_game.run_if(on_event::<GoToInGame>()),
+            (party_panel, setup)
+                .chain()
+                .run_if(in_state(AppState::LoadCharacter)),
         );
         app.observe(load_character);
+        app.observe(add_to_party);
     }
 }
 
 #[derive(Resource, Default)]
-struct SaveFileNames(Vec<String>);
+struct SaveSlots(Vec<SlotInfo>);
+
+/// The saved parties.
+#[derive(Resource, Default)]
+struct PartySlots(Vec<String>);
+
+/// A slot management action waiting on more input from the player.
+#[derive(Resource, Default)]
+enum SlotEdit {
+    #[default]
+    None,
+    Rename(String, String),
+    Delete(String),
+}
+
+/// A slot that failed to load because its save is damaged, and why. The
+/// player is offered its latest backup instead, which is then loaded the
+/// way the slot was: into the game when `play` is set, or into the party.
+#[derive(Resource, Default)]
+struct RestoreOffer(Option<Restore>);
+
+#[derive(Clone)]
+struct Restore {
+    slot: String,
+    error: String,
+    play: bool,
+}
 
 #[derive(Event)]
 pub struct LoadGame(pub String);
 
+/// Loads a character into the party without starting the game.
 #[derive(Event)]
-struct GoToInGame;
-
-fn populate_savefile_names(mut names: ResMut<SaveFileNames>) {
-    let path = Path::new("assets/saves/");
-    let mut str_names = Vec::new();
-    for entry in fs::read_dir(path).unwrap() {
-        let entry = entry.unwrap();
-        let this_path = entry.path();
-        let name = this_path
-            .file_stem()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .split('.')
-            .collect::<Vec<&str>>()
-            .get(0)
-            .unwrap()
-            .to_string();
-        str_names.push(name);
-    }
-    names.0 = str_names;
+pub struct AddToParty(pub String);
+
+fn populate_savefile_names(
+    dir: Res<SaveDir>,
+    mut slots: ResMut<SaveSlots>,
+    mut parties: ResMut<PartySlots>,
+    mut errors: ResMut<SaveErrors>,
+) {
+    slots.0 = read_slots(&dir, &mut errors);
+    parties.0 = party::list_parties(&dir.0).unwrap_or_else(|e| {
+        errors.0.push(e);
+        Vec::new()
+    });
+}
+
+fn read_slots(dir: &SaveDir, errors: &mut SaveErrors) -> Vec<SlotInfo> {
+    saves::list_slots(&dir.0).unwrap_or_else(|e| {
+        errors.0.push(e);
+        Vec::new()
+    })
 }
 
-fn setup(mut contexts: EguiContexts, characters: Res<SaveFileNames>, mut commands: Commands) {
+fn setup(
+    mut contexts: EguiContexts,
+    save_dir: Res<SaveDir>,
+    mut slots: ResMut<SaveSlots>,
+    mut edit: ResMut<SlotEdit>,
+    mut errors: ResMut<SaveErrors>,
+    mut restore: ResMut<RestoreOffer>,
+    mut commands: Commands,
+) {
     let ctx = contexts.ctx_mut();
-    // load character save file names
-    // iterate over those names and then load that character
+    let dir = save_dir.0.as_path();
+    let mut changed = false;
+    if let Some(Restore { slot, error, play }) = restore.0.clone() {
+        egui::Window::new("Damaged save")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{slot} could not be read: {error}"));
+                ui.label("Restore it from its latest backup?");
+                ui.horizontal(|ui| {
+                    if ui.button("Restore").clicked() {
+                        match saves::restore_backup(dir, &slot) {
+                            Ok(()) if play => commands.trigger(LoadGame(slot.clone())),
+                            Ok(()) => commands.trigger(AddToParty(slot.clone())),
+                            Err(e) => errors.0.push(e),
+                        }
+                        restore.0 = None;
+                        changed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        restore.0 = None;
+                    }
+                });
+            });
+    }
     egui::CentralPanel::default().show(ctx, |ui| {
-        ui.vertical_centered(|ui| {
-            for name in &characters.0 {
-                if ui.button(name).clicked() {
-                    commands.trigger(LoadGame(name.clone()));
-                }
-            }
-        })
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("saveslots")
+                .striped(true)
+                .min_col_width(80.)
+                .show(ui, |ui| {
+                    for info in &slots.0 {
+                        let slot = &info.slot;
+                        match &info.metadata {
+                            Some(metadata) => {
+                                ui.vertical(|ui| {
+                                    ui.strong(&metadata.name);
+                                    ui.label(metadata.summary());
+                                });
+                                ui.vertical(|ui| {
+                                    ui.label(saves::format_timestamp(metadata.timestamp));
+                                    ui.label(format!(
+                                        "Played {}, v{}",
+                                        saves::format_play_time(metadata.play_time),
+                                        metadata.version
+                                    ));
+                                    if metadata.schema > SAVE_VERSION {
+                                        ui.colored_label(
+                                            egui::Color32::LIGHT_RED,
+                                            "Saved by a newer version of the game",
+                                        );
+                                    }
+                                });
+                            }
+                            None => {
+                                ui.strong(slot);
+                                ui.label("No save information");
+                            }
+                        }
+                        ui.label(slot);
+                        match &mut *edit {
+                            SlotEdit::Rename(from, to) if from == slot => {
+                                ui.text_edit_singleline(to);
+                                if ui.button("Rename").clicked() {
+                                    if let Err(e) = saves::rename_slot(dir, from, to) {
+                                        errors.0.push(e);
+                                    }
+                                    *edit = SlotEdit::None;
+                                    changed = true;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    *edit = SlotEdit::None;
+                                }
+                            }
+                            SlotEdit::Delete(target) if target == slot => {
+                                ui.label("Delete this save?");
+                                if ui.button("Delete").clicked() {
+                                    if let Err(e) = saves::delete_slot(dir, slot) {
+                                        errors.0.push(e);
+                                    }
+                                    *edit = SlotEdit::None;
+                                    changed = true;
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    *edit = SlotEdit::None;
+                                }
+                            }
+                            _ => {
+                                if ui.button("Load").clicked() {
+                                    commands.trigger(LoadGame(slot.clone()));
+                                }
+                                if ui.button("Add to party").clicked() {
+                                    commands.trigger(AddToParty(slot.clone()));
+                                }
+                                if ui.button("Duplicate").clicked() {
+                                    if let Err(e) = saves::duplicate_slot(dir, slot) {
+                                        errors.0.push(e);
+                                    }
+                                    changed = true;
+                                }
+                                if ui.button("Rename").clicked() {
+                                    *edit = SlotEdit::Rename(slot.clone(), slot.clone());
+                                }
+                                if ui.button("Delete").clicked() {
+                                    *edit = SlotEdit::Delete(slot.clone());
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
     });
+    if changed {
+        slots.0 = read_slots(&save_dir, &mut errors);
+    }
 }
 
-fn transition_to_in_game(
-    mut ev_reader: EventReader<GoToInGame>,
-    mut appstate: ResMut<NextState<AppState>>,
+/// The characters loaded so far, and the saved parties.
+fn party_panel(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    party: Party,
+    parties: Res<PartySlots>,
+    mut set_state: ResMut<NextState<AppState>>,
 ) {
-    for _ in ev_reader.read() {
-        appstate.set(AppState::InGame);
+    let members = party.members();
+    if members.is_empty() && parties.0.is_empty() {
+        return;
     }
+    egui::TopBottomPanel::top("party").show(contexts.ctx_mut(), |ui| {
+        if !members.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.strong("Party:");
+                for (member, name) in party.members.iter_many(&members) {
+                    ui.label(&name.0);
+                    if ui.small_button("Remove").clicked() {
+                        commands.entity(member).despawn_recursive();
+                    }
+                }
+                if ui.button("Play").clicked() {
+                    set_state.set(AppState::InGame);
+                }
+            });
+        }
+        if !parties.0.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.strong("Saved parties:");
+                for slot in &parties.0 {
+                    if ui.button(slot).clicked() {
+                        commands.add(LoadParty(slot.clone()));
+                    }
+                }
+            });
+        }
+    });
 }
 
-fn load_character(
-    trigger: Trigger<LoadGame>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut ev_writer: EventWriter<GoToInGame>,
-) {
-    let file_path = format!("saves/{}.scn.ron", trigger.event().0);
-    commands.spawn(DynamicSceneBundle {
-        scene: asset_server.load(file_path),
-        ..default()
+/// Spawns the save in a slot and only enters the game once it holds
+/// exactly one player. Failures are shown on the load screen, with an offer
+/// to restore the latest backup if the save is damaged. Unless `play` is
+/// set, the character joins the party and the game waits for the rest.
+struct LoadSlot {
+    slot: String,
+    play: bool,
+}
+
+impl Command for LoadSlot {
+    fn apply(self, world: &mut World) {
+        let dir = world.resource::<SaveDir>().0.clone();
+        match party::add_member(world, &dir, &self.slot) {
+            Ok(_) if !self.play => {}
+            Ok(_) => {
+                // Later saves of this game go back to the slot it came from.
+                // A party is saved to a slot of its own.
+                let mut players = world.query_filtered::<(), With<Player>>();
+                if players.iter(world).count() == 1 {
+                    world.resource_mut::<SaveSlot>().0 = Some(self.slot);
+                }
+                world
+                    .resource_mut::<NextState<AppState>>()
+                    .set(AppState::InGame);
+            }
+            Err(e) if e.is_corrupt() && saves::latest_backup(&dir, &self.slot).is_some() => {
+                world.resource_mut::<RestoreOffer>().0 = Some(Restore {
+                    slot: self.slot,
+                    error: e.to_string(),
+                    play: self.play,
+                });
+            }
+            Err(e) => world.resource_mut::<SaveErrors>().0.push(e),
+        }
+    }
+}
+
+/// Spawns every member of a saved party and enters the game.
+struct LoadParty(String);
+
+impl Command for LoadParty {
+    fn apply(self, world: &mut World) {
+        let dir = world.resource::<SaveDir>().0.clone();
+        match party::spawn_party(world, &dir, &self.0) {
+            Ok(_) => {
+                world.resource_mut::<SaveSlot>().0 = Some(self.0);
+                world
+                    .resource_mut::<NextState<AppState>>()
+                    .set(AppState::InGame);
+            }
+            Err(e) => world.resource_mut::<SaveErrors>().0.push(e),
+        }
+    }
+}
+
+fn load_character(trigger: Trigger<LoadGame>, mut commands: Commands) {
+    commands.add(LoadSlot {
+        slot: trigger.event().0.clone(),
+        play: true,
+    });
+}
+
+fn add_to_party(trigger: Trigger<AddToParty>, mut commands: Commands) {
+    commands.add(LoadSlot {
+        slot: trigger.event().0.clone(),
+        play: false,
     });
-    ev_writer.send(GoToInGame);
 }
@@ -1,4 +1,6 @@
-use crate::{despawn_ui, AppState, ButtonType, RootUI};
+use crate::components::{ButtonType, RootUI};
+use crate::ui::despawn_ui;
+use crate::AppState;
 use bevy::prelude::*;
 use bevy_egui::{egui, EguiContexts};
 
@@ -0,0 +1,287 @@
+use std::path::Path;
+
+use crate::combat::InGameState;
+use crate::map::{self, BattleMap, Door, GridPosition, MAP_DIR};
+use crate::saves::SaveErrors;
+use crate::states::battle_map::MapCursor;
+use crate::uvtt::{self, UVTT_EXTENSIONS};
+use crate::vision::{LightLevel, LightSource};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct MapEditorPlugin;
+
+impl Plugin for MapEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapEditor>();
+        app.add_systems(OnEnter(InGameState::MapEditor), open_editor);
+        app.add_systems(
+            Update,
+            (map_editor, paint)
+                .chain()
+                .run_if(in_state(InGameState::MapEditor)),
+        );
+    }
+}
+
+/// What a click on the map paints.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Tool {
+    #[default]
+    Wall,
+    Door(Door),
+    Difficult,
+    Hazard,
+    Light(LightLevel),
+    Torch,
+    Erase,
+}
+
+const TOOLS: [(Tool, &str); 11] = [
+    (Tool::Wall, "Wall"),
+    (Tool::Door(Door::Closed), "Door"),
+    (Tool::Door(Door::Open), "Open door"),
+    (Tool::Door(Door::Locked), "Locked door"),
+    (Tool::Difficult, "Difficult"),
+    (Tool::Hazard, "Hazard"),
+    (Tool::Light(LightLevel::Bright), "Bright"),
+    (Tool::Light(LightLevel::Dim), "Dim"),
+    (Tool::Light(LightLevel::Dark), "Dark"),
+    (Tool::Torch, "Torch"),
+    (Tool::Erase, "Erase"),
+];
+
+#[derive(Resource, Default)]
+struct MapEditor {
+    tool: Tool,
+    /// A PNG or JPG to import as the background, or a Universal VTT file
+    /// to import as the whole map.
+    image: String,
+    saved: Vec<String>,
+    /// The last square painted while the mouse is held down, so each
+    /// square is painted once per stroke.
+    last: Option<GridPosition>,
+}
+
+fn open_editor(mut editor: ResMut<MapEditor>, mut errors: ResMut<SaveErrors>) {
+    editor.saved = read_maps(&mut errors);
+}
+
+fn read_maps(errors: &mut SaveErrors) -> Vec<String> {
+    map::list_maps(Path::new(MAP_DIR)).unwrap_or_else(|e| {
+        errors.0.push(e);
+        Vec::new()
+    })
+}
+
+/// Puts what `tool` paints on `pos`, replacing whatever it can't share the
+/// square with.
+fn apply(map: &mut BattleMap, tool: Tool, pos: GridPosition) {
+    match tool {
+        Tool::Wall => {
+            map.doors.remove(&pos);
+            map.walls.insert(pos);
+        }
+        Tool::Door(door) => {
+            map.walls.remove(&pos);
+            map.doors.insert(pos, door);
+        }
+        Tool::Difficult => {
+            map.difficult.insert(pos);
+        }
+        Tool::Hazard => {
+            map.hazards.insert(pos);
+        }
+        Tool::Light(level) => {
+            map.lit.insert(pos, level);
+        }
+        Tool::Torch => {
+            map.lights.insert(pos, LightSource::TORCH);
+        }
+        Tool::Erase => {
+            map.walls.remove(&pos);
+            map.doors.remove(&pos);
+            map.difficult.remove(&pos);
+            map.hazards.remove(&pos);
+            map.lit.remove(&pos);
+            map.lights.remove(&pos);
+        }
+    }
+}
+
+fn paint(
+    mut map: ResMut<BattleMap>,
+    mut editor: ResMut<MapEditor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    cursor: Res<MapCursor>,
+) {
+    if !mouse.pressed(MouseButton::Left) || (cursor.over_ui && editor.last.is_none()) {
+        editor.last = None;
+        return;
+    }
+    let Some(cell) = cursor.cell.filter(|x| editor.last != Some(*x)) else {
+        return;
+    };
+    editor.last = Some(cell);
+    apply(&mut map, editor.tool, cell);
+}
+
+/// The number of whole squares `pixels` of image hold past `offset`.
+fn squares(pixels: u32, offset: f32, pixels_per_square: f32) -> i32 {
+    ((pixels as f32 - offset) / pixels_per_square)
+        .floor()
+        .max(1.) as i32
+}
+
+fn map_editor(
+    mut contexts: EguiContexts,
+    mut editor: ResMut<MapEditor>,
+    mut map: ResMut<BattleMap>,
+    mut errors: ResMut<SaveErrors>,
+    mut set_state: ResMut<NextState<InGameState>>,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+) {
+    let dir = Path::new(MAP_DIR);
+    let editor = &mut *editor;
+    // Only a change the user makes counts, so the map isn't redrawn and
+    // everyone's sight isn't worked out again every frame.
+    let mut changed = false;
+    let edited = map.bypass_change_detection();
+    let (mut width, mut height) = (edited.width, edited.height);
+    egui::Window::new("Map editor")
+        .collapsible(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (tool, name) in TOOLS {
+                    ui.selectable_value(&mut editor.tool, tool, name);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Size:");
+                ui.add(egui::DragValue::new(&mut width).range(1..=200));
+                ui.label("×");
+                ui.add(egui::DragValue::new(&mut height).range(1..=200));
+                ui.label("Light:");
+                for (level, name) in [
+                    (LightLevel::Bright, "Bright"),
+                    (LightLevel::Dim, "Dim"),
+                    (LightLevel::Dark, "Dark"),
+                ] {
+                    changed |= ui.radio_value(&mut edited.light, level, name).changed();
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Image:");
+                ui.text_edit_singleline(&mut editor.image);
+                if ui.button("Import").clicked() {
+                    let path = Path::new(editor.image.trim());
+                    let is_uvtt = path
+                        .extension()
+                        .and_then(|x| x.to_str())
+                        .is_some_and(|x| UVTT_EXTENSIONS.contains(&x.to_lowercase().as_str()));
+                    // A Universal VTT file is a whole map, saved straight away.
+                    let imported = match is_uvtt {
+                        true => uvtt::import_uvtt(Path::new("assets"), path).and_then(|map| {
+                            map::save_map(dir, &map)?;
+                            (width, height) = (map.width, map.height);
+                            *edited = map;
+                            editor.saved = read_maps(&mut errors);
+                            Ok(())
+                        }),
+                        false => map::import_image(Path::new("assets"), path).map(|image| {
+                            let background = edited.background.get_or_insert_with(default);
+                            background.image = image;
+                        }),
+                    };
+                    match imported {
+                        Ok(_) => changed = true,
+                        Err(e) => errors.0.push(e),
+                    }
+                }
+            });
+            let mut remove_image = false;
+            if let Some(background) = &mut edited.background {
+                ui.label(&background.image);
+                ui.horizontal(|ui| {
+                    ui.label("Pixels per square:");
+                    let scale = egui::DragValue::new(&mut background.pixels_per_square)
+                        .speed(0.25)
+                        .range(4.0..=1000.0);
+                    changed |= ui.add(scale).changed();
+                    ui.label("Grid starts at:");
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut background.offset.0).speed(0.5))
+                        .changed();
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut background.offset.1).speed(0.5))
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    let image = images.get(&asset_server.load::<Image>(&background.image));
+                    let fit = ui.add_enabled(image.is_some(), egui::Button::new("Fit to image"));
+                    if let (true, Some(image)) = (fit.clicked(), image) {
+                        let size = image.size();
+                        let (x, y) = background.offset;
+                        width = squares(size.x, x, background.pixels_per_square);
+                        height = squares(size.y, y, background.pixels_per_square);
+                    }
+                    remove_image = ui.button("Remove image").clicked();
+                });
+            }
+            if remove_image {
+                edited.background = None;
+                changed = true;
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                changed |= ui.text_edit_singleline(&mut edited.name).changed();
+                if ui.button("Save").clicked() {
+                    match map::save_map(dir, edited) {
+                        Ok(_) => editor.saved = read_maps(&mut errors),
+                        Err(e) => errors.0.push(e),
+                    }
+                }
+            });
+            if !editor.saved.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Load:");
+                    for slot in &editor.saved {
+                        if ui.button(slot).clicked() {
+                            match map::load_map(dir, slot) {
+                                Ok(loaded) => {
+                                    (width, height) = (loaded.width, loaded.height);
+                                    *edited = loaded;
+                                    changed = true;
+                                }
+                                Err(e) => errors.0.push(e),
+                            }
+                        }
+                    }
+                });
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("New map").clicked() {
+                    *edited = BattleMap::default();
+                    (width, height) = (edited.width, edited.height);
+                    changed = true;
+                }
+                if ui.button("Done").clicked() {
+                    set_state.set(InGameState::Combat);
+                }
+            });
+        });
+    if (width, height) != (edited.width, edited.height) {
+        edited.resize(width, height);
+        changed = true;
+    }
+    if changed {
+        map.set_changed();
+    }
+}
@@ -2,14 +2,28 @@ use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
 
 // pub mod load_character;
+pub mod area_templates;
+pub mod battle_map;
+pub mod character_sheet;
+pub mod encounter_builder;
 pub mod in_game;
 pub mod load_character;
 pub mod main_menu;
+pub mod map_editor;
 pub mod new_character;
+pub mod party_roster;
+use area_templates::AreaTemplatePlugin;
+use battle_map::BattleMapPlugin;
+use character_sheet::CharacterSheetPlugin;
+use encounter_builder::EncounterBuilderPlugin;
 use in_game::InGamePlugin;
 use load_character::LoadCharacterPlugin;
 use main_menu::MainMenuPlugin;
+use map_editor::MapEditorPlugin;
 use new_character::NewCharacterPlugin;
+use party_roster::PartyRosterPlugin;
+
+use crate::saves::SavesUiPlugin;
 
 pub struct StatePlugins;
 
@@ -19,6 +33,13 @@ impl PluginGroup for StatePlugins {
             .add(MainMenuPlugin)
             .add(NewCharacterPlugin)
             .add(InGamePlugin)
+            .add(BattleMapPlugin)
+            .add(AreaTemplatePlugin)
+            .add(EncounterBuilderPlugin)
+            .add(MapEditorPlugin)
+            .add(PartyRosterPlugin)
+            .add(CharacterSheetPlugin)
             .add(LoadCharacterPlugin)
+            .add(SavesUiPlugin)
     }
 }
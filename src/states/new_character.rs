@@ -1,7 +1,17 @@
+use crate::ability_scores::{drop_lowest, point_buy_cost, AbilityMethod, AbilityScores};
+use crate::backgrounds::{BackgroundCatalog, BackgroundChoices, BackgroundData};
+use crate::character::{
+    non_background_skills, BasePlayer, Catalogs, CharacterBuilder, Derived, Override, Overrides,
+    SpawnCharacter,
+};
+use crate::classes::{ClassCatalog, ClassChoices, ClassData};
 use crate::components::*;
-use crate::despawn_ui;
+use crate::items::ArmorCatalog;
+use crate::races::{RaceCatalog, RacialChoices};
+use crate::saves::{slot_name, SaveSlot};
+use crate::ui::despawn_ui;
 use crate::AppState;
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_egui::{egui, EguiContexts};
 use strum::{Display, EnumIter, IntoEnumIterator};
 
@@ -9,8 +19,11 @@ pub struct NewCharacterPlugin;
 
 impl Plugin for NewCharacterPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<BasePlayer>();
+        app.init_resource::<CharacterBuilder>();
+        app.init_resource::<CreationErrors>();
+        app.register_type::<AbilityRolls>();
         app.add_event::<CreateCharacter>();
+        app.add_sub_state::<CreationStep>();
         app.add_systems(Update, ui.run_if(in_state(AppState::NewCharacter)));
         app.add_systems(OnEnter(AppState::NewCharacter), setup);
         app.add_systems(OnExit(AppState::NewCharacter), despawn_ui);
@@ -21,831 +34,1118 @@ impl Plugin for NewCharacterPlugin {
 #[derive(Event)]
 struct CreateCharacter;
 
+/// The pages of the character creation wizard, in order.
+#[derive(
+    SubStates, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default, EnumIter, Display,
+)]
+#[source(AppState = AppState::NewCharacter)]
+enum CreationStep {
+    #[default]
+    Race,
+    Class,
+    Abilities,
+    Background,
+    Skills,
+    Equipment,
+    Details,
+    Review,
+}
+
+impl CreationStep {
+    fn next(&self) -> Option<Self> {
+        Self::iter().skip_while(|x| x != self).nth(1)
+    }
+
+    fn previous(&self) -> Option<Self> {
+        Self::iter().take_while(|x| x != self).last()
+    }
+}
+
 fn on_character_creation(
-    trigger: Trigger<CreateCharacter>,
-    newchar: Res<PlayerBundle>,
+    _trigger: Trigger<CreateCharacter>,
+    builder: Res<CharacterBuilder>,
+    errors: Res<CreationErrors>,
     mut menu_state: ResMut<NextState<AppState>>,
     mut commands: Commands,
 ) {
-    let mut abilities = newchar.abilities.clone();
-    abilities.str.0.stat.total = abilities.str.0.stat.base;
-    abilities.con.0.stat.total = abilities.con.0.stat.base;
-    abilities.dex.0.stat.total = abilities.dex.0.stat.base;
-    abilities.int.0.stat.total = abilities.int.0.stat.base;
-    abilities.wis.0.stat.total = abilities.wis.0.stat.base;
-    abilities.cha.0.stat.total = abilities.cha.0.stat.base;
-    abilities.str.0.calculate_modifier();
-    abilities.con.0.calculate_modifier();
-    abilities.dex.0.calculate_modifier();
-    abilities.int.0.calculate_modifier();
-    abilities.wis.0.calculate_modifier();
-    abilities.cha.0.calculate_modifier();
-    let mut skills = newchar.skills.clone();
-    skills.athletics.0.stat.total = skills.athletics.0.stat.base;
-    skills.acrobatics.0.stat.total = skills.acrobatics.0.stat.base;
-    skills.sleight_of_hand.0.stat.total = skills.sleight_of_hand.0.stat.base;
-    skills.stealth.0.stat.total = skills.stealth.0.stat.base;
-    skills.arcana.0.stat.total = skills.arcana.0.stat.base;
-    skills.history.0.stat.total = skills.history.0.stat.base;
-    skills.investigation.0.stat.total = skills.investigation.0.stat.base;
-    skills.nature.0.stat.total = skills.nature.0.stat.base;
-    skills.religion.0.stat.total = skills.religion.0.stat.base;
-    skills.animal_handling.0.stat.total = skills.animal_handling.0.stat.base;
-    skills.insight.0.stat.total = skills.insight.0.stat.base;
-    skills.medicine.0.stat.total = skills.medicine.0.stat.base;
-    skills.perception.0.stat.total = skills.perception.0.stat.base;
-    skills.survival.0.stat.total = skills.survival.0.stat.base;
-    skills.deception.0.stat.total = skills.deception.0.stat.base;
-    skills.intimidation.0.stat.total = skills.intimidation.0.stat.base;
-    skills.performance.0.stat.total = skills.performance.0.stat.base;
-    skills.persuasion.0.stat.total = skills.persuasion.0.stat.base;
-
-    let darkvision: Option<DarkVision> = match newchar.race {
-        Race::HillDwarf => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::MountainDwarf => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::HighElf => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::WoodElf => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::DarkElf => Some(DarkVision(Stat::new(120., vec![]))),
-        Race::HalfElf => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::RockGnome => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::ForestGnome => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::HalfOrc => Some(DarkVision(Stat::new(60., vec![]))),
-        Race::Tiefling => Some(DarkVision(Stat::new(60., vec![]))),
-        _ => None,
-    };
-    let char_id = commands
-        .spawn(PlayerBundle {
-            unit_tag: Unit,
-            player_tag: Player,
-            name: newchar.name.clone(),
-            player_name: newchar.player_name.clone(),
-            ac: newchar.ac.clone(),
-            speed: Speed(Stat::new(newchar.speed.0.total, vec![])),
-            abilities,
-            skills,
-            race: newchar.race.clone(),
-            class: newchar.class.clone(),
-            health: Health(newchar.max_health.0.total).clone(),
-            max_health: MaxHealth(Stat::new(newchar.max_health.0.base, vec![])),
-            background: newchar.background.clone(),
-            alignment: newchar.alignment.clone(),
-            xp: newchar.xp.clone(),
-            level: newchar.level.clone(),
-            hit_dice: newchar.hit_dice.clone(),
-            wep_profs: WeaponProficiencies::default(),
-            prof_bonus: ProficiencyBonus::from_level(newchar.level.clone().0),
-            settings: SettingsBundle::default(),
-        })
-        .id();
-    if let Some(dv) = darkvision {
-        commands.entity(char_id).insert(dv);
+    if !errors.0.is_empty() {
+        warn!("Character is not ready to be created: {:?}", errors.0);
+        return;
     }
+    commands.add(SpawnCharacter(builder.clone()));
     menu_state.set(AppState::SaveCharacter);
 }
 
-fn setup(mut newchar: ResMut<PlayerBundle>, mut commands: Commands) {}
+/// Starts every new character from a blank sheet, saved to a fresh slot.
+fn setup(mut builder: ResMut<CharacterBuilder>, mut slot: ResMut<SaveSlot>) {
+    *builder = CharacterBuilder::default();
+    slot.0 = None;
+}
+
+fn ui(
+    mut contexts: EguiContexts,
+    mut builder: ResMut<CharacterBuilder>,
+    catalogs: CatalogParams,
+    mut slot: ResMut<SaveSlot>,
+    mut errors: ResMut<CreationErrors>,
+    step: Res<State<CreationStep>>,
+    mut next_step: ResMut<NextState<CreationStep>>,
+    mut commands: Commands,
+) {
+    let step = *step.get();
+    let catalogs = catalogs.get();
+    let races = catalogs.races;
+    let derived = builder.update(&catalogs);
+    let step_errors = validation_errors(step, &builder, &catalogs);
+    errors.0 = CreationStep::iter()
+        .flat_map(|step| validation_errors(step, &builder, &catalogs))
+        .collect();
+    let CharacterBuilder {
+        sheet: newchar,
+        scores,
+        racial_choices: choices,
+        class_choices,
+        background_choices,
+        overrides,
+    } = &mut *builder;
 
-fn ui(mut contexts: EguiContexts, mut newchar: ResMut<BasePlayer>, mut commands: Commands) {
     let ctx = contexts.ctx_mut();
     egui::TopBottomPanel::top("toppanel").show(ctx, |ui| {
-        egui::Grid::new("toppanelgrid")
-            .min_col_width(100.)
-            .striped(true)
-            .show(ui, |ui| {
-                ui.label("Character Name");
-                ui.text_edit_singleline(&mut newchar.name);
-                ui.label("Class");
-                egui::ComboBox::from_id_source("class")
-                    .selected_text(format!("{}", newchar.class.to_string()))
+        ui.horizontal(|ui| {
+            for other in CreationStep::iter() {
+                // Steps can be revisited, but not skipped ahead of the current one.
+                if ui
+                    .add_enabled(
+                        other <= step,
+                        egui::SelectableLabel::new(other == step, other.to_string()),
+                    )
+                    .clicked()
+                {
+                    next_step.set(other);
+                }
+            }
+        });
+    });
+    egui::TopBottomPanel::bottom("bottompannel").show(ctx, |ui| {
+        let shown = match step {
+            CreationStep::Review => &errors.0,
+            _ => &step_errors,
+        };
+        for error in shown {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+        ui.horizontal(|ui| {
+            if let Some(previous) = step.previous() {
+                if ui.button("Back").clicked() {
+                    next_step.set(previous);
+                }
+            }
+            match step.next() {
+                Some(next) => {
+                    if ui
+                        .add_enabled(step_errors.is_empty(), egui::Button::new("Next"))
+                        .clicked()
+                    {
+                        next_step.set(next);
+                    }
+                }
+                None => {
+                    if ui
+                        .add_enabled(errors.0.is_empty(), egui::Button::new("Create"))
+                        .clicked()
+                    {
+                        commands.trigger(CreateCharacter);
+                    }
+                }
+            }
+        });
+    });
+    egui::CentralPanel::default().show(ctx, |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| match step {
+            CreationStep::Race => {
+                egui::ComboBox::from_label("Race")
+                    .selected_text(races.name(&newchar.race))
                     .show_ui(ui, |ui| {
-                        for class in Class::iter() {
-                            ui.selectable_value(
-                                &mut newchar.class,
-                                class.clone(),
-                                class.to_string(),
-                            );
+                        for race in Race::iter() {
+                            let name = races.name(&race);
+                            if ui.selectable_value(&mut newchar.race, race, name).changed() {
+                                *choices = RacialChoices::default();
+                            }
                         }
                     });
-                ui.label("Background");
-                egui::ComboBox::from_id_source("background")
-                    .selected_text(format!("{}", newchar.background.to_string()))
+                racial_traits_ui(ui, races, &newchar.race, choices);
+            }
+            CreationStep::Class => {
+                egui::ComboBox::from_label("Class")
+                    .selected_text(newchar.class.to_string())
                     .show_ui(ui, |ui| {
-                        for background in Background::iter() {
-                            ui.selectable_value(
-                                &mut newchar.background,
-                                background.clone(),
-                                background.to_string(),
-                            );
+                        for class in Class::iter() {
+                            if ui
+                                .selectable_value(
+                                    &mut newchar.class,
+                                    class.clone(),
+                                    class.to_string(),
+                                )
+                                .changed()
+                            {
+                                *class_choices = ClassChoices::default();
+                            }
                         }
                     });
-                ui.label("Level");
-                ui.add(egui::DragValue::new(&mut newchar.level));
-                ui.end_row();
-                ui.label("Player Name");
-                ui.text_edit_singleline(&mut newchar.player_name);
-                ui.label("Race");
-                egui::ComboBox::from_id_source("race")
-                    .selected_text(format!("{}", newchar.race.to_string()))
+                ui.horizontal(|ui| {
+                    ui.label("Level");
+                    ui.add(egui::DragValue::new(&mut newchar.level).range(1..=20));
+                });
+                if let Some(data) = catalogs.classes.get(&newchar.class) {
+                    class_ui(ui, data, newchar.level, class_choices);
+                }
+            }
+            CreationStep::Abilities => {
+                abilities_ui(ui, scores, races, &newchar.race, choices);
+            }
+            CreationStep::Background => {
+                egui::ComboBox::from_label("Background")
+                    .selected_text(newchar.background.to_string())
                     .show_ui(ui, |ui| {
-                        for race in Race::iter() {
-                            ui.selectable_value(&mut newchar.race, race.clone(), race.to_string());
+                        for background in Background::iter() {
+                            if ui
+                                .selectable_value(
+                                    &mut newchar.background,
+                                    background.clone(),
+                                    background.to_string(),
+                                )
+                                .changed()
+                            {
+                                *background_choices = BackgroundChoices::default();
+                            }
                         }
                     });
-                ui.label("Alignment");
-                egui::ComboBox::from_id_source("alignment")
-                    .selected_text(format!("{}", newchar.alignment.to_string()))
-                    .show_ui(ui, |ui| {
-                        for alignment in Alignment::iter() {
-                            ui.selectable_value(
-                                &mut newchar.alignment,
-                                alignment.clone(),
-                                alignment.to_string(),
-                            );
+                if let Some(data) = catalogs.backgrounds.get(&newchar.background) {
+                    background_ui(ui, data, background_choices);
+                }
+            }
+            CreationStep::Skills => {
+                if let Some(data) = catalogs.classes.get(&newchar.class) {
+                    class_skills_ui(ui, data, class_choices);
+                }
+                if let Some(data) = catalogs.backgrounds.get(&newchar.background) {
+                    let taken = non_background_skills(races, &newchar.race, choices, class_choices);
+                    skill_replacement_ui(ui, data, &taken, background_choices);
+                }
+                ui.separator();
+                skills_ui(ui, &derived, overrides);
+            }
+            CreationStep::Equipment => {
+                if let Some(data) = catalogs.classes.get(&newchar.class) {
+                    class_equipment_ui(ui, data, class_choices);
+                }
+                if let Some(data) = catalogs.backgrounds.get(&newchar.background) {
+                    ui.label(format!(
+                        "From your background: {}",
+                        data.equipment.join(", ")
+                    ));
+                    ui.label(format!("{} gp", data.gold));
+                }
+                ui.separator();
+                egui::Grid::new("equipmentgrid").show(ui, |ui| {
+                    derived_row(ui, "Armor Class", derived.ac, &mut overrides.ac);
+                });
+            }
+            CreationStep::Details => {
+                egui::Grid::new("detailsgrid")
+                    .min_col_width(100.)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Character Name");
+                        ui.text_edit_singleline(&mut newchar.name);
+                        ui.end_row();
+                        ui.label("Player Name");
+                        ui.text_edit_singleline(&mut newchar.player_name);
+                        ui.end_row();
+                        ui.label("Save Slot");
+                        let mut chosen = slot.0.clone().unwrap_or_default();
+                        if ui
+                            .add(
+                                egui::TextEdit::singleline(&mut chosen)
+                                    .hint_text(slot_name(&newchar.name)),
+                            )
+                            .changed()
+                        {
+                            slot.0 = Some(chosen).filter(|x| !x.trim().is_empty());
                         }
+                        ui.end_row();
+                        ui.label("Alignment");
+                        egui::ComboBox::from_id_source("alignment")
+                            .selected_text(newchar.alignment.to_string())
+                            .show_ui(ui, |ui| {
+                                for alignment in Alignment::iter() {
+                                    ui.selectable_value(
+                                        &mut newchar.alignment,
+                                        alignment.clone(),
+                                        alignment.to_string(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        ui.label("XP");
+                        ui.add(egui::DragValue::new(&mut newchar.xp));
+                        ui.end_row();
+                        derived_row(
+                            ui,
+                            "Max Health",
+                            derived.max_health,
+                            &mut overrides.max_health,
+                        );
+                        derived_row(ui, "Speed", derived.speed, &mut overrides.speed);
                     });
-                ui.label("XP");
-                ui.add(egui::DragValue::new(&mut newchar.xp));
-            });
+            }
+            CreationStep::Review => {
+                review_ui(
+                    ui,
+                    newchar,
+                    &catalogs,
+                    &derived,
+                    overrides,
+                    choices,
+                    class_choices,
+                    background_choices,
+                );
+            }
+        });
     });
-    egui::SidePanel::left("left-panel").show(ctx, |ui| {
-        egui::Grid::new("abilitygrid")
-            .num_columns(4)
-            .show(ui, |ui| {
-                ui.label("ABILITIES");
-                ui.end_row();
-                ui.label("Strength");
-                ui.add(egui::DragValue::new(&mut newchar.strength));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("str")
-                    .selected_text(format!("{:?}", newchar.abilities.str.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.str.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.str.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.str.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Constitution");
-                ui.add(egui::DragValue::new(&mut newchar.abilities.con.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("con")
-                    .selected_text(format!("{:?}", newchar.abilities.con.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.con.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.con.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.con.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Dexterity");
-                ui.add(egui::DragValue::new(&mut newchar.abilities.dex.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("dex")
-                    .selected_text(format!("{:?}", newchar.abilities.dex.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.dex.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.dex.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.dex.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Intelligence");
-                ui.add(egui::DragValue::new(&mut newchar.abilities.int.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("int")
-                    .selected_text(format!("{:?}", newchar.abilities.int.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.int.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.int.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.int.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Wisdom");
-                ui.add(egui::DragValue::new(&mut newchar.abilities.wis.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("wis")
-                    .selected_text(format!("{:?}", newchar.abilities.wis.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.wis.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.wis.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.wis.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Charisma");
-                ui.add(egui::DragValue::new(&mut newchar.abilities.cha.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("cha")
-                    .selected_text(format!("{:?}", newchar.abilities.cha.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.abilities.cha.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.cha.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.abilities.cha.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.end_row();
-                ui.label("SKILLS");
-                ui.end_row();
-                ui.label("Acrobatics");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.acrobatics.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("acro")
-                    .selected_text(format!("{:?}", newchar.skills.acrobatics.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.acrobatics.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.acrobatics.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.acrobatics.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Animal Handling");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.animal_handling.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("animal")
-                    .selected_text(format!(
-                        "{:?}",
-                        newchar.skills.animal_handling.0.proficiency
-                    ))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.animal_handling.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.animal_handling.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.animal_handling.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Arcana");
-                ui.add(egui::DragValue::new(&mut newchar.skills.arcana.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("arcana")
-                    .selected_text(format!("{:?}", newchar.skills.arcana.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.arcana.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.arcana.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.arcana.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Athletics");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.athletics.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("athletics")
-                    .selected_text(format!("{:?}", newchar.skills.athletics.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.athletics.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.athletics.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.athletics.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Deception");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.deception.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("deception")
-                    .selected_text(format!("{:?}", newchar.skills.deception.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.deception.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.deception.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.deception.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("History");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.history.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("history")
-                    .selected_text(format!("{:?}", newchar.skills.history.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.history.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.history.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.history.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+}
+
+#[derive(SystemParam)]
+struct CatalogParams<'w> {
+    races: Res<'w, RaceCatalog>,
+    classes: Res<'w, ClassCatalog>,
+    backgrounds: Res<'w, BackgroundCatalog>,
+    armor: Res<'w, ArmorCatalog>,
+}
+
+impl CatalogParams<'_> {
+    fn get(&self) -> Catalogs<'_> {
+        Catalogs {
+            races: &self.races,
+            classes: &self.classes,
+            backgrounds: &self.backgrounds,
+            armor: &self.armor,
+        }
+    }
+}
+
+/// Problems that keep the character from being created, refreshed every
+/// frame by the ui.
+#[derive(Resource, Default)]
+pub struct CreationErrors(pub Vec<String>);
+
+/// Problems with the inputs of one creation step.
+fn validation_errors(
+    step: CreationStep,
+    builder: &CharacterBuilder,
+    catalogs: &Catalogs,
+) -> Vec<String> {
+    let CharacterBuilder {
+        sheet: newchar,
+        scores,
+        racial_choices: choices,
+        class_choices,
+        background_choices,
+        ..
+    } = builder;
+    let mut errors = Vec::new();
+    let class = catalogs.classes.get(&newchar.class);
+    let background = catalogs.backgrounds.get(&newchar.background);
+    match step {
+        CreationStep::Race => {
+            for t in catalogs.races.traits(&newchar.race) {
+                if let Some(choice) = &t.ability_choices {
+                    if choices.abilities.len() < choice.count {
+                        errors.push(format!(
+                            "Choose {} abilities for your racial increase",
+                            choice.count
+                        ));
+                    }
+                }
+                if choices.skills.len() < t.skill_choices {
+                    errors.push(format!("Choose {} racial skills", t.skill_choices));
+                }
+                if choices.languages.len() < t.extra_languages {
+                    errors.push(format!("Choose {} racial languages", t.extra_languages));
+                }
+            }
+        }
+        CreationStep::Class => {
+            if !(1..=20).contains(&newchar.level) {
+                errors.push("Level must be between 1 and 20".to_string());
+            }
+            if let Some(choice) = class.and_then(|x| x.tool_choices.as_ref()) {
+                if class_choices.tools.len() < choice.count {
+                    errors.push(format!("Choose {} class tools", choice.count));
+                }
+            }
+        }
+        CreationStep::Abilities => {
+            if !scores.is_complete() {
+                errors.push(
+                    match scores.method {
+                        AbilityMethod::PointBuy => "Ability scores are over the point buy budget",
+                        AbilityMethod::Manual => "Ability scores must be between 1 and 30",
+                        _ => "Every ability needs a score assigned",
+                    }
+                    .to_string(),
+                );
+            }
+        }
+        CreationStep::Background => {
+            if let Some(data) = background {
+                if let Some(choice) = &data.tool_choices {
+                    if background_choices.tools.len() < choice.count {
+                        errors.push(format!("Choose {} background tools", choice.count));
+                    }
+                }
+                if background_choices.languages.len() < data.extra_languages {
+                    errors.push(format!(
+                        "Choose {} background languages",
+                        data.extra_languages
+                    ));
+                }
+            }
+        }
+        CreationStep::Skills => {
+            if let Some(data) = class {
+                if class_choices.skills.len() < data.skill_choices {
+                    errors.push(format!("Choose {} class skills", data.skill_choices));
+                }
+            }
+            if let Some(data) = background {
+                let taken =
+                    non_background_skills(catalogs.races, &newchar.race, choices, class_choices);
+                for skill in data.overlapping_skills(&taken) {
+                    if !background_choices.replacements.iter().any(|x| x.0 == skill) {
+                        errors.push(format!("Pick a skill to replace {skill:?}"));
+                    }
+                }
+            }
+        }
+        CreationStep::Equipment => {}
+        CreationStep::Details => {
+            if newchar.name.trim().is_empty() {
+                errors.push("The character needs a name".to_string());
+            }
+        }
+        CreationStep::Review => {}
+    }
+    errors
+}
+
+/// The full character sheet as it will be created, shown on the last step.
+#[allow(clippy::too_many_arguments)]
+fn review_ui(
+    ui: &mut egui::Ui,
+    newchar: &BasePlayer,
+    catalogs: &Catalogs,
+    derived: &Derived,
+    overrides: &Overrides,
+    choices: &RacialChoices,
+    class_choices: &ClassChoices,
+    background_choices: &BackgroundChoices,
+) {
+    let races = &catalogs.races;
+    let traits = races.traits(&newchar.race);
+    let class = catalogs.classes.get(&newchar.class);
+    let background = catalogs.backgrounds.get(&newchar.background);
+    let list = |x: Vec<String>| match x.is_empty() {
+        true => "None".to_string(),
+        false => x.join(", "),
+    };
+
+    ui.heading(&newchar.name);
+    ui.label(format!(
+        "Level {} {} {}, {} background, {}",
+        newchar.level,
+        races.name(&newchar.race),
+        newchar.class,
+        newchar.background,
+        newchar.alignment
+    ));
+    ui.label(format!("Player: {}", newchar.player_name));
+    ui.separator();
+
+    ui.columns(3, |columns| {
+        egui::Grid::new("reviewabilities")
+            .striped(true)
+            .show(&mut columns[0], |ui| {
+                ui.label("Ability");
+                ui.label("Score");
+                ui.label("Mod");
+                ui.label("Save");
                 ui.end_row();
-                ui.label("Insight");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.insight.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("insight")
-                    .selected_text(format!("{:?}", newchar.skills.insight.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.insight.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.insight.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.insight.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
+                for (i, ability) in StatEnum::ABILITIES.iter().enumerate() {
+                    let score = derived.abilities[i];
+                    let (proficiency, save) = &derived.saves[i];
+                    ui.label(format!("{ability:?}"));
+                    ui.label(score.to_string());
+                    ui.label(format!("{:+}", ((score - 10.) / 2.).floor()));
+                    ui.label(match proficiency {
+                        Proficiency::None => format!("{save:+}"),
+                        _ => format!("{save:+} *"),
                     });
-                ui.end_row();
-                ui.label("Intimidation");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.intimidation.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("intimidation")
-                    .selected_text(format!("{:?}", newchar.skills.intimidation.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.intimidation.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.intimidation.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.intimidation.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
-                ui.end_row();
-                ui.label("Investigation");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.investigation.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("investigation")
-                    .selected_text(format!("{:?}", newchar.skills.investigation.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.investigation.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.investigation.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.investigation.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
+                    ui.end_row();
+                }
+            });
+        egui::Grid::new("reviewskills")
+            .striped(true)
+            .show(&mut columns[1], |ui| {
+                for (i, skill) in StatEnum::SKILLS.iter().enumerate() {
+                    let (proficiency, total) = &derived.skills[i];
+                    let total = overrides.skills[i].resolve(*total);
+                    ui.label(format!("{skill:?}"));
+                    ui.label(match proficiency {
+                        Proficiency::None => format!("{total:+}"),
+                        _ => format!("{total:+} *"),
                     });
+                    ui.end_row();
+                }
+            });
+        egui::Grid::new("reviewcombat")
+            .striped(true)
+            .show(&mut columns[2], |ui| {
+                ui.label("Armor Class");
+                ui.label(overrides.ac.resolve(derived.ac).to_string());
                 ui.end_row();
-                ui.label("Medicine");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.medicine.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("medicine")
-                    .selected_text(format!("{:?}", newchar.skills.medicine.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.medicine.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.medicine.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.medicine.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+                ui.label("Max Health");
+                ui.label(overrides.max_health.resolve(derived.max_health).to_string());
                 ui.end_row();
-                ui.label("Nature");
-                ui.add(egui::DragValue::new(&mut newchar.skills.nature.0.stat.base));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("nature")
-                    .selected_text(format!("{:?}", newchar.skills.nature.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.nature.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.nature.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.nature.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+                ui.label("Speed");
+                ui.label(format!("{} ft.", overrides.speed.resolve(derived.speed)));
                 ui.end_row();
-                ui.label("Perception");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.perception.0.stat.base,
+                ui.label("Hit Dice");
+                ui.label(format!(
+                    "{}{}",
+                    newchar.hit_dice.0.number, newchar.hit_dice.0.dice_type
                 ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("perception")
-                    .selected_text(format!("{:?}", newchar.skills.perception.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.perception.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.perception.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.perception.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
                 ui.end_row();
-                ui.label("Performance");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.performance.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("performance")
-                    .selected_text(format!("{:?}", newchar.skills.performance.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.performance.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.performance.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.performance.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+                ui.label("Proficiency Bonus");
+                ui.label(format!("{:+}", derived.proficiency_bonus));
                 ui.end_row();
-                ui.label("Persuasion");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.persuasion.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("persuasion")
-                    .selected_text(format!("{:?}", newchar.skills.persuasion.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.persuasion.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.persuasion.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.persuasion.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+                ui.label("Size");
+                ui.label(races.size(&newchar.race).to_string());
                 ui.end_row();
-                ui.label("Religion");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.religion.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("religion")
-                    .selected_text(format!("{:?}", newchar.skills.religion.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.religion.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.religion.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.religion.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+                ui.label("Darkvision");
+                ui.label(match races.darkvision(&newchar.race) {
+                    Some(dv) => format!("{dv} ft."),
+                    None => "None".to_string(),
+                });
                 ui.end_row();
-                ui.label("Sleight of Hand");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.sleight_of_hand.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("sleight_of_hand")
-                    .selected_text(format!(
-                        "{:?}",
-                        newchar.skills.sleight_of_hand.0.proficiency
-                    ))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.sleight_of_hand.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.sleight_of_hand.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.sleight_of_hand.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+            });
+    });
+    ui.separator();
+
+    let mut armor = Vec::new();
+    let mut weapons = Vec::new();
+    let mut tools = Vec::new();
+    let mut languages = Vec::new();
+    let mut equipment = Vec::new();
+    let mut features = Vec::new();
+    for t in &traits {
+        armor.extend(t.armor.iter().map(|x| x.to_string()));
+        weapons.extend(t.weapons.iter().cloned());
+        tools.extend(t.tools.iter().cloned());
+        if !t.tool_choices.is_empty() {
+            tools.push(choices.tool.clone().unwrap_or(t.tool_choices[0].clone()));
+        }
+        languages.extend(t.languages.iter().map(|x| x.to_string()));
+        languages.extend(
+            choices
+                .languages
+                .iter()
+                .take(t.extra_languages)
+                .map(|x| x.to_string()),
+        );
+        features.extend(t.features.iter().map(|x| x.name.clone()));
+    }
+    if let Some(data) = class {
+        armor.extend(data.armor.iter().map(|x| x.to_string()));
+        if data.simple_weapons {
+            weapons.push("Simple".to_string());
+        }
+        if data.martial_weapons {
+            weapons.push("Martial".to_string());
+        }
+        weapons.extend(data.weapons.iter().cloned());
+        tools.extend(data.tools.iter().cloned());
+        tools.extend(class_choices.tools.iter().cloned());
+        equipment.extend(data.equipment(class_choices));
+        features.extend(
+            data.features
+                .iter()
+                .filter(|x| x.level <= newchar.level)
+                .map(|x| x.name.clone()),
+        );
+    }
+    if let Some(data) = background {
+        tools.extend(data.tools.iter().cloned());
+        tools.extend(background_choices.tools.iter().cloned());
+        languages.extend(background_choices.languages.iter().map(|x| x.to_string()));
+        equipment.extend(data.equipment.iter().cloned());
+        features.push(data.feature.name.clone());
+    }
+    for x in [&mut armor, &mut weapons, &mut tools, &mut languages] {
+        let mut seen = Vec::new();
+        x.retain(|x| match seen.contains(x) {
+            true => false,
+            false => {
+                seen.push(x.clone());
+                true
+            }
+        });
+    }
+
+    egui::Grid::new("reviewproficiencies")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Armor");
+            ui.label(list(armor));
+            ui.end_row();
+            ui.label("Weapons");
+            ui.label(list(weapons));
+            ui.end_row();
+            ui.label("Tools");
+            ui.label(list(tools));
+            ui.end_row();
+            ui.label("Languages");
+            ui.label(list(languages));
+            ui.end_row();
+            ui.label("Equipment");
+            ui.label(list(equipment));
+            ui.end_row();
+            ui.label("Gold");
+            ui.label(format!("{} gp", background.map(|x| x.gold).unwrap_or(0)));
+            ui.end_row();
+            ui.label("Features");
+            ui.label(list(features));
+            ui.end_row();
+            let personality = &background_choices.personality;
+            ui.label("Personality");
+            ui.label(list(
+                personality
+                    .traits
+                    .iter()
+                    .filter(|x| !x.is_empty())
+                    .cloned()
+                    .collect(),
+            ));
+            ui.end_row();
+            ui.label("Ideal");
+            ui.label(&personality.ideal);
+            ui.end_row();
+            ui.label("Bond");
+            ui.label(&personality.bond);
+            ui.end_row();
+            ui.label("Flaw");
+            ui.label(&personality.flaw);
+            ui.end_row();
+        });
+}
+
+fn derived_row(ui: &mut egui::Ui, label: &str, derived: f64, value: &mut Override) {
+    ui.label(label);
+    match value.enabled {
+        true => ui.add(egui::DragValue::new(&mut value.value)),
+        false => ui.label(derived.to_string()),
+    };
+    if ui.checkbox(&mut value.enabled, "Override").changed() && value.enabled {
+        value.value = derived;
+    }
+    ui.end_row();
+}
+
+fn skills_ui(ui: &mut egui::Ui, derived: &Derived, overrides: &mut Overrides) {
+    ui.label("SKILLS");
+    egui::Grid::new("skillgrid")
+        .num_columns(4)
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, skill) in StatEnum::SKILLS.iter().enumerate() {
+                let (proficiency, total) = &derived.skills[i];
+                let value = &mut overrides.skills[i];
+                ui.label(format!("{skill:?}"));
+                ui.label(match proficiency {
+                    Proficiency::None => "",
+                    Proficiency::Proficient => "Proficient",
+                    Proficiency::Expert => "Expert",
+                });
+                match value.enabled {
+                    true => ui.add(egui::DragValue::new(&mut value.value)),
+                    false => ui.label(format!("{total:+}")),
+                };
+                if ui.checkbox(&mut value.enabled, "Override").changed() && value.enabled {
+                    value.value = *total;
+                }
                 ui.end_row();
-                ui.label("Stealth");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.stealth.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("stealth")
-                    .selected_text(format!("{:?}", newchar.skills.stealth.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.stealth.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.stealth.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.stealth.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+            }
+        });
+}
+
+fn abilities_ui(
+    ui: &mut egui::Ui,
+    scores: &mut AbilityScores,
+    races: &RaceCatalog,
+    race: &Race,
+    choices: &RacialChoices,
+) {
+    ui.label("ABILITIES");
+    let mut method = scores.method;
+    egui::ComboBox::from_label("Method")
+        .selected_text(method.to_string())
+        .show_ui(ui, |ui| {
+            for m in AbilityMethod::iter() {
+                ui.selectable_value(&mut method, m, m.to_string());
+            }
+        });
+    if method != scores.method {
+        scores.set_method(method);
+    }
+    match scores.method {
+        AbilityMethod::PointBuy => {
+            ui.label(format!("Points remaining: {}", scores.points_remaining()));
+        }
+        AbilityMethod::Roll => {
+            if ui.button("Roll").clicked() {
+                scores.roll(&mut rand::thread_rng());
+            }
+            for dice in &scores.rolls {
+                ui.label(format!("{dice:?} = {}", drop_lowest(dice)));
+            }
+        }
+        _ => {}
+    }
+
+    egui::Grid::new("abilitygrid")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            ui.label("Base");
+            ui.label("Racial");
+            ui.label("Total");
+            ui.label("Mod");
+            ui.end_row();
+            let pool = scores.pool();
+            for (i, ability) in StatEnum::ABILITIES.iter().enumerate() {
+                ui.label(format!("{ability:?}"));
+                match (scores.method, &pool) {
+                    (AbilityMethod::PointBuy, _) => {
+                        let mut score = scores.scores[i];
+                        let max = (8..=15)
+                            .rev()
+                            .map(|x| x as f64)
+                            .find(|x| {
+                                point_buy_cost(*x).unwrap_or(0) - point_buy_cost(score).unwrap_or(0)
+                                    <= scores.points_remaining()
+                            })
+                            .unwrap_or(score);
+                        ui.add(
+                            egui::DragValue::new(&mut score)
+                                .range(8. ..=max)
+                                .speed(1.0)
+                                .max_decimals(0),
+                        );
+                        scores.scores[i] = score;
+                    }
+                    (_, Some(pool)) => {
+                        let mut slot = scores.assigned[i];
+                        egui::ComboBox::from_id_source(format!("assign{ability:?}"))
+                            .selected_text(match slot {
+                                Some(x) => pool[x].to_string(),
+                                None => "-".to_string(),
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut slot, None, "-");
+                                for (j, value) in pool.iter().enumerate() {
+                                    let used = scores.assigned.iter().any(|x| *x == Some(j));
+                                    let label = match used {
+                                        true => format!("{value} (assigned)"),
+                                        false => value.to_string(),
+                                    };
+                                    ui.selectable_value(&mut slot, Some(j), label);
+                                }
+                            });
+                        if slot != scores.assigned[i] {
+                            scores.assign(i, slot);
+                        }
+                    }
+                    (_, None) => {
+                        ui.add(
+                            egui::DragValue::new(&mut scores.scores[i])
+                                .range(1. ..=30.)
+                                .speed(1.0)
+                                .max_decimals(0),
+                        );
+                    }
+                }
+                let racial = races.ability_bonus(race, choices, ability);
+                let total = scores.scores[i] + racial;
+                ui.label(format!("{racial:+}"));
+                ui.label(total.to_string());
+                ui.label(format!("{:+}", ((total - 10.) / 2.).floor()));
                 ui.end_row();
-                ui.label("Survival");
-                ui.add(egui::DragValue::new(
-                    &mut newchar.skills.survival.0.stat.base,
-                ));
-                ui.label("Proficiency");
-                egui::ComboBox::from_id_source("survival")
-                    .selected_text(format!("{:?}", newchar.skills.survival.0.proficiency))
-                    .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut newchar.skills.survival.0.proficiency,
-                            Proficiency::None,
-                            "None",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.survival.0.proficiency,
-                            Proficiency::Proficient,
-                            "Proficient",
-                        );
-                        ui.selectable_value(
-                            &mut newchar.skills.survival.0.proficiency,
-                            Proficiency::Expert,
-                            "Expert",
-                        );
-                    });
+            }
+        });
+}
+
+fn racial_traits_ui(
+    ui: &mut egui::Ui,
+    races: &RaceCatalog,
+    race: &Race,
+    choices: &mut RacialChoices,
+) {
+    let traits = races.traits(race);
+    ui.heading(races.name(race));
+    egui::Grid::new("racegrid").striped(true).show(ui, |ui| {
+        ui.label("Size");
+        ui.label(races.size(race).to_string());
+        ui.end_row();
+        ui.label("Darkvision");
+        match races.darkvision(race) {
+            Some(dv) => ui.label(format!("{dv} ft.")),
+            None => ui.label("None"),
+        };
+        ui.end_row();
+        for ability in StatEnum::ABILITIES {
+            let bonus = races.ability_increase(race, &ability);
+            if bonus != 0. {
+                ui.label(format!("{ability:?}"));
+                ui.label(format!("{bonus:+}"));
                 ui.end_row();
-            });
+            }
+        }
     });
-    egui::SidePanel::right("rightpanel").show(ctx, |ui| {
-        egui::Grid::new("sidepanelgrid")
-            .min_col_width(50.)
-            .striped(true)
-            .show(ui, |ui| {
-                ui.label("Max Health");
-                ui.add(egui::DragValue::new(&mut newchar.max_health));
-                ui.label("Hit Dice");
-                ui.add(egui::DragValue::new(&mut newchar.hit_dice.0.number));
-                egui::ComboBox::from_id_source("hitdice")
-                    .selected_text(format!("{}", newchar.hit_dice.0.dice_type.to_string()))
-                    .show_ui(ui, |ui| {
-                        for dice in DiceType::iter() {
-                            ui.selectable_value(
-                                &mut newchar.hit_dice.0.dice_type,
-                                dice.clone(),
-                                dice.to_string(),
-                            );
-                        }
-                    });
+    for t in traits {
+        if !t.ancestries.is_empty() {
+            egui::ComboBox::from_label("Draconic Ancestry")
+                .selected_text(choices.ancestry.to_string())
+                .show_ui(ui, |ui| {
+                    for anc in &t.ancestries {
+                        ui.selectable_value(
+                            &mut choices.ancestry,
+                            anc.ancestry,
+                            format!("{} ({})", anc.ancestry, anc.damage_type),
+                        );
+                    }
+                });
+        }
+        if let Some(choice) = &t.ability_choices {
+            ui.label(format!(
+                "Increase {} abilities by {}",
+                choice.count, choice.amount
+            ));
+            for ability in StatEnum::ABILITIES
+                .iter()
+                .filter(|x| !choice.exclude.contains(x))
+            {
+                let mut picked = choices.abilities.contains(ability);
+                let full = choices.abilities.len() >= choice.count;
+                if ui
+                    .add_enabled(
+                        picked || !full,
+                        egui::Checkbox::new(&mut picked, format!("{ability:?}")),
+                    )
+                    .changed()
+                {
+                    toggle(&mut choices.abilities, ability, picked);
+                }
+            }
+        }
+        if t.skill_choices > 0 {
+            ui.label(format!("Choose {} skills", t.skill_choices));
+            for skill in StatEnum::SKILLS.iter() {
+                let mut picked = choices.skills.contains(skill);
+                let full = choices.skills.len() >= t.skill_choices;
+                if ui
+                    .add_enabled(
+                        picked || !full,
+                        egui::Checkbox::new(&mut picked, format!("{skill:?}")),
+                    )
+                    .changed()
+                {
+                    toggle(&mut choices.skills, skill, picked);
+                }
+            }
+        }
+        if !t.tool_choices.is_empty() {
+            let selected = choices
+                .tool
+                .clone()
+                .unwrap_or_else(|| t.tool_choices[0].clone());
+            egui::ComboBox::from_label("Tool Proficiency")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for tool in &t.tool_choices {
+                        ui.selectable_value(&mut choices.tool, Some(tool.clone()), tool);
+                    }
+                });
+        }
+        if t.extra_languages > 0 {
+            ui.label(format!("Choose {} extra languages", t.extra_languages));
+            for language in Language::iter() {
+                if t.languages.contains(&language) {
+                    continue;
+                }
+                let mut picked = choices.languages.contains(&language);
+                let full = choices.languages.len() >= t.extra_languages;
+                if ui
+                    .add_enabled(
+                        picked || !full,
+                        egui::Checkbox::new(&mut picked, language.to_string()),
+                    )
+                    .changed()
+                {
+                    toggle(&mut choices.languages, &language, picked);
+                }
+            }
+        }
+        for feature in &t.features {
+            ui.label(&feature.name).on_hover_text(&feature.description);
+        }
+    }
+}
+
+fn background_ui(ui: &mut egui::Ui, data: &BackgroundData, choices: &mut BackgroundChoices) {
+    ui.heading(data.background.to_string());
+    egui::Grid::new("backgroundgrid")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Skills");
+            ui.label(
+                data.skills
+                    .iter()
+                    .map(|x| format!("{x:?}"))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            );
+            ui.end_row();
+            if !data.tools.is_empty() {
+                ui.label("Tools");
+                ui.label(data.tools.join(", "));
                 ui.end_row();
-                ui.label("Speed");
-                ui.add(egui::DragValue::new(&mut newchar.speed.0));
-                ui.label("Armor Class");
-                ui.add(egui::DragValue::new(&mut newchar.ac.0.total));
-            });
+            }
+            ui.label("Equipment");
+            ui.label(data.equipment.join(", "));
+            ui.end_row();
+            ui.label("Gold");
+            ui.label(format!("{} gp", data.gold));
+            ui.end_row();
+            ui.label("Feature");
+            ui.label(&data.feature.name)
+                .on_hover_text(&data.feature.description);
+            ui.end_row();
+        });
+
+    if let Some(choice) = &data.tool_choices {
+        ui.label(format!("Choose {} tools", choice.count));
+        for tool in &choice.options {
+            let mut picked = choices.tools.contains(tool);
+            let full = choices.tools.len() >= choice.count;
+            if ui
+                .add_enabled(picked || !full, egui::Checkbox::new(&mut picked, tool))
+                .changed()
+            {
+                toggle(&mut choices.tools, tool, picked);
+            }
+        }
+    }
+    if data.extra_languages > 0 {
+        ui.label(format!("Choose {} extra languages", data.extra_languages));
+        for language in Language::iter() {
+            let mut picked = choices.languages.contains(&language);
+            let full = choices.languages.len() >= data.extra_languages;
+            if ui
+                .add_enabled(
+                    picked || !full,
+                    egui::Checkbox::new(&mut picked, language.to_string()),
+                )
+                .changed()
+            {
+                toggle(&mut choices.languages, &language, picked);
+            }
+        }
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Personality");
+        if ui.button("Roll for me").clicked() {
+            choices.personality = data.roll_personality(&mut rand::thread_rng());
+        }
     });
-    egui::TopBottomPanel::bottom("bottompannel").show(ctx, |ui| {
-        ui.vertical_centered_justified(|ui| {
-            if ui.button("Create").clicked() {
-                commands.trigger(CreateCharacter);
+    let personality = &mut choices.personality;
+    personality.traits.resize(2, String::new());
+    for (i, chosen) in personality.traits.iter_mut().enumerate() {
+        personality_combo(
+            ui,
+            &format!("Trait {}", i + 1),
+            chosen,
+            &data.personality_traits,
+        );
+    }
+    personality_combo(ui, "Ideal", &mut personality.ideal, &data.ideals);
+    personality_combo(ui, "Bond", &mut personality.bond, &data.bonds);
+    personality_combo(ui, "Flaw", &mut personality.flaw, &data.flaws);
+}
+
+fn skill_replacement_ui(
+    ui: &mut egui::Ui,
+    data: &BackgroundData,
+    taken: &[StatEnum],
+    choices: &mut BackgroundChoices,
+) {
+    // A proficiency granted twice may be exchanged for another of the same kind.
+    let overlaps = data.overlapping_skills(taken);
+    choices.replacements.retain(|x| overlaps.contains(&x.0));
+    for skill in overlaps {
+        ui.label(format!("You already have {skill:?}, pick another skill"));
+        let current = choices
+            .replacements
+            .iter()
+            .find(|x| x.0 == skill)
+            .map(|x| x.1.clone());
+        let options = StatEnum::SKILLS
+            .into_iter()
+            .filter(|x| {
+                !taken.contains(x)
+                    && !data.skills.contains(x)
+                    && !choices
+                        .replacements
+                        .iter()
+                        .any(|r| &r.1 == x && r.0 != skill)
+            })
+            .collect::<Vec<StatEnum>>();
+        egui::ComboBox::from_id_source(format!("replace{skill:?}"))
+            .selected_text(match &current {
+                Some(x) => format!("{x:?}"),
+                None => "Choose".to_string(),
+            })
+            .show_ui(ui, |ui| {
+                for other in &options {
+                    if ui
+                        .selectable_label(current.as_ref() == Some(other), format!("{other:?}"))
+                        .clicked()
+                    {
+                        choices.replacements.retain(|x| x.0 != skill);
+                        choices.replacements.push((skill.clone(), other.clone()));
+                    }
+                }
+            });
+    }
+}
+
+fn personality_combo(ui: &mut egui::Ui, label: &str, chosen: &mut String, table: &[String]) {
+    egui::ComboBox::from_label(label)
+        .width(300.)
+        .selected_text(chosen.clone())
+        .show_ui(ui, |ui| {
+            for entry in table {
+                ui.selectable_value(chosen, entry.clone(), entry);
             }
         });
+}
+
+fn class_ui(ui: &mut egui::Ui, data: &ClassData, level: i64, choices: &mut ClassChoices) {
+    ui.heading(data.class.to_string());
+    egui::Grid::new("classgrid").striped(true).show(ui, |ui| {
+        ui.label("Hit Die");
+        ui.label(data.hit_die.to_string());
+        ui.end_row();
+        ui.label("Saving Throws");
+        ui.label(
+            data.saving_throws
+                .iter()
+                .map(|x| format!("{x:?}"))
+                .collect::<Vec<String>>()
+                .join(", "),
+        );
+        ui.end_row();
+        ui.label("Armor");
+        ui.label(
+            data.armor
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        );
+        ui.end_row();
+        ui.label("Weapons");
+        let mut weapons = Vec::new();
+        if data.simple_weapons {
+            weapons.push("Simple".to_string());
+        }
+        if data.martial_weapons {
+            weapons.push("Martial".to_string());
+        }
+        weapons.extend(data.weapons.iter().cloned());
+        ui.label(weapons.join(", "));
+        ui.end_row();
+        if !data.tools.is_empty() {
+            ui.label("Tools");
+            ui.label(data.tools.join(", "));
+            ui.end_row();
+        }
     });
+    if let Some(choice) = &data.tool_choices {
+        ui.label(format!("Choose {} tools", choice.count));
+        for tool in &choice.options {
+            let mut picked = choices.tools.contains(tool);
+            let full = choices.tools.len() >= choice.count;
+            if ui
+                .add_enabled(picked || !full, egui::Checkbox::new(&mut picked, tool))
+                .changed()
+            {
+                toggle(&mut choices.tools, tool, picked);
+            }
+        }
+    }
+    ui.label("Features");
+    for feature in data.features.iter().filter(|x| x.level <= level.max(1)) {
+        ui.label(&feature.name).on_hover_text(&feature.description);
+    }
+}
+
+fn class_skills_ui(ui: &mut egui::Ui, data: &ClassData, choices: &mut ClassChoices) {
+    ui.label(format!("Choose {} skills", data.skill_choices));
+    for skill in &data.skill_list {
+        let mut picked = choices.skills.contains(skill);
+        let full = choices.skills.len() >= data.skill_choices;
+        if ui
+            .add_enabled(
+                picked || !full,
+                egui::Checkbox::new(&mut picked, format!("{skill:?}")),
+            )
+            .changed()
+        {
+            toggle(&mut choices.skills, skill, picked);
+        }
+    }
 }
 
-#[derive(Resource, Default, Reflect)]
-pub struct BasePlayer {
-    pub player_tag: Player,
-    pub unit_tag: Unit,
-    pub name: String,
-    pub player_name: String,
-    pub ac: f64,
-    pub speed: f64,
-    pub strength: f64,
-    pub constitution: f64,
-    pub dexterity: f64,
-    pub intelligence: f64,
-    pub wisdom: f64,
-    pub charisma: f64,
-    pub athletics: f64,
-    pub acrobatics: f64,
-    pub sleight_of_hand: f64,
-    pub stealth: f64,
-    pub arcana: f64,
-    pub history: f64,
-    pub investigation: f64,
-    pub nature: f64,
-    pub religion: f64,
-    pub animal_handling: f64,
-    pub insight: f64,
-    pub medicine: f64,
-    pub perception: f64,
-    pub survival: f64,
-    pub deception: f64,
-    pub intimidation: f64,
-    pub performance: f64,
-    pub persuasion: f64,
-    pub race: Race,
-    pub class: Class,
-    pub wep_profs: WeaponProficiencies,
-    pub prof_bonus: ProficiencyBonus,
-    pub health: f64,
-    pub max_health: f64,
-    pub background: Background,
-    pub alignment: Alignment,
-    pub xp: f64,
-    pub level: f64,
-    pub hit_dice: HitDice,
-    pub settings: SettingsBundle,
+fn class_equipment_ui(ui: &mut egui::Ui, data: &ClassData, choices: &mut ClassChoices) {
+    ui.label("Starting Equipment");
+    choices.equipment.resize(data.equipment.len(), 0);
+    for (i, choice) in data.equipment.iter().enumerate() {
+        ui.horizontal(|ui| {
+            for (j, alternative) in choice.iter().enumerate() {
+                ui.radio_value(&mut choices.equipment[i], j, alternative.join(", "));
+            }
+        });
+    }
+}
+
+fn toggle<T: PartialEq + Clone>(list: &mut Vec<T>, value: &T, on: bool) {
+    if on {
+        if !list.contains(value) {
+            list.push(value.clone());
+        }
+    } else {
+        list.retain(|x| x != value);
+    }
 }
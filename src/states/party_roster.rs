@@ -0,0 +1,86 @@
+use crate::combat::Rest;
+use crate::components::*;
+use crate::party::{ActiveCharacter, Party};
+use crate::states::battle_map::Selection;
+use crate::AppState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct PartyRosterPlugin;
+
+impl Plugin for PartyRosterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (follow_selection, roster)
+                .chain()
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+type Member = (
+    &'static UnitName,
+    &'static Level,
+    &'static Health,
+    &'static MaxHealth,
+);
+
+/// Picking a player's token on the battle map makes them active.
+fn follow_selection(
+    selection: Res<Selection>,
+    mut active: ResMut<ActiveCharacter>,
+    players: Query<(), With<Player>>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    if let Some(unit) = selection.unit.filter(|x| players.contains(*x)) {
+        active.set_if_neq(ActiveCharacter(Some(unit)));
+    }
+}
+
+/// Every member of the party. Clicking one makes them active and selects
+/// their token. The party rests from here too.
+fn roster(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut party: Party,
+    members: Query<Member>,
+    mut selection: ResMut<Selection>,
+) {
+    let current = party.active();
+    let mut picked = None;
+    egui::Window::new("Party")
+        .collapsible(true)
+        .show(contexts.ctx_mut(), |ui| {
+            for member in party.members() {
+                let Ok((name, level, health, max_health)) = members.get(member) else {
+                    continue;
+                };
+                let label = format!(
+                    "{}, level {}: {}/{} HP",
+                    name.0, level.0, health.0, max_health.0.total
+                );
+                if ui
+                    .selectable_label(current == Some(member), label)
+                    .clicked()
+                {
+                    picked = Some(member);
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Short rest").clicked() {
+                    commands.trigger(Rest { long: false });
+                }
+                if ui.button("Long rest").clicked() {
+                    commands.trigger(Rest { long: true });
+                }
+            });
+        });
+    if let Some(member) = picked.filter(|x| current != Some(*x)) {
+        party.active.0 = Some(member);
+        selection.unit = Some(member);
+    }
+}
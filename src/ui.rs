@@ -1,3 +1,9 @@
+use crate::components::RootUI;
 use bevy::prelude::*;
 
 // const DefaultButton: ButtonBundle = ButtonBundle {};
+
+pub fn despawn_ui(mut commands: Commands, ui_root: Query<Entity, With<RootUI>>) {
+    let root = ui_root.single();
+    commands.entity(root).despawn_descendants();
+}
@@ -0,0 +1,260 @@
+//! Maps in the Universal VTT format that Dungeondraft exports as `.dd2vtt`:
+//! JSON holding the map's image, its walls as lines, its doors and its
+//! lights, all measured in grid squares from the image's top left. The
+//! importer lays them over the battle map's squares, so a map comes in
+//! ready to play.
+
+use crate::map::{Background, BattleMap, Door, GridPosition, CELL_FEET, MAP_IMAGE_DIR};
+use crate::saves::{ensure_dir, io, slot_name, SaveError};
+use crate::vision::{LightLevel, LightSource};
+use base64::Engine;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The file extensions Universal VTT files go by.
+pub const UVTT_EXTENSIONS: [&str; 3] = ["dd2vtt", "df2vtt", "uvtt"];
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl From<Point> for Vec2 {
+    fn from(point: Point) -> Self {
+        Vec2::new(point.x, point.y)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Resolution {
+    /// Where the grid starts, in squares.
+    #[serde(default)]
+    pub map_origin: Point,
+    /// The size of the map, in squares.
+    pub map_size: Point,
+    pub pixels_per_grid: f32,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Portal {
+    pub position: Point,
+    /// The two ends of the doorway.
+    pub bounds: Vec<Point>,
+    #[serde(default)]
+    pub closed: bool,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct UvttLight {
+    pub position: Point,
+    /// How far the light reaches, in squares.
+    pub range: f32,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Environment {
+    /// The light that's everywhere, as hex ARGB like `"ff7f7f7f"`.
+    #[serde(default)]
+    pub ambient_light: String,
+}
+
+/// A Universal VTT file. Fields the battle map has no use for are left out.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct UvttFile {
+    pub resolution: Resolution,
+    /// Walls, as lines through the points of each.
+    #[serde(default)]
+    pub line_of_sight: Vec<Vec<Point>>,
+    /// Walls around objects, like pillars, from format 0.3 on.
+    #[serde(default)]
+    pub objects_line_of_sight: Vec<Vec<Point>>,
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+    #[serde(default)]
+    pub lights: Vec<UvttLight>,
+    #[serde(default)]
+    pub environment: Environment,
+    /// The map's image, base64 encoded.
+    #[serde(default)]
+    pub image: String,
+}
+
+/// How many points along a square's width walls are sampled at.
+const SAMPLES: f32 = 8.;
+
+impl UvttFile {
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    fn height(&self) -> i32 {
+        self.resolution.map_size.y.round() as i32
+    }
+
+    /// The square `point` falls in. The file counts down from the top and
+    /// the battle map up from the bottom, so a point on the edge between
+    /// two squares takes the one above it in the image, or to its right.
+    fn cell(&self, point: Vec2) -> GridPosition {
+        let point = point - Vec2::from(self.resolution.map_origin);
+        GridPosition::new(
+            point.x.floor() as i32,
+            (self.height() as f32 - point.y).floor() as i32,
+        )
+    }
+
+    /// Whether `point` is on the outside edge of the map, where a wall
+    /// belongs to no square: the edge already stops everyone.
+    fn on_edge(&self, point: Vec2) -> bool {
+        const EDGE: f32 = 1e-4;
+        let point = point - Vec2::from(self.resolution.map_origin);
+        let size = Vec2::from(self.resolution.map_size);
+        [point.x, point.y, point.x - size.x, point.y - size.y]
+            .iter()
+            .any(|x| x.abs() < EDGE)
+    }
+
+    /// The squares the line from `from` to `to` runs through, and the
+    /// squares at either end if `ends`. Walls keep their ends so they close
+    /// up at corners; a doorway only spans the squares between its ends.
+    /// Points on the map's outside edge are left out, so a wall drawn round
+    /// the map doesn't wall off a row or column on any side.
+    fn cells_along(&self, from: Point, to: Point, ends: bool) -> Vec<GridPosition> {
+        let (from, to) = (Vec2::from(from), Vec2::from(to));
+        let steps = ((to - from).length() * SAMPLES).ceil().max(1.) as i32;
+        let samples = (0..steps).map(|i| (i as f32 + 0.5) / steps as f32);
+        let ends = [0., 1.].into_iter().filter(|_| ends);
+        let mut cells = samples
+            .chain(ends)
+            .map(|t| from.lerp(to, t))
+            .filter(|x| !self.on_edge(*x))
+            .map(|x| self.cell(x))
+            .collect::<Vec<_>>();
+        cells.sort_by_key(|x| (x.x, x.y));
+        cells.dedup();
+        cells
+    }
+
+    /// The map's ambient light, going by how bright its colour is. Maps
+    /// without one are bright.
+    fn light(&self) -> Result<LightLevel, String> {
+        let hex = self.environment.ambient_light.trim_start_matches('#');
+        if hex.is_empty() {
+            return Ok(LightLevel::Bright);
+        }
+        let rgb = hex
+            .get(hex.len().saturating_sub(6)..)
+            .and_then(|x| u32::from_str_radix(x, 16).ok())
+            .ok_or_else(|| format!("the ambient light {hex:?} is not a hex colour"))?;
+        let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.;
+        let brightness = 0.299 * channel(16) + 0.587 * channel(8) + 0.114 * channel(0);
+        Ok(match brightness {
+            x if x >= 0.6 => LightLevel::Bright,
+            x if x >= 0.25 => LightLevel::Dim,
+            _ => LightLevel::Dark,
+        })
+    }
+
+    /// The battle map this file describes, called `name`, with `image` as
+    /// its background. Walls cover every square their lines run through,
+    /// and doors replace the walls they sit in. Lights shed bright light
+    /// for the first half of their range and dim light for the rest.
+    pub fn to_map(&self, name: &str, image: Option<String>) -> Result<BattleMap, String> {
+        let Resolution {
+            map_origin,
+            pixels_per_grid,
+            ..
+        } = self.resolution;
+        let mut map = BattleMap {
+            name: name.to_string(),
+            width: self.resolution.map_size.x.round() as i32,
+            height: self.height(),
+            background: image.map(|image| Background {
+                image,
+                pixels_per_square: pixels_per_grid,
+                offset: (
+                    map_origin.x * pixels_per_grid,
+                    map_origin.y * pixels_per_grid,
+                ),
+            }),
+            light: self.light()?,
+            ..default()
+        };
+        let walls = self.line_of_sight.iter().chain(&self.objects_line_of_sight);
+        for points in walls {
+            for line in points.windows(2) {
+                map.walls.extend(self.cells_along(line[0], line[1], true));
+            }
+        }
+        for portal in &self.portals {
+            let door = match portal.closed {
+                true => Door::Closed,
+                false => Door::Open,
+            };
+            let cells = match portal.bounds.as_slice() {
+                [from, to] => self.cells_along(*from, *to, false),
+                _ => vec![self.cell(portal.position.into())],
+            };
+            for cell in cells {
+                map.walls.remove(&cell);
+                map.doors.insert(cell, door);
+            }
+        }
+        for light in &self.lights {
+            let feet = light.range as f64 * CELL_FEET / 2.;
+            let pos = self.cell(light.position.into());
+            map.lights.insert(pos, LightSource::new(feet, feet));
+        }
+        let (width, height) = (map.width, map.height);
+        let on_map =
+            |pos: &GridPosition| (0..width).contains(&pos.x) && (0..height).contains(&pos.y);
+        map.walls.retain(on_map);
+        map.doors.retain(|x, _| on_map(x));
+        map.lights.retain(|x, _| on_map(x));
+        Ok(map)
+    }
+
+    /// The embedded image and the extension it should be saved with.
+    pub fn image(&self) -> Result<Option<(Vec<u8>, &'static str)>, String> {
+        if self.image.is_empty() {
+            return Ok(None);
+        }
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(self.image.trim())
+            .map_err(|e| e.to_string())?;
+        let extension = match bytes.as_slice() {
+            [0x89, b'P', b'N', b'G', ..] => "png",
+            [0xff, 0xd8, ..] => "jpg",
+            _ => return Err("the image is not a PNG or JPG".to_string()),
+        };
+        Ok(Some((bytes, extension)))
+    }
+}
+
+/// Reads a Universal VTT file into a battle map named after it, saving its
+/// image into the asset folder under `assets`.
+pub fn import_uvtt(assets: &Path, file: &Path) -> Result<BattleMap, SaveError> {
+    let parse_error = |e: String| SaveError::Parse(file.to_path_buf(), e);
+    let text = io(file, fs::read_to_string(file))?;
+    let uvtt = UvttFile::from_json(&text).map_err(parse_error)?;
+    let name = file
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or("Imported map");
+    let image = uvtt.image().map_err(parse_error)?;
+    let file_name = image
+        .as_ref()
+        .map(|(_, extension)| format!("{}.{extension}", slot_name(name)));
+    let asset = file_name.as_ref().map(|x| format!("{MAP_IMAGE_DIR}/{x}"));
+    // Nothing is written until the whole file has been read.
+    let map = uvtt.to_map(name, asset).map_err(parse_error)?;
+    if let Some(((bytes, _), file_name)) = image.zip(file_name) {
+        let dir = assets.join(MAP_IMAGE_DIR);
+        ensure_dir(&dir)?;
+        let to = dir.join(file_name);
+        io(&to, fs::write(&to, bytes))?;
+    }
+    Ok(map)
+}
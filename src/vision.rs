@@ -0,0 +1,237 @@
+//! Light and sight on the battle map. Squares are bright, dim or dark from
+//! the map and any light sources nearby, and each unit makes them out as
+//! well as its darkvision lets it. Players remember what they've seen as
+//! their fog of war.
+
+use crate::components::*;
+use crate::map::{line_of_sight, BattleMap, DiagonalRule, GridPosition};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+pub struct VisionPlugin;
+
+impl Plugin for VisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LightSource>();
+        app.add_systems(Update, update_fog_of_war);
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum LightLevel {
+    Dark,
+    Dim,
+    #[default]
+    Bright,
+}
+
+impl LightLevel {
+    /// One step brighter, as darkvision sees it.
+    fn brighter(self) -> Self {
+        match self {
+            LightLevel::Dark => LightLevel::Dim,
+            _ => LightLevel::Bright,
+        }
+    }
+}
+
+/// Sheds bright light out to `bright` feet and dim light for `dim` feet
+/// past that. Units carry it; lights fixed to the map are in
+/// `BattleMap::lights`.
+#[derive(Component, Reflect, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct LightSource {
+    pub bright: f64,
+    pub dim: f64,
+}
+
+impl LightSource {
+    pub const CANDLE: Self = Self::new(5., 5.);
+    pub const TORCH: Self = Self::new(20., 20.);
+    /// A hooded lantern, with its hood up.
+    pub const LANTERN: Self = Self::new(30., 30.);
+    /// The Light cantrip, cast on something the unit carries.
+    pub const LIGHT_CANTRIP: Self = Self::new(20., 20.);
+
+    pub const KINDS: [(&'static str, Self); 4] = [
+        ("Candle", Self::CANDLE),
+        ("Torch", Self::TORCH),
+        ("Lantern", Self::LANTERN),
+        ("Light", Self::LIGHT_CANTRIP),
+    ];
+
+    pub const fn new(bright: f64, dim: f64) -> Self {
+        Self { bright, dim }
+    }
+
+    /// The light it sheds `distance` feet away.
+    pub fn light_at(&self, distance: f64) -> LightLevel {
+        match distance {
+            x if x <= self.bright => LightLevel::Bright,
+            x if x <= self.bright + self.dim => LightLevel::Dim,
+            _ => LightLevel::Dark,
+        }
+    }
+}
+
+/// How well lit `cell` is: the map's own light, brightened by any of
+/// `sources` that can shine on it past the walls.
+pub fn light_level(
+    map: &BattleMap,
+    rule: DiagonalRule,
+    sources: impl IntoIterator<Item = (GridPosition, LightSource)>,
+    cell: GridPosition,
+) -> LightLevel {
+    sources
+        .into_iter()
+        .chain(map.lights.iter().map(|(pos, source)| (*pos, *source)))
+        .filter(|(pos, _)| line_of_sight(map, *pos, cell))
+        .map(|(pos, source)| source.light_at(pos.distance(cell, rule)))
+        .fold(map.light_at(cell), LightLevel::max)
+}
+
+/// How a unit with `darkvision` feet of it makes out a square lit by
+/// `light`, `distance` feet away. Within range it sees dim light as bright
+/// and darkness as dim. Dark means it can't see the square.
+pub fn perceived(light: LightLevel, distance: f64, darkvision: f64) -> LightLevel {
+    match darkvision > 0. && distance <= darkvision {
+        true => light.brighter(),
+        false => light,
+    }
+}
+
+/// What each unit can see of the map.
+#[derive(SystemParam)]
+pub struct Vision<'w, 's> {
+    pub map: Res<'w, BattleMap>,
+    pub rule: Res<'w, DiagonalRule>,
+    pub sources: Query<'w, 's, (&'static GridPosition, &'static LightSource)>,
+    pub viewers: Query<'w, 's, (&'static GridPosition, Option<&'static DarkVision>)>,
+}
+
+impl Vision<'_, '_> {
+    pub fn light_at(&self, cell: GridPosition) -> LightLevel {
+        let sources = self.sources.iter().map(|(pos, source)| (*pos, *source));
+        light_level(&self.map, *self.rule, sources, cell)
+    }
+
+    /// How well lit every open square is, worked out once for everyone
+    /// looking at the map.
+    pub fn light_grid(&self) -> HashMap<GridPosition, LightLevel> {
+        (0..self.map.width)
+            .flat_map(|x| (0..self.map.height).map(move |y| GridPosition::new(x, y)))
+            .filter(|x| !self.map.blocks(*x))
+            .map(|x| (x, self.light_at(x)))
+            .collect()
+    }
+
+    /// How well `viewer` makes out `cell`, or Dark if it can't see it.
+    /// Units off the map see everything.
+    pub fn sees(&self, viewer: Entity, cell: GridPosition) -> LightLevel {
+        self.sees_lit(viewer, cell, || self.light_at(cell))
+    }
+
+    /// `sees`, with the light on `cell` only worked out once the viewer has
+    /// a line of sight to it.
+    fn sees_lit(
+        &self,
+        viewer: Entity,
+        cell: GridPosition,
+        light: impl FnOnce() -> LightLevel,
+    ) -> LightLevel {
+        let Ok((pos, darkvision)) = self.viewers.get(viewer) else {
+            return LightLevel::Bright;
+        };
+        if !line_of_sight(&self.map, *pos, cell) {
+            return LightLevel::Dark;
+        }
+        let darkvision = darkvision.map_or(0., |x| x.0.total);
+        perceived(light(), pos.distance(cell, *self.rule), darkvision)
+    }
+
+    /// Whether `viewer` can see `target`. Units off the map can always be
+    /// seen.
+    pub fn can_see(&self, viewer: Entity, target: Entity) -> bool {
+        match self.viewers.get(target) {
+            Ok((pos, _)) => self.sees(viewer, *pos) > LightLevel::Dark,
+            Err(_) => true,
+        }
+    }
+
+    /// Every square `viewer` can see, and the walls and doors next to them,
+    /// going by the light in `lights` from `light_grid`.
+    pub fn visible_cells(
+        &self,
+        viewer: Entity,
+        lights: &HashMap<GridPosition, LightLevel>,
+    ) -> HashSet<GridPosition> {
+        let cells = (0..self.map.width)
+            .flat_map(|x| (0..self.map.height).map(move |y| GridPosition::new(x, y)));
+        let (blocking, open) = cells.partition::<Vec<_>, _>(|x| self.map.blocks(*x));
+        let mut visible = open
+            .into_iter()
+            .filter(|x| {
+                let light = || lights.get(x).copied().unwrap_or_else(|| self.light_at(*x));
+                self.sees_lit(viewer, *x, light) > LightLevel::Dark
+            })
+            .collect::<HashSet<_>>();
+        let walls = blocking
+            .into_iter()
+            .filter(|wall| visible.iter().any(|x| x.cells_to(*wall) == 1))
+            .collect::<Vec<_>>();
+        visible.extend(walls);
+        visible
+    }
+}
+
+/// What a player can see of the map now, and everything they've seen
+/// before.
+#[derive(Component, Default, Debug, Clone)]
+pub struct FogOfWar {
+    pub visible: HashSet<GridPosition>,
+    pub explored: HashSet<GridPosition>,
+}
+
+type Moved = Or<(
+    Changed<GridPosition>,
+    Changed<LightSource>,
+    Changed<DarkVision>,
+)>;
+
+type PlacedPlayer = (With<Player>, With<GridPosition>);
+
+/// Works out what each player sees whenever somebody moves or the light
+/// changes.
+fn update_fog_of_war(
+    mut commands: Commands,
+    vision: Vision,
+    mut players: Query<(Entity, Option<&mut FogOfWar>), PlacedPlayer>,
+    moved: Query<(), Moved>,
+    mut put_out: RemovedComponents<LightSource>,
+) {
+    let changed = vision.map.is_changed() || !moved.is_empty() || put_out.read().count() > 0;
+    let mut lights = None;
+    for (player, fog) in &mut players {
+        match fog {
+            Some(mut fog) if changed => {
+                let lights = lights.get_or_insert_with(|| vision.light_grid());
+                let visible = vision.visible_cells(player, lights);
+                fog.explored.extend(visible.iter().copied());
+                fog.visible = visible;
+            }
+            Some(_) => {}
+            None => {
+                let lights = lights.get_or_insert_with(|| vision.light_grid());
+                let visible = vision.visible_cells(player, lights);
+                commands.entity(player).insert(FogOfWar {
+                    explored: visible.clone(),
+                    visible,
+                });
+            }
+        }
+    }
+}
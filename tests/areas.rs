@@ -0,0 +1,117 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use newtable::areas::*;
+use newtable::components::*;
+use newtable::map::{BattleMap, GridPosition};
+use std::f32::consts::FRAC_PI_2;
+
+#[test]
+fn templates_cover_squares_by_the_grid_rules() {
+    let at = GridPosition::new;
+    let mut map = BattleMap::default();
+
+    // A 20 ft radius from the corner between four squares.
+    let fireball = Template::new(Shape::Sphere { radius: 20. }, Vec2::new(10., 7.));
+    let cells = fireball.cells(&map);
+    assert!(cells.contains(&at(10, 7)) && cells.contains(&at(9, 6)));
+    assert!(cells.contains(&at(6, 7)) && cells.contains(&at(13, 7)));
+    assert!(!cells.contains(&at(5, 7)) && !cells.contains(&at(14, 7)));
+    assert!(!cells.contains(&at(13, 10)));
+
+    // Only the squares at least half inside the cone count.
+    let cone = Template::new(Shape::Cone { length: 15. }, Vec2::new(5., 5.));
+    let mut cells = cone.cells(&map);
+    cells.sort_by_key(|x| (x.x, x.y));
+    assert_eq!(cells, vec![at(6, 4), at(6, 5), at(7, 4), at(7, 5)]);
+
+    // A line from the middle of a square's side runs along one row, and
+    // turns with the template.
+    let line = Shape::Line {
+        length: 30.,
+        width: 5.,
+    };
+    let cells = Template::new(line, Vec2::new(2., 3.5)).cells(&map);
+    assert_eq!(cells, (2..8).map(|x| at(x, 3)).collect::<Vec<_>>());
+    let mut turned = Template::new(line, Vec2::new(2.5, 3.));
+    turned.rotate(FRAC_PI_2);
+    assert_eq!(
+        turned.cells(&map),
+        (3..9).map(|y| at(2, y)).collect::<Vec<_>>()
+    );
+
+    // Walls stop the blast.
+    map.walls.extend((0..map.height).map(|y| at(7, y)));
+    let cells = fireball.cells(&map);
+    assert!(!cells.contains(&at(6, 7)) && !cells.contains(&at(7, 7)));
+    assert!(cells.contains(&at(8, 7)));
+}
+
+#[test]
+fn area_effects_deal_half_damage_on_a_save() {
+    let mut harness = Harness::new(
+        r#"(
+            seed: 5,
+            units: [
+                (name: "Brom", side: Player, max_health: 100),
+                (name: "Goblin", max_health: 100),
+                (name: "Wolf", max_health: 100),
+            ],
+        )"#,
+    );
+    harness.record::<SaveResult>();
+    for (unit, x) in harness.units.clone().into_iter().zip([10, 9, 18]) {
+        harness
+            .world()
+            .entity_mut(unit)
+            .insert(GridPosition::new(x, 7));
+    }
+    let fireball = |harness: &mut Harness, dc, half_on_save| {
+        harness.take::<SaveResult>();
+        harness.trigger(AreaEffect {
+            template: Template::new(Shape::Sphere { radius: 20. }, Vec2::new(10., 7.)),
+            save: StatEnum::Dexterity,
+            dc,
+            damage: Dice {
+                dice_type: DiceType::D6,
+                number: 8,
+            },
+            damage_type: DamageType::Fire,
+            half_on_save,
+        });
+        harness.take::<SaveResult>()
+    };
+
+    // Nobody makes a DC 30 save with +0, and the damage is rolled once.
+    let results = fireball(&mut harness, 30, true);
+    assert_eq!(results.len(), 2);
+    let full = results[0].damage;
+    assert!(full >= 8.);
+    assert!(results.iter().all(|x| !x.saved && x.damage == full));
+    assert_eq!(harness.get::<Health>("Brom").0, 100. - full);
+    assert_eq!(harness.get::<Health>("Wolf").0, 100.);
+
+    // Everyone makes a DC 1 save and takes half, rounded down.
+    let results = fireball(&mut harness, 1, true);
+    let half = results[0].damage;
+    assert!(results.iter().all(|x| x.saved && x.damage == half));
+    assert!(half >= 4.);
+    assert_eq!(harness.get::<Health>("Goblin").0, 100. - full - half);
+
+    // Some effects do nothing at all on a save.
+    let results = fireball(&mut harness, 1, false);
+    assert!(results.iter().all(|x| x.saved && x.damage == 0.));
+    assert_eq!(harness.get::<Health>("Goblin").0, 100. - full - half);
+
+    // Resisting fire halves what's left after the save.
+    let goblin = harness.unit("Goblin").unwrap();
+    harness
+        .world()
+        .entity_mut(goblin)
+        .insert(Resistances(vec![DamageType::Fire]));
+    let results = fireball(&mut harness, 30, true);
+    let (brom, goblin) = (&results[0], &results[1]);
+    assert_eq!(goblin.unit, harness.unit("Goblin").unwrap());
+    assert_eq!(goblin.damage, (brom.damage / 2.).floor());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::Harness;
+use newtable::autosave::{Autosave, AutosaveReason};
+use newtable::combat::{Downed, Rest, TakeDamage};
+use newtable::components::*;
+use newtable::saves::{scene_path, PendingSaves, SaveDir};
+
+const PARTY: &str = r#"(
+    seed: 7,
+    units: [
+        (name: "Brom", side: Player, level: 3, abilities: (15, 14, 12, 8, 13, 10), max_health: 28),
+        (name: "Goblin", max_health: 7, ac: 15),
+    ],
+)"#;
+
+fn reasons(harness: &mut Harness) -> Vec<AutosaveReason> {
+    harness
+        .take::<Autosave>()
+        .into_iter()
+        .map(|x| x.0)
+        .collect()
+}
+
+#[test]
+fn long_rests_heal_the_party_and_autosave() {
+    let mut harness = Harness::new(PARTY);
+    harness.record::<Autosave>();
+    let brom = harness.units[0];
+    harness.trigger(TakeDamage {
+        unit: brom,
+        amount: 30.,
+    });
+    assert!(harness.world().get::<Downed>(brom).is_some());
+
+    harness.trigger(Rest { long: true });
+    assert_eq!(reasons(&mut harness), [AutosaveReason::Rest]);
+    assert_eq!(harness.get::<Health>("Brom").0, 28.);
+    assert!(harness.world().get::<Downed>(brom).is_none());
+    // Enemies don't rest with the party.
+    assert_eq!(harness.get::<Health>("Goblin").0, 7.);
+
+    // The save lands in the harness's save directory.
+    for _ in 0..100 {
+        harness.advance(1);
+        if harness.world().resource::<PendingSaves>().0.is_empty() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let dir = harness.world().resource::<SaveDir>().0.clone();
+    assert!(scene_path(&dir, "Brom").exists());
+}
+
+#[test]
+fn defeating_the_last_enemy_autosaves() {
+    let mut harness = Harness::new(PARTY);
+    harness.play();
+    harness.record::<Autosave>();
+    let goblin = harness.unit("Goblin").unwrap();
+    harness.trigger(TakeDamage {
+        unit: goblin,
+        amount: 7.,
+    });
+    harness.advance(1);
+    assert_eq!(reasons(&mut harness), [AutosaveReason::CombatEnded]);
+}
+
+#[test]
+fn levelling_up_autosaves() {
+    let mut harness = Harness::new(PARTY);
+    harness.play();
+    harness.record::<Autosave>();
+    harness.advance(1);
+    assert_eq!(reasons(&mut harness), []);
+
+    let brom = harness.units[0];
+    harness.world().get_mut::<Level>(brom).unwrap().0 = 4;
+    harness.advance(1);
+    assert_eq!(reasons(&mut harness), [AutosaveReason::LevelUp]);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::Harness;
+use newtable::checks::{passive_score, Check, CheckResult, RollCheck};
+use newtable::combat::TakeDamage;
+use newtable::components::*;
+
+const ROGUE: &str = r#"(
+    seed: 11,
+    units: [
+        (name: "Ilsa", side: Player, level: 5, abilities: (15, 14, 12, 8, 13, 10), max_health: 30),
+    ],
+)"#;
+
+/// Rolls `check` for Ilsa and returns what it added to the d20.
+fn modifier(harness: &mut Harness, check: Check) -> i64 {
+    let unit = harness.unit("Ilsa").unwrap();
+    harness.trigger(RollCheck { unit, check });
+    let result = harness.take::<CheckResult>().pop().unwrap();
+    assert!((1..=20).contains(&result.roll));
+    result.total - result.roll
+}
+
+#[test]
+fn checks_and_saves_add_proficiency_where_the_sheet_has_it() {
+    let mut harness = Harness::new(ROGUE);
+    harness.record::<CheckResult>();
+    let ilsa = harness.unit("Ilsa").unwrap();
+    let mut unit = harness.world().entity_mut(ilsa);
+    unit.get_mut::<Stealth>().unwrap().0.proficiency = Proficiency::Expert;
+    unit.get_mut::<Wisdom>().unwrap().0.proficiency = Proficiency::Proficient;
+    unit.get_mut::<Strength>().unwrap().0.proficiency = Proficiency::Proficient;
+
+    // Level 5 is a +3 proficiency bonus; experts add it twice.
+    assert_eq!(modifier(&mut harness, Check::Skill(StatEnum::Stealth)), 7);
+    assert_eq!(modifier(&mut harness, Check::Skill(StatEnum::Arcana)), -1);
+    assert_eq!(modifier(&mut harness, Check::Save(StatEnum::Wisdom)), 4);
+    assert_eq!(modifier(&mut harness, Check::Save(StatEnum::Charisma)), 0);
+    // A plain check doesn't get the save's proficiency.
+    assert_eq!(
+        modifier(&mut harness, Check::Ability(StatEnum::Strength)),
+        2
+    );
+
+    let perception = &harness.get::<Perception>("Ilsa").0;
+    assert_eq!(passive_score(perception, 3), 11);
+}
+
+#[test]
+fn temporary_hit_points_take_damage_first() {
+    let mut harness = Harness::new(ROGUE);
+    let unit = harness.unit("Ilsa").unwrap();
+    harness.world().entity_mut(unit).insert(TempHealth(5.));
+
+    harness.trigger(TakeDamage { unit, amount: 3. });
+    assert_eq!(harness.get::<TempHealth>("Ilsa").0, 2.);
+    assert_eq!(harness.get::<Health>("Ilsa").0, 30.);
+
+    harness.trigger(TakeDamage { unit, amount: 6. });
+    assert_eq!(harness.get::<TempHealth>("Ilsa").0, 0.);
+    assert_eq!(harness.get::<Health>("Ilsa").0, 26.);
+}
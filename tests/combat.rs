@@ -0,0 +1,262 @@
+mod common;
+
+use bevy::ecs::world::Command;
+use bevy::prelude::*;
+use common::Harness;
+use newtable::combat::*;
+use newtable::components::*;
+use newtable::encounters::{Encounter, StartEncounter};
+use newtable::map::{BattleMap, GridPosition};
+use newtable::monsters::SpawnMonster;
+use newtable::simulation::simulate;
+use newtable::vision::{LightLevel, LightSource};
+use newtable::AppState;
+
+const V1: &str = include_str!("fixtures/saves/v1.scn.ron");
+
+#[test]
+fn initiative_orders_turns_and_skips_the_downed() {
+    let mut harness = Harness::new(
+        r#"(
+            seed: 3,
+            units: [
+                (name: "Brom", side: Player, abilities: (10, 10, 8, 10, 10, 10)),
+                (name: "Ilsa", side: Player, abilities: (10, 10, 18, 10, 10, 10)),
+                (name: "Goblin", abilities: (10, 10, 14, 10, 10, 10)),
+            ],
+        )"#,
+    );
+    harness.trigger(StartCombat);
+    let order = harness.world().resource::<TurnOrder>().order.clone();
+    assert_eq!(order.len(), 3);
+    assert_eq!(harness.world().resource::<TurnOrder>().round, 1);
+    let initiatives = order
+        .iter()
+        .map(|x| harness.world().get::<Initiative>(*x).unwrap().0)
+        .collect::<Vec<i64>>();
+    assert!(initiatives.windows(2).all(|x| x[0] >= x[1]));
+
+    // Seed 3 rolls Ilsa, then the goblin, then Brom.
+    let names = order
+        .iter()
+        .map(|x| harness.world().get::<UnitName>(*x).unwrap().0.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Ilsa", "Goblin", "Brom"]);
+
+    // The goblin dies on its turn, so it's skipped, and it leaves the
+    // order at the end of the round.
+    harness.trigger(TakeDamage {
+        unit: order[1],
+        amount: 100.,
+    });
+    harness.trigger(EndTurn);
+    assert_eq!(
+        harness.world().resource::<TurnOrder>().active(),
+        Some(order[2])
+    );
+    harness.trigger(EndTurn);
+    let turn_order = harness.world().resource::<TurnOrder>();
+    assert_eq!(turn_order.round, 2);
+    assert_eq!(turn_order.order, [order[0], order[2]]);
+    assert_eq!(turn_order.active(), Some(order[0]));
+}
+
+#[test]
+fn monsters_attack_with_their_stat_block() {
+    let mut harness = Harness::new(
+        r#"(seed: 11, units: [(name: "Brom", side: Player, max_health: 1000, ac: 12)])"#,
+    );
+    SpawnMonster("Goblin".to_string()).apply(harness.world());
+    harness.record::<AttackResult>();
+    assert_eq!(harness.get::<MaxHealth>("Goblin").0.total, 7.);
+    assert_eq!(harness.get::<ProficiencyBonus>("Goblin").0, 2);
+
+    let goblin = harness.unit("Goblin").unwrap();
+    let attacks = harness.get::<Children>("Goblin").to_vec();
+    let scimitar = attacks
+        .into_iter()
+        .find(|x| harness.app.world().get::<ItemName>(*x).unwrap().0 == "Scimitar")
+        .unwrap();
+    let brom = harness.units[0];
+    for _ in 0..50 {
+        harness.trigger(Attack::new(goblin, scimitar, brom));
+    }
+    let results = harness.take::<AttackResult>();
+    assert_eq!(results.len(), 50);
+    let mut damage = 0.;
+    for result in &results {
+        // Finesse uses Dexterity +2, plus proficiency +2.
+        assert_eq!(result.total, result.roll + 4);
+        assert_eq!(result.hit, result.roll != 1 && result.total >= 12);
+        assert_eq!(result.critical, result.roll == 20);
+        if result.hit {
+            let max = if result.critical { 16. } else { 8. };
+            assert!((3. ..=max).contains(&result.damage));
+        } else {
+            assert_eq!(result.damage, 0.);
+        }
+        damage += result.damage;
+    }
+    assert_eq!(harness.get::<Health>("Brom").0, 1000. - damage);
+}
+
+#[test]
+fn simulations_report_every_run_and_repeat_under_a_seed() {
+    let party = vec![V1.to_string()];
+    let monsters = vec!["Giant Rat".to_string(), "Giant Rat".to_string()];
+    let report = simulate(&party, &monsters, 40, 5).unwrap();
+    assert_eq!(report.runs, 40);
+    assert_eq!(report.wins + report.losses + report.draws, 40);
+    assert_eq!(report.characters.len(), 1);
+    // Two giant rats usually see off one first level character, and every
+    // loss leaves them downed.
+    assert_eq!((report.wins, report.losses, report.draws), (3, 37, 0));
+    assert_eq!(report.characters[0].downed, 37);
+    assert_eq!(report.win_rate(), 0.075);
+    assert_eq!(report.average_rounds(), 4.4);
+
+    let again = simulate(&party, &monsters, 40, 5).unwrap();
+    assert_eq!(report.wins, again.wins);
+    assert_eq!(report.rounds, again.rounds);
+    assert_eq!(report.health_left, again.health_left);
+
+    assert!(simulate(&party, &["Tarrasque".to_string()], 1, 5).is_err());
+}
+
+#[test]
+fn starting_an_encounter_spawns_enemies_and_enters_combat() {
+    let mut harness = Harness::new(r#"(seed: 2, units: [(name: "Brom", side: Player, level: 3)])"#);
+    let mut encounter = Encounter::default();
+    encounter.add("Goblin");
+    encounter.add("Goblin");
+    encounter.add("Wolf");
+    StartEncounter(encounter).apply(harness.world());
+    harness.advance(1);
+
+    let world = harness.world();
+    assert_eq!(world.resource::<State<AppState>>().get(), &AppState::InGame);
+    assert_eq!(
+        world.resource::<State<InGameState>>().get(),
+        &InGameState::Combat
+    );
+    let enemies = world
+        .query_filtered::<&UnitName, With<Enemy>>()
+        .iter(world)
+        .filter(|x| x.0 == "Goblin")
+        .count();
+    assert_eq!(enemies, 2);
+    assert_eq!(world.resource::<TurnOrder>().order.len(), 4);
+}
+
+#[test]
+fn attacks_check_range_reach_and_cover() {
+    let mut harness = Harness::new(
+        r#"(
+            seed: 4,
+            units: [
+                (name: "Ilsa", side: Player, max_health: 100),
+                (name: "Goblin", max_health: 1000),
+                (name: "Wolf", max_health: 1000),
+            ],
+        )"#,
+    );
+    harness.record::<AttackResult>();
+    let [ilsa, goblin, wolf] = harness.units[..] else {
+        unreachable!()
+    };
+    let longbow = harness.arm(ilsa, "Longbow");
+    let glaive = harness.arm(ilsa, "Glaive");
+    let longsword = harness.arm(ilsa, "Longsword");
+    let attack_from = |harness: &mut Harness, x: i32, with: Entity| {
+        harness
+            .world()
+            .entity_mut(goblin)
+            .insert(GridPosition::new(x, 0));
+        harness.take::<AttackResult>();
+        harness.trigger(Attack::new(ilsa, with, goblin));
+        harness.take::<AttackResult>().into_iter().next()
+    };
+    harness
+        .world()
+        .entity_mut(ilsa)
+        .insert(GridPosition::new(0, 0));
+    harness
+        .world()
+        .entity_mut(wolf)
+        .insert(GridPosition::new(0, 10));
+
+    // 50 ft is within the longbow's range, 200 ft is long range and 650 ft
+    // is too far to shoot at all.
+    assert!(!attack_from(&mut harness, 10, longbow).unwrap().disadvantage);
+    assert!(attack_from(&mut harness, 40, longbow).unwrap().disadvantage);
+    assert!(attack_from(&mut harness, 130, longbow).is_none());
+
+    // The glaive reaches 10 ft, the longsword only 5.
+    assert!(attack_from(&mut harness, 2, glaive).is_some());
+    assert!(attack_from(&mut harness, 2, longsword).is_none());
+    assert!(attack_from(&mut harness, 1, longsword).is_some());
+
+    // Shooting with an enemy next to you is harder.
+    harness
+        .world()
+        .entity_mut(wolf)
+        .insert(GridPosition::new(1, 1));
+    assert!(attack_from(&mut harness, 10, longbow).unwrap().disadvantage);
+    harness
+        .world()
+        .entity_mut(wolf)
+        .insert(GridPosition::new(0, 10));
+
+    // Behind a wall the goblin can't be hit at all.
+    harness.world().resource_mut::<BattleMap>().walls.extend([
+        GridPosition::new(5, -1),
+        GridPosition::new(5, 0),
+        GridPosition::new(5, 1),
+    ]);
+    let result = attack_from(&mut harness, 10, longbow).unwrap();
+    assert_eq!(result.cover, Some(Cover::Total));
+    assert!(!result.hit);
+}
+
+#[test]
+fn unseen_units_attack_with_advantage() {
+    let mut harness = Harness::new(
+        r#"(
+            units: [
+                (name: "Brom", side: Player, max_health: 1000),
+                (name: "Goblin", max_health: 1000),
+            ],
+        )"#,
+    );
+    harness.record::<AttackResult>();
+    let [brom, goblin] = harness.units[..] else {
+        unreachable!()
+    };
+    let longbow = harness.arm(brom, "Longbow");
+    let shortbow = harness.arm(goblin, "Shortbow");
+    harness.world().resource_mut::<BattleMap>().light = LightLevel::Dark;
+    harness
+        .world()
+        .entity_mut(brom)
+        .insert(GridPosition::new(0, 0));
+    harness
+        .world()
+        .entity_mut(goblin)
+        .insert((GridPosition::new(6, 0), DarkVision(Stat::new(60., vec![]))));
+    let attack = |harness: &mut Harness, from, with, to| {
+        harness.take::<AttackResult>();
+        harness.trigger(Attack::new(from, with, to));
+        harness.take::<AttackResult>().remove(0)
+    };
+
+    // In the dark only the goblin can see.
+    let result = attack(&mut harness, brom, longbow, goblin);
+    assert!(result.disadvantage && !result.advantage);
+    let result = attack(&mut harness, goblin, shortbow, brom);
+    assert!(result.advantage && !result.disadvantage);
+
+    // A torch shows them to each other.
+    harness.world().entity_mut(brom).insert(LightSource::TORCH);
+    let result = attack(&mut harness, brom, longbow, goblin);
+    assert!(!result.advantage && !result.disadvantage);
+}
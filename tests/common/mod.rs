@@ -0,0 +1,127 @@
+//! A headless app running the rules, set up from a `Scenario`.
+#![allow(dead_code)]
+
+use bevy::prelude::*;
+use newtable::components::UnitName;
+use newtable::items::WeaponCatalog;
+use newtable::saves::SaveDir;
+use newtable::scenario::Scenario;
+use newtable::{AppState, RulesPlugin};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An empty directory for a test to write into, left from any earlier run.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("newtable-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Every `E` triggered since `Harness::record::<E>`, oldest first.
+#[derive(Resource)]
+pub struct Recorded<E>(pub Vec<E>);
+
+impl<E> Default for Recorded<E> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+fn record<E: Event + Clone>(trigger: Trigger<E>, mut recorded: ResMut<Recorded<E>>) {
+    recorded.0.push(trigger.event().clone());
+}
+
+pub struct Harness {
+    pub app: App,
+    pub units: Vec<Entity>,
+}
+
+impl Harness {
+    /// Builds the app, spawns the scenario and runs one update so that items
+    /// are equipped and stats are up to date. Saves go to a scratch
+    /// directory of the harness's own.
+    pub fn new(scenario: &str) -> Self {
+        static HARNESSES: AtomicUsize = AtomicUsize::new(0);
+        let scenario = Scenario::from_ron(scenario).expect("scenario to be valid RON");
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, RulesPlugin));
+        let n = HARNESSES.fetch_add(1, Ordering::Relaxed);
+        app.insert_resource(SaveDir(scratch_dir(&format!("harness-{n}"))));
+        let units = scenario.spawn(app.world_mut());
+        let mut harness = Self { app, units };
+        harness.advance(1);
+        harness
+    }
+
+    pub fn advance(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Enters the game, as the load screen's Play button does.
+    pub fn play(&mut self) {
+        self.world()
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        self.advance(1);
+    }
+
+    pub fn world(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    /// The unit called `name`, if it's still alive.
+    pub fn unit(&mut self, name: &str) -> Option<Entity> {
+        let world = self.app.world_mut();
+        world
+            .query::<(Entity, &UnitName)>()
+            .iter(world)
+            .find(|x| x.1 .0 == name)
+            .map(|x| x.0)
+    }
+
+    pub fn get<T: Component>(&mut self, name: &str) -> &T {
+        let unit = self.unit(name).unwrap_or_else(|| panic!("{name} to exist"));
+        self.app
+            .world()
+            .get::<T>(unit)
+            .unwrap_or_else(|| panic!("{name} to have a {}", std::any::type_name::<T>()))
+    }
+
+    pub fn trigger(&mut self, event: impl Event) {
+        self.app.world_mut().trigger(event);
+        self.app.world_mut().flush();
+    }
+
+    /// Gives `unit` a weapon from the catalog and returns it.
+    pub fn arm(&mut self, unit: Entity, weapon: &str) -> Entity {
+        let world = self.world();
+        let data = world
+            .resource::<WeaponCatalog>()
+            .get(weapon)
+            .unwrap_or_else(|| panic!("{weapon} to be in the catalog"))
+            .clone();
+        let weapon = data.spawn(world);
+        world.entity_mut(unit).add_child(weapon);
+        weapon
+    }
+
+    /// Starts keeping every `E` that's triggered in `Recorded<E>`.
+    pub fn record<E: Event + Clone>(&mut self) {
+        self.app.init_resource::<Recorded<E>>().observe(record::<E>);
+    }
+
+    /// The `E`s triggered since they were last taken.
+    pub fn take<E: Event + Clone>(&mut self) -> Vec<E> {
+        std::mem::take(&mut self.world().resource_mut::<Recorded<E>>().0)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.app.world().resource::<SaveDir>().0);
+    }
+}
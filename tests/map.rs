@@ -0,0 +1,246 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{scratch_dir, Harness};
+use newtable::components::Cover;
+use newtable::map::{
+    cover, import_image, line_of_sight, list_maps, load_map, map_path, save_map, Background,
+    BattleMap, Door, GridPosition, ToggleDoor,
+};
+use newtable::migrations::MigrationError;
+use newtable::saves::{serialize_scene, spawn_scene, SaveError};
+use newtable::uvtt::{import_uvtt, Point, UvttFile};
+use newtable::vision::{LightLevel, LightSource};
+use std::collections::HashSet;
+use std::fs;
+
+const PARTY: &str = r#"(
+    units: [
+        (name: "Brom", side: Player),
+        (name: "Ilsa", side: Player),
+        (name: "Goblin"),
+    ],
+)"#;
+
+#[test]
+fn units_are_placed_on_free_squares() {
+    let mut harness = Harness::new(PARTY);
+    let width = harness.world().resource::<BattleMap>().width;
+    let brom = *harness.get::<GridPosition>("Brom");
+    let ilsa = *harness.get::<GridPosition>("Ilsa");
+    let goblin = *harness.get::<GridPosition>("Goblin");
+    assert_eq!(brom, GridPosition::new(0, 0));
+    assert_eq!(ilsa, GridPosition::new(0, 1));
+    assert_eq!(goblin, GridPosition::new(width - 1, 0));
+}
+
+#[test]
+fn grid_positions_are_saved() {
+    let mut harness = Harness::new(r#"(units: [(name: "Brom", side: Player)])"#);
+    let brom = harness.units[0];
+    harness
+        .world()
+        .entity_mut(brom)
+        .insert(GridPosition::new(7, 3));
+    let scene = serialize_scene(harness.world());
+    assert!(scene.contains("GridPosition"));
+    assert!(!scene.contains("Transform"));
+
+    harness.world().despawn(brom);
+    let loaded = spawn_scene(harness.world(), &scene).unwrap();
+    assert_eq!(
+        harness.world().get::<GridPosition>(loaded),
+        Some(&GridPosition::new(7, 3))
+    );
+}
+
+#[test]
+fn walls_block_sight_and_give_cover() {
+    let at = GridPosition::new;
+    let mut map = BattleMap::default();
+    assert!(line_of_sight(&map, at(0, 0), at(6, 3)));
+    assert_eq!(cover(&map, at(0, 0), at(6, 0)), None);
+
+    // A wall square right in front of the target.
+    map.walls.insert(at(5, 0));
+    assert!(!line_of_sight(&map, at(0, 0), at(6, 0)));
+    // From its bottom corner the attacker sees along the wall's edge.
+    assert_eq!(cover(&map, at(0, 0), at(6, 0)), Some(Cover::Half));
+    assert_eq!(cover(&map, at(0, 3), at(6, 0)), Some(Cover::Half));
+    assert_eq!(cover(&map, at(6, 5), at(6, 0)), None);
+
+    // A longer wall hides it completely.
+    map.walls.extend([at(5, 1), at(5, -1)]);
+    assert_eq!(cover(&map, at(0, 0), at(6, 0)), Some(Cover::Total));
+}
+
+#[test]
+fn maps_round_trip_through_ron() {
+    let at = GridPosition::new;
+    let mut map = BattleMap {
+        name: "Old Mill".to_string(),
+        width: 12,
+        height: 9,
+        background: Some(Background {
+            image: "maps/images/mill.png".to_string(),
+            pixels_per_square: 140.,
+            offset: (12., 30.5),
+        }),
+        light: LightLevel::Dim,
+        ..default()
+    };
+    map.walls.extend([at(3, 0), at(3, 1), at(3, 3)]);
+    map.doors.insert(at(3, 2), Door::Locked);
+    map.doors.insert(at(8, 4), Door::Open);
+    map.difficult.insert(at(5, 5));
+    map.hazards.insert(at(6, 6));
+    map.lit.insert(at(0, 0), LightLevel::Dark);
+    map.lights.insert(at(10, 2), LightSource::TORCH);
+
+    let dir = scratch_dir("map-round-trip");
+    let slot = save_map(&dir, &map).unwrap();
+    assert_eq!(slot, "Old-Mill");
+    assert_eq!(list_maps(&dir).unwrap(), ["Old-Mill"]);
+    assert_eq!(load_map(&dir, &slot).unwrap(), map);
+
+    // Saving again writes the same file, whatever order the sets are in.
+    let text = fs::read_to_string(map_path(&dir, &slot)).unwrap();
+    save_map(&dir, &load_map(&dir, &slot).unwrap()).unwrap();
+    assert_eq!(fs::read_to_string(map_path(&dir, &slot)).unwrap(), text);
+
+    fs::write(map_path(&dir, "future"), "(version: 99)").unwrap();
+    assert!(matches!(
+        load_map(&dir, "future"),
+        Err(SaveError::Version(MigrationError::TooNew(99)))
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Shrinking the map drops whatever is left outside it.
+    map.resize(8, 5);
+    assert_eq!((map.width, map.height), (8, 5));
+    assert_eq!(map.walls.len(), 3);
+    assert_eq!(map.doors.keys().collect::<Vec<_>>(), [&at(3, 2)]);
+    assert!(map.difficult.is_empty() && map.hazards.is_empty());
+    assert!(map.lights.is_empty());
+    assert_eq!(map.lit.len(), 1);
+}
+
+#[test]
+fn only_images_are_imported() {
+    let dir = scratch_dir("map-import");
+    let source = dir.join("cave.PNG");
+    fs::write(&source, b"not really a png").unwrap();
+    let assets = dir.join("assets");
+    assert_eq!(
+        import_image(&assets, &source).unwrap(),
+        "maps/images/cave.PNG"
+    );
+    assert!(assets.join("maps/images/cave.PNG").exists());
+
+    fs::write(dir.join("notes.txt"), "").unwrap();
+    assert!(import_image(&assets, &dir.join("notes.txt")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn closed_doors_block_until_opened() {
+    let at = GridPosition::new;
+    let mut harness = Harness::new(PARTY);
+    {
+        let mut map = harness.world().resource_mut::<BattleMap>();
+        map.walls.extend([at(2, 0), at(2, 2)]);
+        map.doors.insert(at(2, 1), Door::Closed);
+        map.doors.insert(at(4, 1), Door::Locked);
+    }
+    let map = harness.world().resource::<BattleMap>().clone();
+    assert!(!line_of_sight(&map, at(1, 1), at(3, 1)));
+    assert!(!map.is_open(at(2, 1)));
+
+    harness.trigger(ToggleDoor(at(2, 1)));
+    harness.trigger(ToggleDoor(at(4, 1)));
+    let map = harness.world().resource::<BattleMap>().clone();
+    assert_eq!(map.doors[&at(2, 1)], Door::Open);
+    assert!(line_of_sight(&map, at(1, 1), at(3, 1)));
+    assert!(map.is_open(at(2, 1)));
+    // Locked doors stay shut.
+    assert_eq!(map.doors[&at(4, 1)], Door::Locked);
+    assert!(!map.is_open(at(4, 1)));
+
+    harness.trigger(ToggleDoor(at(2, 1)));
+    assert_eq!(
+        harness.world().resource::<BattleMap>().doors[&at(2, 1)],
+        Door::Closed
+    );
+}
+
+/// A 6 by 4 cellar walled all round and split down the middle by a wall
+/// with a closed door in it, lit by a torch in the dark.
+const CELLAR: &str = r#"{
+    "format": 0.3,
+    "resolution": {
+        "map_origin": { "x": 0, "y": 0 },
+        "map_size": { "x": 6, "y": 4 },
+        "pixels_per_grid": 100
+    },
+    "line_of_sight": [
+        [{ "x": 0, "y": 0 }, { "x": 6, "y": 0 }, { "x": 6, "y": 4 }, { "x": 0, "y": 4 }, { "x": 0, "y": 0 }],
+        [{ "x": 3, "y": 0 }, { "x": 3, "y": 1 }],
+        [{ "x": 3, "y": 2 }, { "x": 3, "y": 4 }]
+    ],
+    "objects_line_of_sight": [],
+    "portals": [
+        {
+            "position": { "x": 3, "y": 1.5 },
+            "bounds": [{ "x": 3, "y": 1 }, { "x": 3, "y": 2 }],
+            "rotation": 1.5708,
+            "closed": true,
+            "freestanding": false
+        }
+    ],
+    "environment": { "baked_lighting": false, "ambient_light": "ff202020" },
+    "lights": [
+        { "position": { "x": 1.5, "y": 1.5 }, "range": 8, "intensity": 1, "color": "ffffcc88", "shadows": true }
+    ],
+    "image": "iVBORw0KGgo="
+}"#;
+
+#[test]
+fn universal_vtt_maps_import_ready_to_play() {
+    let at = GridPosition::new;
+    let dir = scratch_dir("map-uvtt");
+    let file = dir.join("Cellar.dd2vtt");
+    fs::write(&file, CELLAR).unwrap();
+    let assets = dir.join("assets");
+    let map = import_uvtt(&assets, &file).unwrap();
+
+    assert_eq!(map.name, "Cellar");
+    assert_eq!((map.width, map.height), (6, 4));
+    let background = map.background.as_ref().unwrap();
+    assert_eq!(background.image, "maps/images/Cellar.png");
+    assert_eq!(background.pixels_per_square, 100.);
+    assert!(assets.join("maps/images/Cellar.png").exists());
+
+    // The file counts rows from the top, the battle map from the bottom.
+    // The walls round the edge of the map don't take up any squares.
+    let walls = map.walls.iter().copied().collect::<HashSet<_>>();
+    assert_eq!(walls, HashSet::from([at(3, 0), at(3, 1), at(3, 3)]));
+    assert_eq!(map.doors.len(), 1);
+    assert_eq!(map.doors[&at(3, 2)], Door::Closed);
+    assert_eq!(map.light, LightLevel::Dark);
+    assert_eq!(map.lights[&at(1, 2)], LightSource::new(20., 20.));
+    assert!(!line_of_sight(&map, at(1, 2), at(5, 2)));
+
+    // It saves as a map like any other.
+    let slot = save_map(&dir, &map).unwrap();
+    assert_eq!(load_map(&dir, &slot).unwrap(), map);
+    fs::remove_dir_all(&dir).unwrap();
+
+    // A grid that starts inside the image starts as far into the background.
+    let mut uvtt = UvttFile::from_json(CELLAR).unwrap();
+    uvtt.resolution.map_origin = Point { x: 0.5, y: 0.25 };
+    let map = uvtt.to_map("Cellar", Some("cellar.png".into())).unwrap();
+    assert_eq!(map.background.unwrap().offset, (50., 25.));
+
+    uvtt.environment.ambient_light = "2é20202".to_string();
+    assert!(uvtt.to_map("Cellar", None).is_err());
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use bevy::utils::HashSet;
+use common::Harness;
+use newtable::combat::{AttackResult, StartCombat};
+use newtable::map::{BattleMap, DiagonalRule, GridPosition};
+use newtable::movement::{find_path, MoveUnit, Movement, OpportunityAttacks};
+
+fn at(x: i32, y: i32) -> GridPosition {
+    GridPosition::new(x, y)
+}
+
+#[test]
+fn paths_go_around_walls_and_units() {
+    let mut map = BattleMap::default();
+    map.walls.extend((0..4).map(|y| at(2, y)));
+    let none = HashSet::default();
+    let path = find_path(&map, DiagonalRule::Uniform, at(0, 1), at(4, 1), &none).unwrap();
+    assert_eq!(path.cells.last(), Some(&at(4, 1)));
+    assert!(path.cells.iter().all(|x| !map.walls.contains(x)));
+    // Up past the end of the wall and back down, without cutting its
+    // corners.
+    assert_eq!(path.cost, 40.);
+    assert!(path.cells.contains(&at(1, 4)) && path.cells.contains(&at(3, 4)));
+
+    // A unit standing in the gap blocks it.
+    let occupied = HashSet::from_iter([at(2, 4)]);
+    let path = find_path(&map, DiagonalRule::Uniform, at(0, 1), at(4, 1), &occupied).unwrap();
+    assert!(!path.cells.contains(&at(2, 4)));
+    assert_eq!(path.cost, 40.);
+    assert_eq!(
+        find_path(&map, DiagonalRule::Uniform, at(0, 1), at(2, 1), &none),
+        None
+    );
+}
+
+#[test]
+fn diagonals_and_difficult_terrain_cost_more() {
+    let mut map = BattleMap::default();
+    let none = HashSet::default();
+    let cost = |map: &BattleMap, rule| {
+        find_path(map, rule, at(0, 0), at(3, 3), &none)
+            .unwrap()
+            .cost
+    };
+    assert_eq!(cost(&map, DiagonalRule::Uniform), 15.);
+    assert_eq!(cost(&map, DiagonalRule::Alternating), 20.);
+    assert_eq!(at(0, 0).distance(at(4, 4), DiagonalRule::Alternating), 30.);
+
+    map.difficult.extend([at(1, 1), at(2, 2)]);
+    let path = find_path(&map, DiagonalRule::Uniform, at(0, 0), at(3, 0), &none).unwrap();
+    assert_eq!(path.cost, 15.);
+    // Through the difficult squares costs 25; around them is cheaper.
+    assert_eq!(cost(&map, DiagonalRule::Uniform), 20.);
+}
+
+const DUEL: &str = r#"(
+    seed: 1,
+    units: [
+        (name: "Brom", side: Player, speed: 30),
+        (name: "Goblin"),
+    ],
+)"#;
+
+fn duel() -> Harness {
+    let mut harness = Harness::new(DUEL);
+    let [brom, goblin] = harness.units[..] else {
+        unreachable!()
+    };
+    harness.world().entity_mut(brom).insert(at(5, 5));
+    harness.world().entity_mut(goblin).insert(at(6, 5));
+    harness
+}
+
+#[test]
+fn moves_are_limited_by_movement_left() {
+    let mut harness = duel();
+    let brom = harness.units[0];
+    harness.trigger(StartCombat);
+    assert_eq!(harness.get::<Movement>("Brom").0, 30.);
+
+    harness.world().entity_mut(brom).insert(Movement(10.));
+    harness.trigger(MoveUnit {
+        unit: brom,
+        to: at(5, 8),
+    });
+    assert_eq!(*harness.get::<GridPosition>("Brom"), at(5, 5));
+
+    harness.trigger(MoveUnit {
+        unit: brom,
+        to: at(5, 7),
+    });
+    assert_eq!(*harness.get::<GridPosition>("Brom"), at(5, 7));
+    assert_eq!(harness.get::<Movement>("Brom").0, 0.);
+}
+
+#[test]
+fn leaving_reach_provokes_opportunity_attacks() {
+    let mut harness = duel();
+    let [brom, goblin] = harness.units[..] else {
+        unreachable!()
+    };
+    harness.arm(goblin, "Club");
+    let glaive = harness.arm(goblin, "Glaive");
+    // Stepping around the goblin stays in its reach.
+    harness.trigger(MoveUnit {
+        unit: brom,
+        to: at(6, 6),
+    });
+    assert!(harness
+        .world()
+        .resource::<OpportunityAttacks>()
+        .0
+        .is_empty());
+
+    harness.trigger(MoveUnit {
+        unit: brom,
+        to: at(6, 8),
+    });
+    let attacks = harness.world().resource::<OpportunityAttacks>().0.clone();
+    assert_eq!(attacks.len(), 1);
+    assert_eq!(attacks[0].attacker, goblin);
+    assert_eq!(attacks[0].target, brom);
+    // The glaive's reach is what Brom left, from the square before his
+    // last, and the attack lands there even though he's moved on.
+    assert_eq!(attacks[0].with, Some(glaive));
+    assert_eq!(attacks[0].at, at(5, 7));
+    harness.record::<AttackResult>();
+    harness.trigger(attacks[0].attack().unwrap());
+    let results = harness.take::<AttackResult>();
+    assert_eq!(results.len(), 1);
+    assert_eq!((results[0].from, results[0].to), (goblin, brom));
+}
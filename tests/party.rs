@@ -0,0 +1,166 @@
+mod common;
+
+use common::{scratch_dir, Harness};
+use newtable::components::*;
+use newtable::items::{ItemsEnum, SpawnItem};
+use newtable::party::{
+    add_member, party_path, read_party, spawn_party, ActiveCharacter, CharacterSlot, PartyFile,
+    PartySave,
+};
+use newtable::saves::{delete_slot, rename_slot, spawn_slot, SaveError};
+use std::fs;
+
+const PARTY: &str = r#"(
+    units: [
+        (name: "Ilsa", side: Player, max_health: 14),
+        (name: "Brom", side: Player, max_health: 20),
+        (name: "Goblin", max_health: 7),
+    ],
+)"#;
+
+fn max_health(harness: &mut Harness, name: &str) -> f64 {
+    harness.get::<MaxHealth>(name).0.total
+}
+
+#[test]
+fn items_go_to_the_active_character() {
+    let mut harness = Harness::new(PARTY);
+    // Nobody was picked, so the first by name is active.
+    let brom = harness.unit("Brom");
+    assert_eq!(harness.world().resource::<ActiveCharacter>().0, brom);
+
+    harness.trigger(SpawnItem(ItemsEnum::RingOfHealth));
+    harness.advance(1);
+    assert_eq!(max_health(&mut harness, "Brom"), 30.);
+    assert_eq!(max_health(&mut harness, "Ilsa"), 14.);
+
+    let ilsa = harness.unit("Ilsa");
+    harness.world().resource_mut::<ActiveCharacter>().0 = ilsa;
+    harness.trigger(SpawnItem(ItemsEnum::RingOfHealth));
+    harness.advance(1);
+    assert_eq!(max_health(&mut harness, "Brom"), 30.);
+    assert_eq!(max_health(&mut harness, "Ilsa"), 24.);
+
+    // When the active character leaves, someone else takes over.
+    harness.world().despawn(ilsa.unwrap());
+    harness.advance(1);
+    assert_eq!(harness.world().resource::<ActiveCharacter>().0, brom);
+}
+
+#[test]
+fn parties_save_each_member_and_load_together() {
+    let dir = scratch_dir("party");
+    let mut harness = Harness::new(PARTY);
+    let ilsa = harness.unit("Ilsa");
+    harness.world().resource_mut::<ActiveCharacter>().0 = ilsa;
+    let save = PartySave::from_world(harness.world(), Some("Iron Company"));
+    assert_eq!(save.slot, "Iron-Company");
+    save.write(&dir).unwrap();
+
+    let file = read_party(&dir, "Iron-Company").unwrap();
+    assert_eq!(file.members, ["Brom", "Ilsa"]);
+    assert_eq!(file.active.as_deref(), Some("Ilsa"));
+    // Each member's save holds just them, so it also loads on its own.
+    let mut alone = Harness::new("(units: [])");
+    let brom = spawn_slot(alone.world(), &dir, "Brom").unwrap();
+    assert_eq!(alone.world().get::<UnitName>(brom).unwrap().0, "Brom");
+
+    let mut loaded = Harness::new("(units: [])");
+    let members = spawn_party(loaded.world(), &dir, "Iron-Company").unwrap();
+    assert_eq!(members.len(), 2);
+    let ilsa = loaded.unit("Ilsa").unwrap();
+    assert_eq!(loaded.world().resource::<ActiveCharacter>().0, Some(ilsa));
+    assert_eq!(
+        loaded.world().get::<CharacterSlot>(ilsa),
+        Some(&CharacterSlot("Ilsa".to_string()))
+    );
+    assert!(loaded.unit("Goblin").is_none());
+    // Loading it again doesn't double up the party.
+    spawn_party(loaded.world(), &dir, "Iron-Company").unwrap();
+    let world = loaded.world();
+    let mut players = world.query_filtered::<(), bevy::prelude::With<Player>>();
+    assert_eq!(players.iter(world).count(), 2);
+
+    // Members who were already in the party stay when another can't load.
+    let broken = PartyFile {
+        members: vec!["Ilsa".into(), "Brom".into(), "Nobody".into()],
+        ..Default::default()
+    };
+    let text = ron::to_string(&broken).unwrap();
+    fs::write(party_path(&dir, "Broken"), text).unwrap();
+    let mut failed = Harness::new("(units: [])");
+    add_member(failed.world(), &dir, "Ilsa").unwrap();
+    assert!(spawn_party(failed.world(), &dir, "Broken").is_err());
+    assert!(failed.unit("Ilsa").is_some());
+    assert!(failed.unit("Brom").is_none());
+
+    // A member without a save keeps the whole party from loading.
+    fs::remove_file(dir.join("Brom.scn.ron")).unwrap();
+    let mut failed = Harness::new("(units: [])");
+    assert!(spawn_party(failed.world(), &dir, "Iron-Company").is_err());
+    assert!(failed.unit("Ilsa").is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn members_with_the_same_name_save_to_their_own_slots() {
+    let dir = scratch_dir("party-twins");
+    let mut harness = Harness::new(
+        r#"(
+            units: [
+                (name: "Brom", side: Player, max_health: 20),
+                (name: "Brom", side: Player, max_health: 30),
+            ],
+        )"#,
+    );
+    let save = PartySave::from_world(harness.world(), None);
+    assert_eq!(save.file.members, ["Brom", "Brom-copy"]);
+    save.write(&dir).unwrap();
+
+    let mut loaded = Harness::new("(units: [])");
+    let members = spawn_party(loaded.world(), &dir, &save.slot).unwrap();
+    let mut health = members
+        .iter()
+        .map(|x| loaded.world().get::<MaxHealth>(*x).unwrap().0.total)
+        .collect::<Vec<_>>();
+    health.sort_by(f64::total_cmp);
+    assert_eq!(health, [20., 30.]);
+
+    // Saving again keeps each in the slot they were given.
+    let again = PartySave::from_world(harness.world(), None);
+    assert_eq!(again.file.members, save.file.members);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn saved_parties_follow_renamed_members_and_keep_deleted_ones() {
+    let dir = scratch_dir("party-slots");
+    let mut harness = Harness::new(PARTY);
+    let ilsa = harness.unit("Ilsa");
+    harness.world().resource_mut::<ActiveCharacter>().0 = ilsa;
+    PartySave::from_world(harness.world(), Some("Iron Company"))
+        .write(&dir)
+        .unwrap();
+
+    assert_eq!(rename_slot(&dir, "Ilsa", "Ilsa Vane").unwrap(), "Ilsa-Vane");
+    let file = read_party(&dir, "Iron-Company").unwrap();
+    assert_eq!(file.members, ["Brom", "Ilsa-Vane"]);
+    assert_eq!(file.active.as_deref(), Some("Ilsa-Vane"));
+    let mut loaded = Harness::new("(units: [])");
+    assert_eq!(
+        spawn_party(loaded.world(), &dir, "Iron-Company")
+            .unwrap()
+            .len(),
+        2
+    );
+
+    assert!(matches!(
+        delete_slot(&dir, "Brom"),
+        Err(SaveError::InParty(slot, party)) if slot == "Brom" && party == "Iron-Company"
+    ));
+    assert!(dir.join("Brom.scn.ron").exists());
+    fs::remove_file(party_path(&dir, "Iron-Company")).unwrap();
+    delete_slot(&dir, "Brom").unwrap();
+    assert!(!dir.join("Brom.scn.ron").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
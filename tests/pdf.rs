@@ -0,0 +1,108 @@
+mod common;
+
+use bevy::prelude::*;
+use common::{scratch_dir, Harness};
+use newtable::components::*;
+use newtable::pdf::{export_sheet, PrintedSheet};
+use newtable::saves::SaveError;
+use std::fs;
+
+const ROGUE: &str = r#"(
+    units: [
+        (name: "Ilsa", side: Player, level: 5, abilities: (15, 14, 12, 8, 13, 10), max_health: 30, ac: 14),
+    ],
+)"#;
+
+/// Ilsa with a dagger she's proficient with, a longbow she isn't, and a
+/// feature and a spell.
+fn equipped() -> (Harness, Entity) {
+    let mut harness = Harness::new(ROGUE);
+    let ilsa = harness.unit("Ilsa").unwrap();
+    harness.arm(ilsa, "Dagger");
+    harness.arm(ilsa, "Longbow");
+    let world = harness.world();
+    let feature = world
+        .spawn(Feature {
+            name: "Sneak Attack".to_string(),
+            description: "Once per turn, deal an extra 3d6 damage.".to_string(),
+        })
+        .id();
+    let spell = world
+        .spawn((Spell, SpellName("Mage Hand".to_string())))
+        .id();
+    let mut unit = world.entity_mut(ilsa);
+    unit.add_child(feature).add_child(spell);
+    unit.insert((
+        SimpleWeaponProficiency(Proficiency::Proficient),
+        TempHealth(4.),
+    ));
+    unit.get_mut::<Stealth>().unwrap().0.proficiency = Proficiency::Expert;
+    unit.get_mut::<Dexterity>().unwrap().0.proficiency = Proficiency::Proficient;
+    (harness, ilsa)
+}
+
+#[test]
+fn printed_sheets_total_up_the_character() {
+    let (mut harness, ilsa) = equipped();
+    let sheet = PrintedSheet::from_world(harness.world(), ilsa).unwrap();
+    assert_eq!(sheet.name, "Ilsa");
+    assert_eq!((sheet.level, sheet.proficiency_bonus), (5, 3));
+    assert_eq!((sheet.armor_class, sheet.temp_health), (14., 4.));
+
+    let dexterity = &sheet.abilities[2];
+    assert_eq!(dexterity.stat, StatEnum::Dexterity);
+    assert_eq!(
+        (dexterity.score, dexterity.modifier, dexterity.save),
+        (12., 1, 4)
+    );
+    assert_eq!(sheet.skills.len(), 18);
+    let stealth = sheet
+        .skills
+        .iter()
+        .find(|x| x.stat == StatEnum::Stealth)
+        .unwrap();
+    assert_eq!(stealth.modifier, 7);
+    assert_eq!(sheet.passives[0], ("Perception".to_string(), 11));
+
+    // Finesse takes the better of Strength and Dexterity.
+    let attacks = sheet
+        .attacks
+        .iter()
+        .map(|x| (x.name.as_str(), x.bonus, x.damage.as_str()))
+        .collect::<Vec<_>>();
+    assert!(attacks.contains(&("Dagger", 5, "1d4+2 Piercing")));
+    assert!(attacks.contains(&("Longbow", 1, "1d8+1 Piercing")));
+    assert_eq!(sheet.equipment.len(), 2);
+    assert_eq!(sheet.features[0].name, "Sneak Attack");
+    assert_eq!(sheet.spells, ["Mage Hand"]);
+}
+
+#[test]
+fn sheets_export_to_a_two_page_pdf() {
+    let dir = scratch_dir("pdf");
+    let (mut harness, ilsa) = equipped();
+    let path = export_sheet(harness.world(), &dir, ilsa).unwrap();
+    assert_eq!(path, dir.join("Ilsa.pdf"));
+
+    let bytes = fs::read(&path).unwrap();
+    assert!(bytes.starts_with(b"%PDF-"));
+    let text = String::from_utf8_lossy(&bytes);
+    assert!(text.contains("/Count 2"));
+    for shown in [
+        "(Ilsa)",
+        "(Sneak Attack)",
+        "(Mage Hand)",
+        "(Dagger)",
+        "(+7)",
+    ] {
+        assert!(text.contains(shown), "{shown} to be printed");
+    }
+
+    // Anything that isn't a character has no sheet to export.
+    let item = harness.world().spawn(ItemName("Rope".to_string())).id();
+    assert!(matches!(
+        export_sheet(harness.world(), &dir, item),
+        Err(SaveError::NotACharacter(x)) if x == item
+    ));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use bevy::prelude::Children;
+use common::Harness;
+use newtable::combat::TakeDamage;
+use newtable::components::*;
+use newtable::RulesRng;
+
+const PARTY: &str = r#"(
+    seed: 7,
+    units: [
+        (name: "Brom", side: Player, level: 3, abilities: (15, 14, 12, 8, 13, 10), max_health: 28),
+        (name: "Goblin", max_health: 7, ac: 15),
+    ],
+)"#;
+
+#[test]
+fn ring_of_health_raises_max_health_by_10() {
+    let mut harness = Harness::new(
+        r#"(units: [(name: "Brom", side: Player, max_health: 20, items: [RingOfHealth])])"#,
+    );
+    assert_eq!(harness.get::<MaxHealth>("Brom").0.total, 30.);
+    assert_eq!(harness.get::<MaxHealth>("Brom").0.base, 20.);
+    assert_eq!(harness.get::<Children>("Brom").len(), 1);
+    // Equipping doesn't heal.
+    assert_eq!(harness.get::<Health>("Brom").0, 20.);
+}
+
+#[test]
+fn proficiency_bonus_follows_level() {
+    let bonuses = (1..=20)
+        .map(|x| ProficiencyBonus::from_level(x).0)
+        .collect::<Vec<i64>>();
+    assert_eq!(
+        bonuses,
+        [2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6]
+    );
+
+    let mut harness = Harness::new(PARTY);
+    assert_eq!(harness.get::<ProficiencyBonus>("Brom").0, 2);
+    assert_eq!(harness.get::<Level>("Brom").0, 3);
+}
+
+#[test]
+fn ability_modifiers_round_down() {
+    let modifier = |score: f64| {
+        Ability {
+            stat: Stat::new(score, vec![]),
+            proficiency: Proficiency::None,
+        }
+        .calculate_modifier()
+    };
+    let modifiers = (1..=30).map(|x| modifier(x as f64)).collect::<Vec<f64>>();
+    assert_eq!(modifiers[0], -5.);
+    assert_eq!(modifiers[7], -1.);
+    assert_eq!(modifiers[8], -1.);
+    assert_eq!(modifiers[9], 0.);
+    assert_eq!(modifiers[10], 0.);
+    assert_eq!(modifiers[11], 1.);
+    assert_eq!(modifiers[29], 10.);
+
+    let mut harness = Harness::new(PARTY);
+    assert_eq!(harness.get::<Strength>("Brom").0.calculate_modifier(), 2.);
+    assert_eq!(
+        harness.get::<Intelligence>("Brom").0.calculate_modifier(),
+        -1.
+    );
+}
+
+#[test]
+fn stat_totals_apply_every_mod_type() {
+    let total = |replace, best, add, mult, parent| {
+        let mut stat = Stat::new(10., vec![]);
+        let changed = stat.calculate_total(replace, best, add, mult, parent);
+        (stat.total, changed)
+    };
+    // Add
+    assert_eq!(total(None, None, 3., 0., 0.), (13., true));
+    // Mult adds up percentages of the base, after adds and the parent
+    // modifier.
+    assert_eq!(total(None, None, 0., 0.5, 0.), (15., true));
+    assert_eq!(total(None, None, 2., 0.5, 2.), (21., true));
+    // Replace wins over everything else.
+    assert_eq!(total(Some(19.), Some(25.), 5., 1., 3.), (19., true));
+    // BestOf only raises the total.
+    assert_eq!(total(None, Some(19.), 0., 0., 0.), (19., true));
+    assert_eq!(total(None, Some(8.), 0., 0., 0.), (10., false));
+    // Nothing changed.
+    assert_eq!(total(None, None, 0., 0., 0.), (10., false));
+
+    let mut stat = Stat::new(10., vec![]);
+    stat.calculate_total(Some(19.), None, 0., 0., 0.);
+    assert!(!stat.calculate_total(Some(19.), None, 0., 0., 0.));
+}
+
+#[test]
+fn enemies_die_at_zero_health() {
+    let mut harness = Harness::new(PARTY);
+    let goblin = harness.unit("Goblin").unwrap();
+    harness.trigger(TakeDamage {
+        unit: goblin,
+        amount: 6.,
+    });
+    assert_eq!(harness.get::<Health>("Goblin").0, 1.);
+
+    harness.trigger(TakeDamage {
+        unit: goblin,
+        amount: 1.,
+    });
+    assert_eq!(harness.unit("Goblin"), None);
+}
+
+#[test]
+fn players_drop_instead_of_despawning() {
+    let mut harness = Harness::new(PARTY);
+    // Units are spawned in scenario order.
+    let brom = harness.units[0];
+    harness.trigger(TakeDamage {
+        unit: brom,
+        amount: 30.,
+    });
+    assert_eq!(harness.get::<Health>("Brom").0, -2.);
+
+    // Damage of twice the maximum is instant death, which isn't handled
+    // yet: the player stays on the field.
+    harness.trigger(TakeDamage {
+        unit: brom,
+        amount: 60.,
+    });
+    harness.advance(1);
+    assert_eq!(harness.get::<Health>("Brom").0, -62.);
+}
+
+#[test]
+fn seeded_scenarios_roll_the_same() {
+    let rolls = |seed: u64| {
+        let mut harness = Harness::new(&format!("(seed: {seed})"));
+        let dice = Dice {
+            dice_type: DiceType::D20,
+            number: 1,
+        };
+        let mut rng = harness.world().resource_mut::<RulesRng>();
+        (0..10).map(|_| dice.roll(&mut rng.0)).collect::<Vec<i64>>()
+    };
+    assert_eq!(rolls(7), rolls(7));
+    assert_ne!(rolls(7), rolls(8));
+    assert!(rolls(7).iter().all(|x| (1..=20).contains(x)));
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use common::Harness;
+use newtable::components::*;
+use newtable::map::{BattleMap, DiagonalRule, GridPosition};
+use newtable::vision::*;
+
+#[test]
+fn light_sources_brighten_squares_they_can_reach() {
+    let at = GridPosition::new;
+    let mut map = BattleMap {
+        light: LightLevel::Dark,
+        ..default()
+    };
+    map.lit.insert(at(0, 5), LightLevel::Dim);
+    let torch = [(at(0, 0), LightSource::TORCH)];
+    let rule = DiagonalRule::Uniform;
+    assert_eq!(light_level(&map, rule, torch, at(4, 0)), LightLevel::Bright);
+    assert_eq!(light_level(&map, rule, torch, at(8, 0)), LightLevel::Dim);
+    assert_eq!(light_level(&map, rule, torch, at(9, 0)), LightLevel::Dark);
+    assert_eq!(light_level(&map, rule, [], at(0, 5)), LightLevel::Dim);
+
+    // Walls keep the light in, but lights on the map shine all the same.
+    map.walls.insert(at(2, 0));
+    assert_eq!(light_level(&map, rule, torch, at(4, 0)), LightLevel::Dark);
+    map.lights.insert(at(6, 0), LightSource::CANDLE);
+    assert_eq!(light_level(&map, rule, torch, at(4, 0)), LightLevel::Dim);
+
+    // Darkvision sees one step brighter, out to its range.
+    assert_eq!(perceived(LightLevel::Dark, 60., 60.), LightLevel::Dim);
+    assert_eq!(perceived(LightLevel::Dim, 30., 60.), LightLevel::Bright);
+    assert_eq!(perceived(LightLevel::Dark, 65., 60.), LightLevel::Dark);
+    assert_eq!(perceived(LightLevel::Dim, 5., 0.), LightLevel::Dim);
+}
+
+#[test]
+fn players_see_what_their_light_and_darkvision_show() {
+    let mut harness = Harness::new(
+        r#"(
+            units: [
+                (name: "Brom", side: Player),
+                (name: "Goblin"),
+            ],
+        )"#,
+    );
+    let [brom, goblin] = harness.units[..] else {
+        unreachable!()
+    };
+    harness.world().resource_mut::<BattleMap>().light = LightLevel::Dark;
+    harness
+        .world()
+        .entity_mut(brom)
+        .insert(GridPosition::new(0, 0));
+    harness
+        .world()
+        .entity_mut(goblin)
+        .insert((GridPosition::new(6, 0), DarkVision(Stat::new(60., vec![]))));
+    harness.advance(1);
+
+    let can_see = |harness: &mut Harness, viewer, target| {
+        let mut state = SystemState::<Vision>::new(harness.world());
+        state.get(harness.world()).can_see(viewer, target)
+    };
+    assert!(harness.get::<FogOfWar>("Brom").visible.is_empty());
+    assert!(!can_see(&mut harness, brom, goblin));
+    assert!(can_see(&mut harness, goblin, brom));
+
+    // A torch lights 40 ft around Brom.
+    harness.world().entity_mut(brom).insert(LightSource::TORCH);
+    harness.advance(1);
+    let fog = harness.get::<FogOfWar>("Brom");
+    assert!(fog.visible.contains(&GridPosition::new(8, 0)));
+    assert!(!fog.visible.contains(&GridPosition::new(9, 0)));
+    assert!(can_see(&mut harness, brom, goblin));
+
+    // Once it's out, Brom still remembers what he saw.
+    harness.world().entity_mut(brom).remove::<LightSource>();
+    harness.advance(1);
+    let fog = harness.get::<FogOfWar>("Brom");
+    assert!(fog.visible.is_empty());
+    assert!(fog.explored.contains(&GridPosition::new(8, 0)));
+}